@@ -0,0 +1,21 @@
+use arc_swap::ArcSwap;
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+use std::sync::Arc;
+use tipi_lang::compiler::compile;
+use tipi_lang::vm::interpret;
+
+// the tipi programs in benches/programs, compiled once and run in every iteration
+fn programs(c: &mut Criterion) {
+    for name in ["loop", "calls", "nested_calls", "lists"] {
+        let path = format!("{}/benches/programs/{}.tp", env!("CARGO_MANIFEST_DIR"), name);
+        let source = std::fs::read_to_string(&path).unwrap();
+        let registry = ArcSwap::from(Arc::new(compile(&source).unwrap()));
+        c.bench_function(name, |b| {
+            b.iter(|| black_box(interpret(registry.load(), "main").unwrap()))
+        });
+    }
+}
+
+criterion_group!(benches, programs);
+criterion_main!(benches);
@@ -0,0 +1,163 @@
+use crate::builtins::{FunctionMap, Parameter, Signature, add, expected};
+use crate::compiler::tokens::TokenType::{Date, DateTime, I64, StringType};
+use crate::errors::RuntimeError;
+use crate::value::{Value, string};
+use crate::vm::Vm;
+use chrono::{Datelike, Timelike};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+pub(crate) fn datetime_functions() -> FunctionMap {
+    let mut datetime_functions: FunctionMap = HashMap::new();
+    let functions = &mut datetime_functions;
+    add(functions, "year", Signature::new(vec![], I64, datetime_year));
+    add(functions, "month", Signature::new(vec![], I64, datetime_month));
+    add(functions, "day", Signature::new(vec![], I64, datetime_day));
+    add(functions, "hour", Signature::new(vec![], I64, datetime_hour));
+    add(functions, "minute", Signature::new(vec![], I64, datetime_minute));
+    add(functions, "second", Signature::new(vec![], I64, datetime_second));
+    add(functions, "weekday", Signature::new(vec![], I64, datetime_weekday));
+    add(functions, "date", Signature::new(vec![], Date, datetime_date));
+    add(
+        functions,
+        "format",
+        Signature::new(
+            vec![Parameter::new("pattern", StringType)],
+            StringType,
+            datetime_format,
+        ),
+    );
+    add(
+        functions,
+        "to_timezone",
+        Signature::new(
+            vec![Parameter::new("timezone", StringType)],
+            DateTime,
+            datetime_to_timezone,
+        ),
+    );
+    add(functions, "to_utc", Signature::new(vec![], DateTime, datetime_to_utc));
+    datetime_functions
+}
+
+pub(crate) fn date_functions() -> FunctionMap {
+    let mut date_functions: FunctionMap = HashMap::new();
+    let functions = &mut date_functions;
+    add(functions, "year", Signature::new(vec![], I64, date_year));
+    add(functions, "month", Signature::new(vec![], I64, date_month));
+    add(functions, "day", Signature::new(vec![], I64, date_day));
+    add(functions, "weekday", Signature::new(vec![], I64, date_weekday));
+    add(
+        functions,
+        "format",
+        Signature::new(
+            vec![Parameter::new("pattern", StringType)],
+            StringType,
+            date_format,
+        ),
+    );
+    date_functions
+}
+
+pub(crate) fn duration_functions() -> FunctionMap {
+    let mut duration_functions: FunctionMap = HashMap::new();
+    let functions = &mut duration_functions;
+    add(functions, "days", Signature::new(vec![], I64, duration_days));
+    add(functions, "hours", Signature::new(vec![], I64, duration_hours));
+    add(functions, "minutes", Signature::new(vec![], I64, duration_minutes));
+    add(functions, "seconds", Signature::new(vec![], I64, duration_seconds));
+    add(
+        functions,
+        "milliseconds",
+        Signature::new(vec![], I64, duration_milliseconds),
+    );
+    duration_functions
+}
+
+macro_rules! component {
+    ($name:ident, $variant:ident, $expected:literal, |$v:ident| $body:expr) => {
+        fn $name(_vm: &Vm, self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+            match self_val {
+                Value::$variant($v) => Ok(Value::I64($body as i64)),
+                _ => Err(expected($expected)),
+            }
+        }
+    };
+}
+
+component!(datetime_year, DateTime, "datetime", |d| d.year());
+component!(datetime_month, DateTime, "datetime", |d| d.month());
+component!(datetime_day, DateTime, "datetime", |d| d.day());
+component!(datetime_hour, DateTime, "datetime", |d| d.hour());
+component!(datetime_minute, DateTime, "datetime", |d| d.minute());
+component!(datetime_second, DateTime, "datetime", |d| d.second());
+component!(datetime_weekday, DateTime, "datetime", |d| d
+    .weekday()
+    .number_from_monday());
+component!(date_year, Date, "date", |d| d.year());
+component!(date_month, Date, "date", |d| d.month());
+component!(date_day, Date, "date", |d| d.day());
+component!(date_weekday, Date, "date", |d| d.weekday().number_from_monday());
+component!(duration_days, Duration, "duration", |d| d.num_days());
+component!(duration_hours, Duration, "duration", |d| d.num_hours());
+component!(duration_minutes, Duration, "duration", |d| d.num_minutes());
+component!(duration_seconds, Duration, "duration", |d| d.num_seconds());
+component!(duration_milliseconds, Duration, "duration", |d| d
+    .num_milliseconds());
+
+fn datetime_date(_vm: &Vm, self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+    match self_val {
+        Value::DateTime(d) => Ok(Value::Date(d.date_naive())),
+        _ => Err(expected("datetime")),
+    }
+}
+
+fn datetime_format(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    match (self_val, args.first()) {
+        (Value::DateTime(d), Some(Value::String(pattern))) => {
+            format(pattern, |s| write!(s, "{}", d.format(pattern)))
+        }
+        _ => Err(expected("datetime")),
+    }
+}
+
+fn date_format(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    match (self_val, args.first()) {
+        (Value::Date(d), Some(Value::String(pattern))) => {
+            format(pattern, |s| write!(s, "{}", d.format(pattern)))
+        }
+        _ => Err(expected("date")),
+    }
+}
+
+// chrono reports invalid patterns as a formatting error, instead of panicking
+fn format(
+    pattern: &str,
+    write: impl FnOnce(&mut String) -> std::fmt::Result,
+) -> Result<Value, RuntimeError> {
+    let mut formatted = String::new();
+    write(&mut formatted).map_err(|_| {
+        RuntimeError::IllegalArgumentException(format!("Invalid date pattern '{}'", pattern))
+    })?;
+    Ok(string(formatted))
+}
+
+fn datetime_to_timezone(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    match (self_val, args.first()) {
+        (Value::DateTime(d), Some(Value::String(timezone))) => {
+            let timezone: Tz = timezone.parse().map_err(|_| {
+                RuntimeError::IllegalArgumentException(format!("Unknown timezone '{}'", timezone))
+            })?;
+            Ok(d.with_timezone(&timezone).fixed_offset().into())
+        }
+        _ => Err(expected("datetime")),
+    }
+}
+
+fn datetime_to_utc(_vm: &Vm, self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+    match self_val {
+        Value::DateTime(d) => Ok(d.to_utc().into()),
+        _ => Err(expected("datetime")),
+    }
+}
@@ -0,0 +1,64 @@
+use crate::builtins::{FunctionFn, FunctionMap, Parameter, Signature, add, expected};
+use crate::compiler::tokens::TokenType::{Decimal, I64};
+use crate::errors::RuntimeError;
+use crate::value::Value;
+use crate::vm::Vm;
+use rust_decimal::RoundingStrategy;
+use std::collections::HashMap;
+
+pub(crate) fn decimal_functions() -> FunctionMap {
+    let mut decimal_functions: FunctionMap = HashMap::new();
+    let functions = &mut decimal_functions;
+    add(functions, "round_half_up", rounding(round_half_up));
+    add(functions, "round_half_down", rounding(round_half_down));
+    add(functions, "round_half_even", rounding(round_half_even));
+    add(functions, "round_up", rounding(round_up));
+    add(functions, "round_down", rounding(round_down));
+    add(functions, "scale", Signature::new(vec![], I64, decimal_scale));
+    decimal_functions
+}
+
+fn rounding(function: FunctionFn) -> Signature {
+    Signature::new(vec![Parameter::new("places", I64)], Decimal, function)
+}
+
+macro_rules! rounding {
+    ($name:ident, $strategy:ident) => {
+        fn $name(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+            round(self_val, args, RoundingStrategy::$strategy)
+        }
+    };
+}
+
+// round_up and round_down round away from and towards zero respectively
+rounding!(round_half_up, MidpointAwayFromZero);
+rounding!(round_half_down, MidpointTowardZero);
+rounding!(round_half_even, MidpointNearestEven);
+rounding!(round_up, AwayFromZero);
+rounding!(round_down, ToZero);
+
+fn round(
+    self_val: Value,
+    args: Vec<Value>,
+    strategy: RoundingStrategy,
+) -> Result<Value, RuntimeError> {
+    match (self_val, args.first()) {
+        (Value::Decimal(d), Some(Value::I64(places))) => {
+            let places = u32::try_from(*places).map_err(|_| {
+                RuntimeError::IllegalArgumentException(format!(
+                    "Illegal number of decimal places {}",
+                    places
+                ))
+            })?;
+            Ok(Value::Decimal(d.round_dp_with_strategy(places, strategy)))
+        }
+        _ => Err(expected("decimal")),
+    }
+}
+
+fn decimal_scale(_vm: &Vm, self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+    match self_val {
+        Value::Decimal(d) => Ok(Value::I64(d.scale() as i64)),
+        _ => Err(expected("decimal")),
+    }
+}
@@ -1,7 +1,9 @@
-use crate::builtins::{FunctionMap, Signature, add};
-use crate::compiler::tokens::TokenType::DateTime;
+use crate::builtins::{FunctionMap, Parameter, Signature, add, expected};
+use crate::compiler::tokens::TokenType::{Date, DateTime, Duration, I64, StringType, Uuid};
 use crate::errors::RuntimeError;
 use crate::value::Value;
+use crate::vm::Vm;
+use chrono::TimeDelta;
 use std::collections::HashMap;
 use std::sync::LazyLock;
 
@@ -9,12 +11,105 @@ pub(crate) static GLOBAL_FUNCTIONS: LazyLock<FunctionMap> = LazyLock::new(|| {
     let mut global_functions: FunctionMap = HashMap::new();
     let functions = &mut global_functions;
     add(functions, "now", Signature::new(vec![], DateTime, now));
+    add(functions, "today", Signature::new(vec![], Date, today));
+    add(
+        functions,
+        "parse_datetime",
+        Signature::new(
+            vec![Parameter::new("text", StringType), Parameter::new("pattern", StringType)],
+            DateTime,
+            parse_datetime,
+        ),
+    );
+    add(
+        functions,
+        "parse_date",
+        Signature::new(
+            vec![Parameter::new("text", StringType), Parameter::new("pattern", StringType)],
+            Date,
+            parse_date,
+        ),
+    );
+    add(functions, "days", duration_fn(days));
+    add(functions, "hours", duration_fn(hours));
+    add(functions, "minutes", duration_fn(minutes));
+    add(functions, "seconds", duration_fn(seconds));
+    add(functions, "milliseconds", duration_fn(milliseconds));
+    add(functions, "uuid_v4", Signature::new(vec![], Uuid, uuid_v4));
+    add(functions, "uuid_v7", Signature::new(vec![], Uuid, uuid_v7));
 
     global_functions
 });
 
-fn now(_self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
-    Ok(Value::DateTime(Box::new(chrono::DateTime::from(
-        chrono::Utc::now(),
-    ))))
+fn now(_vm: &Vm, _self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(chrono::Utc::now().into())
+}
+
+fn today(_vm: &Vm, _self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(chrono::Local::now().date_naive().into())
+}
+
+fn parse_datetime(_vm: &Vm, _self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (text, pattern) = text_and_pattern(&args)?;
+    chrono::DateTime::parse_from_str(text, pattern)
+        .map(Value::from)
+        .map_err(|e| illegal_date(text, pattern, e))
+}
+
+fn parse_date(_vm: &Vm, _self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (text, pattern) = text_and_pattern(&args)?;
+    chrono::NaiveDate::parse_from_str(text, pattern)
+        .map(Value::from)
+        .map_err(|e| illegal_date(text, pattern, e))
+}
+
+fn text_and_pattern(args: &[Value]) -> Result<(&str, &str), RuntimeError> {
+    match (args.first(), args.get(1)) {
+        (Some(Value::String(text)), Some(Value::String(pattern))) => Ok((text, pattern)),
+        _ => Err(expected("string")),
+    }
+}
+
+fn illegal_date(text: &str, pattern: &str, e: chrono::ParseError) -> RuntimeError {
+    RuntimeError::IllegalArgumentException(format!(
+        "Cannot parse '{}' with pattern '{}': {}",
+        text, pattern, e
+    ))
+}
+
+fn duration_fn(function: crate::builtins::FunctionFn) -> Signature {
+    Signature::new(vec![Parameter::new("amount", I64)], Duration, function)
+}
+
+macro_rules! duration {
+    ($name:ident, $constructor:path) => {
+        fn $name(_vm: &Vm, _self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+            match args.first() {
+                Some(Value::I64(amount)) => $constructor(*amount)
+                    .map(Value::Duration)
+                    .ok_or_else(|| {
+                        RuntimeError::IllegalArgumentException(format!(
+                            "Duration out of range: {}",
+                            amount
+                        ))
+                    }),
+                _ => Err(expected("i64")),
+            }
+        }
+    };
+}
+
+duration!(days, TimeDelta::try_days);
+duration!(hours, TimeDelta::try_hours);
+duration!(minutes, TimeDelta::try_minutes);
+duration!(seconds, TimeDelta::try_seconds);
+duration!(milliseconds, TimeDelta::try_milliseconds);
+
+fn uuid_v4(_vm: &Vm, _self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(Value::Uuid(uuid::Uuid::new_v4()))
+}
+
+// time-ordered, which makes for better database keys
+fn uuid_v7(_vm: &Vm, _self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(Value::Uuid(uuid::Uuid::now_v7()))
 }
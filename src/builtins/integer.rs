@@ -0,0 +1,75 @@
+use crate::builtins::{FunctionMap, Parameter, Signature, add, expected};
+use crate::compiler::tokens::TokenType;
+use crate::errors::RuntimeError;
+use crate::value::Value;
+use crate::vm::Vm;
+use std::collections::HashMap;
+
+/// the explicit alternatives for the checked arithmetic operators
+pub(crate) fn integer_functions(integer_type: TokenType) -> FunctionMap {
+    let mut integer_functions: FunctionMap = HashMap::new();
+    let functions = &mut integer_functions;
+    let signature = |function| {
+        Signature::new(
+            vec![Parameter::new("other", integer_type.clone())],
+            integer_type.clone(),
+            function,
+        )
+    };
+    add(functions, "wrapping_add", signature(wrapping_add));
+    add(functions, "wrapping_sub", signature(wrapping_sub));
+    add(functions, "saturating_add", signature(saturating_add));
+    add(functions, "saturating_sub", signature(saturating_sub));
+    integer_functions
+}
+
+macro_rules! integer_fn {
+    ($name:ident, $op:tt, $fit:ident) => {
+        fn $name(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+            let (a, b) = operands(&self_val, &args)?;
+            Ok($fit(&self_val, a $op b))
+        }
+    };
+}
+
+integer_fn!(wrapping_add, +, wrap);
+integer_fn!(wrapping_sub, -, wrap);
+integer_fn!(saturating_add, +, saturate);
+integer_fn!(saturating_sub, -, saturate);
+
+// i128 holds the sum and difference of any two 64 bit integers
+fn operands(self_val: &Value, args: &[Value]) -> Result<(i128, i128), RuntimeError> {
+    match (integer(self_val), args.first().and_then(integer)) {
+        (Some(a), Some(b)) => Ok((a, b)),
+        _ => Err(expected("integer")),
+    }
+}
+
+fn integer(value: &Value) -> Option<i128> {
+    match value {
+        Value::I32(i) => Some(*i as i128),
+        Value::I64(i) => Some(*i as i128),
+        Value::U32(u) => Some(*u as i128),
+        Value::U64(u) => Some(*u as i128),
+        _ => None,
+    }
+}
+
+// truncating the two's complement wraps around
+fn wrap(receiver: &Value, result: i128) -> Value {
+    match receiver {
+        Value::I32(_) => Value::I32(result as i32),
+        Value::U32(_) => Value::U32(result as u32),
+        Value::U64(_) => Value::U64(result as u64),
+        _ => Value::I64(result as i64),
+    }
+}
+
+fn saturate(receiver: &Value, result: i128) -> Value {
+    match receiver {
+        Value::I32(_) => Value::I32(result.clamp(i32::MIN as i128, i32::MAX as i128) as i32),
+        Value::U32(_) => Value::U32(result.clamp(0, u32::MAX as i128) as u32),
+        Value::U64(_) => Value::U64(result.clamp(0, u64::MAX as i128) as u64),
+        _ => Value::I64(result.clamp(i64::MIN as i128, i64::MAX as i128) as i64),
+    }
+}
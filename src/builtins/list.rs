@@ -1,16 +1,23 @@
 use crate::compiler::ast_pass::Parameter;
-use crate::builtins::{FunctionMap, Signature, add, expected};
+use crate::builtins::{FunctionFn, FunctionMap, Signature, add, expected};
 use crate::errors::RuntimeError;
 use crate::compiler::tokens::TokenType;
-use crate::compiler::tokens::TokenType::U64;
-use crate::value::{Value, u64};
+use crate::compiler::tokens::TokenType::{Any, Bool, U64};
+use crate::value::{Value, bool, u64};
+use crate::vm::Vm;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 macro_rules! mut_list_fn {
     (mut $list:ident, mut $args:ident => $body:expr) => {
-        |self_val: Value, mut $args: Vec<Value>| -> Result<Value, RuntimeError> {
+        |_vm: &Vm, self_val: Value, mut $args: Vec<Value>| -> Result<Value, RuntimeError> {
             match self_val {
-                Value::List(mut $list) => $body,
+                // only copies the list when it is shared
+                Value::List(list) => {
+                    let mut $list = Arc::unwrap_or_clone(list);
+                    $body
+                }
                 _ => Err(expected_a_list()),
             }
         }
@@ -19,7 +26,7 @@ macro_rules! mut_list_fn {
 
 macro_rules! list_fn {
     ($list:ident, $args:ident => $body:expr) => {
-        |self_val: Value, $args: Vec<Value>| -> Result<Value, RuntimeError> {
+        |_vm: &Vm, self_val: Value, $args: Vec<Value>| -> Result<Value, RuntimeError> {
             match self_val {
                 Value::List($list) => $body,
                 _ => Err(expected_a_list()),
@@ -45,10 +52,10 @@ pub(crate) fn list_functions() -> FunctionMap {
         "push",
         Signature::new(
             vec![Parameter::new("element", TokenType::Any)],
-            U64,
+            TokenType::untyped_list(),
             mut_list_fn!(mut list, mut args => {
                 list.push(args.remove(0));
-                Ok(Value::List(list))
+                Ok(Value::List(list.into()))
             }),
         ),
     );
@@ -57,20 +64,227 @@ pub(crate) fn list_functions() -> FunctionMap {
         "remove",
         Signature::new(
             vec![Parameter::new("index", U64)],
-            U64,
+            TokenType::untyped_list(),
             mut_list_fn!(mut list, mut args => {
                 let index = args.remove(0).cast_usize().unwrap();
                 if index >= list.len() {
                     return Err(RuntimeError::IndexOutOfBounds(index, list.len()))
                 }
                 list.remove(index);
-                Ok(Value::List(list))
+                Ok(Value::List(list.into()))
+            }),
+        ),
+    );
+    add(functions, "map", with_function(TokenType::untyped_list(), list_map));
+    add(functions, "filter", with_function(TokenType::untyped_list(), list_filter));
+    add(functions, "any", with_function(Bool, list_any));
+    add(functions, "all", with_function(Bool, list_all));
+    add(functions, "find", with_function(Any, list_find));
+    add(functions, "sort_by", with_function(TokenType::untyped_list(), list_sort_by));
+    add(functions, "group_by", with_function(TokenType::untyped_map(), list_group_by));
+    add(functions, "flat_map", with_function(TokenType::untyped_list(), list_flat_map));
+    add(
+        functions,
+        "reduce",
+        Signature::new(
+            vec![Parameter::new("initial", Any), Parameter::new("function", any_function(2))],
+            Any,
+            list_reduce,
+        ),
+    );
+    add(
+        functions,
+        "zip",
+        Signature::new(
+            vec![Parameter::new("other", TokenType::untyped_list())],
+            TokenType::ListType(Box::new(TokenType::untyped_list())),
+            list_fn!(list, args => match args.into_iter().next() {
+                Some(Value::List(other)) => Ok(Value::List(
+                    list.iter()
+                        .zip(other.iter())
+                        .map(|(a, b)| Value::List(vec![a.clone(), b.clone()].into()))
+                        .collect::<Vec<_>>()
+                        .into(),
+                )),
+                _ => Err(expected_a_list()),
+            }),
+        ),
+    );
+    add(
+        functions,
+        "enumerate",
+        Signature::new(
+            vec![],
+            TokenType::ListType(Box::new(TokenType::untyped_list())),
+            list_fn!(list, _args => Ok(Value::List(
+                list.iter()
+                    .enumerate()
+                    .map(|(i, element)| {
+                        Value::List(vec![Value::I64(i as i64), element.clone()].into())
+                    })
+                    .collect::<Vec<_>>()
+                    .into(),
+            ))),
+        ),
+    );
+    add(
+        functions,
+        "join",
+        Signature::new(
+            vec![Parameter::new("separator", TokenType::StringType)],
+            TokenType::StringType,
+            list_fn!(list, args => match args.first() {
+                Some(Value::String(separator)) => Ok(Value::String(
+                    list.iter()
+                        .map(|element| element.to_string())
+                        .collect::<Vec<_>>()
+                        .join(separator)
+                        .into(),
+                )),
+                _ => Err(expected("string")),
             }),
         ),
     );
     list_functions
 }
 
+// the functions are checked against the element type by the type checker
+// (symbol_builder::check_list_function), the builtins only see values
+fn any_function(arity: usize) -> TokenType {
+    TokenType::FunctionType(vec![Any; arity], Box::new(Any))
+}
+
+fn with_function(return_type: TokenType, function: FunctionFn) -> Signature {
+    Signature::new(vec![Parameter::new("function", any_function(1))], return_type, function)
+}
+
+// splits the receiver and the function argument
+fn list_and_function(
+    self_val: Value,
+    args: Vec<Value>,
+) -> Result<(Arc<Vec<Value>>, Value), RuntimeError> {
+    match (self_val, args.into_iter().next()) {
+        (Value::List(list), Some(function)) => Ok((list, function)),
+        _ => Err(expected_a_list()),
+    }
+}
+
+fn predicate(vm: &Vm, function: &Value, element: &Value) -> Result<bool, RuntimeError> {
+    match vm.call_value(function.clone(), vec![element.clone()])? {
+        Value::Bool(b) => Ok(b),
+        _ => Err(expected("bool")),
+    }
+}
+
+fn list_map(vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (list, function) = list_and_function(self_val, args)?;
+    list.iter()
+        .map(|element| vm.call_value(function.clone(), vec![element.clone()]))
+        .collect::<Result<Vec<_>, _>>()
+        .map(|mapped| Value::List(mapped.into()))
+}
+
+fn list_filter(vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (list, function) = list_and_function(self_val, args)?;
+    let mut filtered = vec![];
+    for element in list.iter() {
+        if predicate(vm, &function, element)? {
+            filtered.push(element.clone());
+        }
+    }
+    Ok(Value::List(filtered.into()))
+}
+
+fn list_any(vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (list, function) = list_and_function(self_val, args)?;
+    for element in list.iter() {
+        if predicate(vm, &function, element)? {
+            return Ok(bool(true));
+        }
+    }
+    Ok(bool(false))
+}
+
+fn list_all(vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (list, function) = list_and_function(self_val, args)?;
+    for element in list.iter() {
+        if !predicate(vm, &function, element)? {
+            return Ok(bool(false));
+        }
+    }
+    Ok(bool(true))
+}
+
+// the first element that matches, NoMatch when there is none
+fn list_find(vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (list, function) = list_and_function(self_val, args)?;
+    for element in list.iter() {
+        if predicate(vm, &function, element)? {
+            return Ok(element.clone());
+        }
+    }
+    Err(RuntimeError::NoMatch)
+}
+
+// sorts on the key the function returns for each element, keeping the order of equal keys
+fn list_sort_by(vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (list, function) = list_and_function(self_val, args)?;
+    let mut keyed = list
+        .iter()
+        .map(|element| Ok((vm.call_value(function.clone(), vec![element.clone()])?, element)))
+        .collect::<Result<Vec<_>, RuntimeError>>()?;
+    // keys like a number and a string have no order
+    let mut incomparable = None;
+    keyed.sort_by(|(a, _), (b, _)| {
+        a.partial_cmp(b).unwrap_or_else(|| {
+            incomparable.get_or_insert_with(|| (a.to_string(), b.to_string()));
+            Ordering::Equal
+        })
+    });
+    if let Some((a, b)) = incomparable {
+        return Err(RuntimeError::Incomparable(a, b));
+    }
+    let sorted: Vec<_> = keyed.into_iter().map(|(_, element)| element.clone()).collect();
+    Ok(Value::List(sorted.into()))
+}
+
+fn list_group_by(vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (list, function) = list_and_function(self_val, args)?;
+    let mut groups: HashMap<Value, Value> = HashMap::new();
+    for element in list.iter() {
+        let key = vm.call_value(function.clone(), vec![element.clone()])?;
+        let group = groups.entry(key).or_insert_with(|| Value::List(vec![].into()));
+        if let Value::List(group) = group {
+            Arc::make_mut(group).push(element.clone());
+        }
+    }
+    Ok(Value::Map(groups.into()))
+}
+
+fn list_flat_map(vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (list, function) = list_and_function(self_val, args)?;
+    let mut flattened = vec![];
+    for element in list.iter() {
+        match vm.call_value(function.clone(), vec![element.clone()])? {
+            Value::List(elements) => flattened.extend(elements.iter().cloned()),
+            _ => return Err(expected_a_list()),
+        }
+    }
+    Ok(Value::List(flattened.into()))
+}
+
+fn list_reduce(vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let mut args = args.into_iter();
+    match (self_val, args.next(), args.next()) {
+        (Value::List(list), Some(initial), Some(function)) => {
+            list.iter().try_fold(initial, |acc, element| {
+                vm.call_value(function.clone(), vec![acc, element.clone()])
+            })
+        }
+        _ => Err(expected_a_list()),
+    }
+}
+
 fn expected_a_list() -> RuntimeError {
     expected("list")
 }
@@ -1,15 +1,22 @@
 mod string;
 mod list;
+mod datetime;
+mod decimal;
+mod integer;
 pub(crate) mod globals;
 
 use crate::builtins::string::string_functions;
 use crate::errors::{CompilerError, RuntimeError};
 use crate::compiler::tokens::TokenType;
 use crate::value::Value;
+use crate::vm::Vm;
 use std::collections::HashMap;
 use std::sync::LazyLock;
 use crate::compiler::ast_pass::Parameter;
 use crate::builtins::list::list_functions;
+use crate::builtins::datetime::{date_functions, datetime_functions, duration_functions};
+use crate::builtins::decimal::decimal_functions;
+use crate::builtins::integer::integer_functions;
 
 pub(crate) struct Signature {
     pub(crate) parameters: Vec<Parameter>,
@@ -35,7 +42,8 @@ impl Signature {
     }
 }
 
-pub(crate) type FunctionFn = fn(Value, Vec<Value>) -> Result<Value, RuntimeError>;
+/// builtins get the vm, so that they can call function values passed as arguments
+pub(crate) type FunctionFn = fn(&Vm, Value, Vec<Value>) -> Result<Value, RuntimeError>;
 /// maps function names to the signature
 pub(crate) type FunctionMap = HashMap<String, Signature>;
 /// maps receiver type name to a function map
@@ -45,10 +53,30 @@ static FUNCTIONS: LazyLock<FunctionTable> = LazyLock::new(|| {
     let mut table: FunctionTable = HashMap::new();
     table.insert("string".to_string(), string_functions());
     table.insert("list".to_string(), list_functions());
+    table.insert("datetime".to_string(), datetime_functions());
+    table.insert("date".to_string(), date_functions());
+    table.insert("duration".to_string(), duration_functions());
+    table.insert("decimal".to_string(), decimal_functions());
+    for integer_type in [TokenType::I32, TokenType::I64, TokenType::U32, TokenType::U64] {
+        table.insert(integer_type.to_string(), integer_functions(integer_type));
+    }
 
     table
 });
 
+/// methods that every type has
+static ANY_FUNCTIONS: LazyLock<FunctionMap> = LazyLock::new(|| {
+    let mut functions: FunctionMap = HashMap::new();
+    add(
+        &mut functions,
+        "to_string",
+        Signature::new(vec![], TokenType::StringType, |_vm, self_val, _args| {
+            Ok(Value::String(self_val.to_string().into()))
+        }),
+    );
+    functions
+});
+
 pub(crate) fn add(m: &mut FunctionMap, name: &str, method: Signature) {
     m.insert(name.to_string(), method);
 }
@@ -57,18 +85,10 @@ pub(crate) fn lookup(type_name: &str, method_name: &str) -> Result<&'static Sign
      FUNCTIONS
         .get(type_name)
         .and_then(|methods| methods.get(method_name))
+        .or_else(|| ANY_FUNCTIONS.get(method_name))
         .ok_or_else(|| CompilerError::FunctionNotFound(format!("{}.{}", type_name, method_name)))
 }
 
-pub(crate) fn call(
-    type_name: &str,
-    method_name: &str,
-    self_val: Value,
-    args: Vec<Value>,
-) -> Result<Value, RuntimeError> {
-    (lookup(type_name,method_name).map_err(|e|RuntimeError::FunctionNotFound(e.to_string()))?.function)(self_val, args)
-}
-
 pub(crate) fn expected(expected_type: &str) -> RuntimeError {
     RuntimeError::ExpectedType(expected_type.to_string())
 }
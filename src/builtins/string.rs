@@ -1,9 +1,20 @@
 use crate::builtins::{FunctionMap, Parameter, Signature, add, expected};
 use crate::errors::RuntimeError;
-use crate::compiler::tokens::TokenType::{StringType, U64};
+use crate::compiler::tokens::TokenType;
+use crate::compiler::tokens::TokenType::{
+    Bool, Char, Decimal, F32, F64, I32, I64, StringType, U32, U64,
+};
 use crate::value::{Value, bool, string, u64};
+use crate::vm::Vm;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
 use regex::Regex;
 use std::collections::HashMap;
+use std::sync::Arc;
+
+// everything but the unreserved characters of rfc 3986
+const URL_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+// the longest string that repeat and padding make, in bytes
+const MAX_LEN: usize = 1 << 30;
 
 pub(crate) fn string_functions() -> FunctionMap {
     let mut string_functions: FunctionMap = HashMap::new();
@@ -19,7 +30,7 @@ pub(crate) fn string_functions() -> FunctionMap {
         "to_lowercase",
         Signature::new(vec![], StringType, string_to_lowercase),
     );
-    add(functions, "contains", Signature::new(vec![Parameter::new("key", StringType)], StringType, string_contains));
+    add(functions, "contains", Signature::new(vec![Parameter::new("key", StringType)], Bool, string_contains));
     add(functions, "reverse", Signature::new(vec![], StringType, string_reverse));
     add(functions, "trim", Signature::new(vec![], StringType, string_trim));
     add(
@@ -40,67 +51,136 @@ pub(crate) fn string_functions() -> FunctionMap {
             string_replace_all,
         ),
     );
+    let string_list = || TokenType::ListType(Box::new(StringType));
+    add(
+        functions,
+        "split",
+        Signature::new(vec![Parameter::new("separator", StringType)], string_list(), string_split),
+    );
+    add(
+        functions,
+        "starts_with",
+        Signature::new(vec![Parameter::new("prefix", StringType)], Bool, string_starts_with),
+    );
+    add(
+        functions,
+        "ends_with",
+        Signature::new(vec![Parameter::new("suffix", StringType)], Bool, string_ends_with),
+    );
+    add(functions, "find", Signature::new(vec![Parameter::new("key", StringType)], I64, string_find));
+    add(
+        functions,
+        "substring",
+        Signature::new(
+            vec![Parameter::new("start", I64), Parameter::new("end", I64)],
+            StringType,
+            string_substring,
+        ),
+    );
+    add(functions, "chars", Signature::new(vec![], TokenType::ListType(Box::new(Char)), string_chars));
+    add(functions, "lines", Signature::new(vec![], string_list(), string_lines));
+    add(
+        functions,
+        "pad_start",
+        Signature::new(
+            vec![Parameter::new("width", I64), Parameter::new("padding", StringType)],
+            StringType,
+            string_pad_start,
+        ),
+    );
+    add(
+        functions,
+        "pad_end",
+        Signature::new(
+            vec![Parameter::new("width", I64), Parameter::new("padding", StringType)],
+            StringType,
+            string_pad_end,
+        ),
+    );
+    add(functions, "repeat", Signature::new(vec![Parameter::new("times", I64)], StringType, string_repeat));
+    add(functions, "parse_int", Signature::new(vec![], I64, string_parse_int));
+    add(functions, "parse_float", Signature::new(vec![], F64, string_parse_float));
+    add(functions, "parse_i64", Signature::new(vec![], I64, string_parse_int));
+    add(functions, "parse_i32", Signature::new(vec![], I32, string_parse_i32));
+    add(functions, "parse_u32", Signature::new(vec![], U32, string_parse_u32));
+    add(functions, "parse_u64", Signature::new(vec![], U64, string_parse_u64));
+    add(functions, "parse_f64", Signature::new(vec![], F64, string_parse_float));
+    add(functions, "parse_f32", Signature::new(vec![], F32, string_parse_f32));
+    add(functions, "parse_decimal", Signature::new(vec![], Decimal, string_parse_decimal));
+    add(functions, "parse_bool", Signature::new(vec![], Bool, string_parse_bool));
+    add(
+        functions,
+        "matches",
+        Signature::new(vec![Parameter::new("pattern", StringType)], Bool, string_matches),
+    );
+    add(
+        functions,
+        "captures",
+        Signature::new(vec![Parameter::new("pattern", StringType)], string_list(), string_captures),
+    );
+    add(functions, "url_encode", Signature::new(vec![], StringType, string_url_encode));
+    add(functions, "url_decode", Signature::new(vec![], StringType, string_url_decode));
     string_functions
 }
 
-fn string_len(self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+fn string_len(_vm: &Vm, self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
     match self_val {
         Value::String(s) => Ok(u64(s.len() as u64)),
         _ => Err(expected_a_string()),
     }
 }
 
-fn string_to_uppercase(self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+fn string_to_uppercase(_vm: &Vm, self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
     match self_val {
         Value::String(s) => Ok(string(s.to_uppercase())),
         _ => Err(expected_a_string()),
     }
 }
 
-fn string_to_lowercase(self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+fn string_to_lowercase(_vm: &Vm, self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
     match self_val {
         Value::String(s) => Ok(string(s.to_lowercase())),
         _ => Err(expected_a_string()),
     }
 }
 
-fn string_contains(self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+fn string_contains(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
     match (self_val, args.first()) {
-        (Value::String(s), Some(Value::String(pat))) => Ok(bool(s.contains(pat.as_str()))),
+        (Value::String(s), Some(Value::String(pat))) => Ok(bool(s.contains(&**pat))),
         _ => Err(expected_a_string()),
     }
 }
 
-fn string_reverse(self_val: Value, _: Vec<Value>) -> Result<Value, RuntimeError> {
+fn string_reverse(_vm: &Vm, self_val: Value, _: Vec<Value>) -> Result<Value, RuntimeError> {
     match self_val {
         Value::String(s) => Ok(s.chars().rev().collect::<String>().into()),
         _ => Err(expected_a_string()),
     }
 }
 
-fn string_trim(self_val: Value, _: Vec<Value>) -> Result<Value, RuntimeError> {
+fn string_trim(_vm: &Vm, self_val: Value, _: Vec<Value>) -> Result<Value, RuntimeError> {
     match self_val {
         Value::String(s) => Ok(string(s.trim())),
         _ => Err(expected_a_string()),
     }
 }
 
-fn string_trim_start(self_val: Value, _: Vec<Value>) -> Result<Value, RuntimeError> {
+fn string_trim_start(_vm: &Vm, self_val: Value, _: Vec<Value>) -> Result<Value, RuntimeError> {
     match self_val {
         Value::String(s) => Ok(string(s.trim_start())),
         _ => Err(expected_a_string()),
     }
 }
 
-fn string_trim_end(self_val: Value, _: Vec<Value>) -> Result<Value, RuntimeError> {
+fn string_trim_end(_vm: &Vm, self_val: Value, _: Vec<Value>) -> Result<Value, RuntimeError> {
     match self_val {
         Value::String(s) => Ok(string(s.trim_end())),
         _ => Err(expected_a_string()),
     }
 }
-fn string_replace_all(receiver: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+fn string_replace_all(_vm: &Vm, receiver: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
     let pattern = if let Value::String(s) = &args[0] {
-        Regex::new(s).map_err(|_| RuntimeError::IllegalArgumentException("Invalid regex".into()))?
+        regex(s)?
     } else {
         return Err(RuntimeError::IllegalArgumentException(
             format!("Illegal pattern. Expected a string, but got {}", &args[0]),
@@ -117,11 +197,204 @@ fn string_replace_all(receiver: Value, args: Vec<Value>) -> Result<Value, Runtim
         ));
     };
     match receiver {
-        Value::String(ref str) => Ok(string(pattern.replace_all(str, replacement))),
+        Value::String(ref str) => Ok(string(pattern.replace_all(str, &**replacement))),
         _ => Err(expected_a_string()),
     }
 }
 
+fn string_split(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (s, separator) = string_and_argument(self_val, &args)?;
+    Ok(Value::List(s.split(separator).map(string).collect::<Vec<_>>().into()))
+}
+
+fn string_starts_with(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (s, prefix) = string_and_argument(self_val, &args)?;
+    Ok(bool(s.starts_with(prefix)))
+}
+
+fn string_ends_with(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (s, suffix) = string_and_argument(self_val, &args)?;
+    Ok(bool(s.ends_with(suffix)))
+}
+
+// the char index of the first occurrence, or -1
+fn string_find(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (s, key) = string_and_argument(self_val, &args)?;
+    Ok(Value::I64(match s.find(key) {
+        Some(byte_index) => s[..byte_index].chars().count() as i64,
+        None => -1,
+    }))
+}
+
+// start inclusive, end exclusive, both in chars
+fn string_substring(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let Value::String(s) = self_val else {
+        return Err(expected_a_string());
+    };
+    let len = s.chars().count();
+    let start = index(&args[0], len)?;
+    let end = index(&args[1], len)?;
+    if start > end {
+        return Err(RuntimeError::IndexOutOfBounds(start, end));
+    }
+    Ok(string(s.chars().skip(start).take(end - start).collect::<String>()))
+}
+
+fn string_chars(_vm: &Vm, self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+    match self_val {
+        Value::String(s) => Ok(Value::List(s.chars().map(Value::Char).collect::<Vec<_>>().into())),
+        _ => Err(expected_a_string()),
+    }
+}
+
+fn string_lines(_vm: &Vm, self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+    match self_val {
+        Value::String(s) => Ok(Value::List(s.lines().map(string).collect::<Vec<_>>().into())),
+        _ => Err(expected_a_string()),
+    }
+}
+
+fn string_pad_start(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (s, padding) = padding(self_val, &args)?;
+    Ok(string(padding + &s))
+}
+
+fn string_pad_end(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (s, padding) = padding(self_val, &args)?;
+    Ok(string(format!("{}{}", s, padding)))
+}
+
+// the padding repeated (and cut off) to fill the string up to width chars
+fn padding(self_val: Value, args: &[Value]) -> Result<(Arc<str>, String), RuntimeError> {
+    let Value::String(s) = self_val else {
+        return Err(expected_a_string());
+    };
+    let width = count(&args[0])?;
+    let Value::String(padding) = &args[1] else {
+        return Err(expected_a_string());
+    };
+    if padding.is_empty() {
+        return Err(RuntimeError::IllegalArgumentException("Padding cannot be empty".into()));
+    }
+    let missing = width.saturating_sub(s.chars().count());
+    // a char of the padding is at most 4 bytes
+    max_len(missing.checked_mul(4))?;
+    Ok((s, padding.chars().cycle().take(missing).collect()))
+}
+
+fn string_repeat(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    match self_val {
+        Value::String(s) => {
+            let times = count(&args[0])?;
+            max_len(s.len().checked_mul(times))?;
+            Ok(string(s.repeat(times)))
+        }
+        _ => Err(expected_a_string()),
+    }
+}
+
+macro_rules! parse_fn {
+    ($name:ident, $variant:ident, $type_name:literal) => {
+        fn $name(_vm: &Vm, self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+            match self_val {
+                Value::String(s) => s
+                    .trim()
+                    .parse()
+                    .map(Value::$variant)
+                    .map_err(|_| RuntimeError::CannotParse(s.to_string(), $type_name)),
+                _ => Err(expected_a_string()),
+            }
+        }
+    };
+}
+
+parse_fn!(string_parse_int, I64, "i64");
+parse_fn!(string_parse_i32, I32, "i32");
+parse_fn!(string_parse_u32, U32, "u32");
+parse_fn!(string_parse_u64, U64, "u64");
+parse_fn!(string_parse_float, F64, "f64");
+parse_fn!(string_parse_f32, F32, "f32");
+parse_fn!(string_parse_decimal, Decimal, "decimal");
+parse_fn!(string_parse_bool, Bool, "bool");
+
+fn string_matches(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (s, pattern) = string_and_argument(self_val, &args)?;
+    Ok(bool(regex(pattern)?.is_match(&s)))
+}
+
+// the whole match followed by the groups of the first match, empty when there is no match
+fn string_captures(_vm: &Vm, self_val: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (s, pattern) = string_and_argument(self_val, &args)?;
+    let captures = match regex(pattern)?.captures(&s) {
+        Some(captures) => captures
+            .iter()
+            .map(|group| string(group.map(|m| m.as_str()).unwrap_or_default()))
+            .collect(),
+        None => vec![],
+    };
+    Ok(Value::List(captures.into()))
+}
+
+fn string_url_encode(_vm: &Vm, self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+    match self_val {
+        Value::String(s) => Ok(string(utf8_percent_encode(&s, URL_ENCODE_SET).to_string())),
+        _ => Err(expected_a_string()),
+    }
+}
+
+fn string_url_decode(_vm: &Vm, self_val: Value, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+    match self_val {
+        Value::String(s) => percent_decode_str(&s)
+            .decode_utf8()
+            .map(string)
+            .map_err(|_| RuntimeError::IllegalArgumentException(format!("Invalid url encoding '{}'", s))),
+        _ => Err(expected_a_string()),
+    }
+}
+
+fn string_and_argument(self_val: Value, args: &[Value]) -> Result<(Arc<str>, &str), RuntimeError> {
+    match (self_val, args.first()) {
+        (Value::String(s), Some(Value::String(argument))) => Ok((s, &**argument)),
+        _ => Err(expected_a_string()),
+    }
+}
+
+fn regex(pattern: &str) -> Result<Regex, RuntimeError> {
+    Regex::new(pattern)
+        .map_err(|e| RuntimeError::IllegalArgumentException(format!("Invalid regex: {}", e)))
+}
+
+// a string that is too long to make, instead of running out of memory
+fn max_len(len: Option<usize>) -> Result<(), RuntimeError> {
+    match len {
+        Some(len) if len <= MAX_LEN => Ok(()),
+        _ => Err(RuntimeError::IllegalArgumentException(format!(
+            "The string would be longer than {} bytes",
+            MAX_LEN
+        ))),
+    }
+}
+
+fn count(value: &Value) -> Result<usize, RuntimeError> {
+    match value {
+        Value::I32(n) if *n < 0 => Err(not_positive(value)),
+        Value::I64(n) if *n < 0 => Err(not_positive(value)),
+        _ => Ok(value.clone().cast_usize()?),
+    }
+}
+
+fn not_positive(value: &Value) -> RuntimeError {
+    RuntimeError::IllegalArgumentException(format!("Expected a positive number, got {}", value))
+}
+
+fn index(value: &Value, len: usize) -> Result<usize, RuntimeError> {
+    let index = count(value)?;
+    if index > len {
+        return Err(RuntimeError::IndexOutOfBounds(index, len));
+    }
+    Ok(index)
+}
+
 fn expected_a_string() -> RuntimeError {
     expected("string")
 }
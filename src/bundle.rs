@@ -0,0 +1,149 @@
+use crate::AsmRegistry;
+use crate::compiler::{bytecode, map_underlying};
+use crate::errors::TipiLangError;
+use crate::errors::TipiLangError::{Bytecode, Platform};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use walkdir::WalkDir;
+
+/// the last bytes of an executable with a bundle
+const MAGIC: &[u8; 8] = b"TIPIBNDL";
+// the length of the bundle and the magic
+const TRAILER_LEN: usize = 16;
+
+/// a compiled service with its static files, that is served without a source directory
+pub struct Bundle {
+    pub registry: AsmRegistry,
+    /// the contents of the static files, by url path
+    pub assets: HashMap<String, Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredBundle {
+    bytecode: Vec<u8>,
+    assets: Vec<(String, Vec<u8>)>,
+}
+
+/// writes a copy of the running executable, with the registry and the static files appended
+/// to it, so that one file is all a service needs
+pub fn write_executable(
+    registry: &AsmRegistry,
+    assets: &HashMap<String, Vec<u8>>,
+    output: &str,
+) -> Result<(), TipiLangError> {
+    let runtime = std::env::current_exe().map_err(map_underlying())?;
+    let executable = pack(&fs::read(&runtime).map_err(map_underlying())?, registry, assets)?;
+    fs::write(output, executable).map_err(map_underlying())?;
+    // keeps the executable bit
+    let permissions = fs::metadata(&runtime).map_err(map_underlying())?.permissions();
+    fs::set_permissions(output, permissions).map_err(map_underlying())
+}
+
+/// the bundle of the running executable, if it has one. Only the trailer and the bundle are read
+pub fn embedded() -> Result<Option<Bundle>, TipiLangError> {
+    let runtime = std::env::current_exe().map_err(map_underlying())?;
+    let mut file = fs::File::open(runtime).map_err(map_underlying())?;
+    let len = file.metadata().map_err(map_underlying())?.len() as usize;
+    if len < TRAILER_LEN {
+        return Ok(None);
+    }
+    let mut trailer = [0; TRAILER_LEN];
+    file.seek(SeekFrom::End(-(TRAILER_LEN as i64))).map_err(map_underlying())?;
+    file.read_exact(&mut trailer).map_err(map_underlying())?;
+    let Some(payload_len) = payload_len(&trailer, len) else {
+        return Ok(None);
+    };
+    let mut payload = vec![0; payload_len];
+    file.seek(SeekFrom::End(-((payload_len + TRAILER_LEN) as i64))).map_err(map_underlying())?;
+    file.read_exact(&mut payload).map_err(map_underlying())?;
+    decode(&payload).map(Some)
+}
+
+/// appends the bundle to the runtime. A bundle the runtime already has is replaced
+pub fn pack(
+    runtime: &[u8],
+    registry: &AsmRegistry,
+    assets: &HashMap<String, Vec<u8>>,
+) -> Result<Vec<u8>, TipiLangError> {
+    let runtime = &runtime[..runtime.len() - bundle_len(runtime)];
+    let mut assets: Vec<_> = assets.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    assets.sort();
+    let stored = StoredBundle {
+        bytecode: bytecode::write(registry)?,
+        assets,
+    };
+    let payload = postcard::to_allocvec(&stored).map_err(|e| Bytecode(e.to_string()))?;
+    let mut executable = runtime.to_vec();
+    executable.extend_from_slice(&payload);
+    executable.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    executable.extend_from_slice(MAGIC);
+    Ok(executable)
+}
+
+/// the bundle at the end of an executable, or none for a plain runtime
+pub fn unpack(executable: &[u8]) -> Result<Option<Bundle>, TipiLangError> {
+    let len = bundle_len(executable);
+    if len == 0 {
+        return Ok(None);
+    }
+    decode(&executable[executable.len() - len..executable.len() - TRAILER_LEN]).map(Some)
+}
+
+fn decode(payload: &[u8]) -> Result<Bundle, TipiLangError> {
+    let stored: StoredBundle =
+        postcard::from_bytes(payload).map_err(|e| Bytecode(e.to_string()))?;
+    Ok(Bundle {
+        registry: bytecode::read(&stored.bytecode)?,
+        assets: stored.assets.into_iter().collect(),
+    })
+}
+
+/// reads the files of a directory, by their path relative to it, like /css/site.css
+pub fn read_assets(dir: &str) -> Result<HashMap<String, Vec<u8>>, TipiLangError> {
+    let mut assets = HashMap::new();
+    for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+        if entry.file_type().is_file() {
+            let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+            let url_path: Vec<_> =
+                relative.iter().map(|segment| segment.to_string_lossy()).collect();
+            let url_path = format!("/{}", url_path.join("/"));
+            check_asset_path(&url_path)?;
+            let contents = fs::read(entry.path()).map_err(map_underlying())?;
+            assets.insert(url_path, contents);
+        }
+    }
+    Ok(assets)
+}
+
+/// a static file is served on its path, that cannot have the {param} and * syntax of a route
+pub fn check_asset_path(path: &str) -> Result<(), TipiLangError> {
+    let route_syntax = path.contains(['{', '}', '*'])
+        || path.split('/').any(|segment| segment.starts_with(':'));
+    if route_syntax {
+        return Err(Platform(format!(
+            "{}: a static file cannot have '{{', '}}', '*' or a leading ':' in its path",
+            path
+        )));
+    }
+    Ok(())
+}
+
+// the length of the bundle with its trailer, 0 when there is none
+fn bundle_len(executable: &[u8]) -> usize {
+    if executable.len() < TRAILER_LEN {
+        return 0;
+    }
+    let trailer = &executable[executable.len() - TRAILER_LEN..];
+    payload_len(trailer, executable.len()).map_or(0, |len| len + TRAILER_LEN)
+}
+
+// the length of the bundle before the trailer of an executable of the given length
+fn payload_len(trailer: &[u8], executable_len: usize) -> Option<usize> {
+    if !trailer.ends_with(MAGIC) {
+        return None;
+    }
+    let payload_len = u64::from_le_bytes(trailer[0..8].try_into().unwrap()) as usize;
+    (payload_len <= executable_len - TRAILER_LEN).then_some(payload_len)
+}
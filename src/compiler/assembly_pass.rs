@@ -1,72 +1,38 @@
-use crate::builtins::globals::GLOBAL_FUNCTIONS;
-use crate::builtins::lookup;
+use crate::AsmRegistry;
 use crate::compiler::assembly_pass::Op::{
-    Add, And, Assign, BitAnd, BitOr, BitXor, Call, CallBuiltin, Constant, DefList, DefMap, Divide,
-    Dup, Equal, Get, Goto, GotoIf, GotoIfNot, Greater, GreaterEqual, Less, LessEqual, ListGet,
-    Multiply, Negate, Not, NotEqual, Or, Pop, Print, Return, Shr, Subtract,
+    Assign, Call, CallBuiltin, CallValue, Cast, Closure, Concat, Constant, DefList, DefMap, Dup,
+    FieldGet, FieldSet, Get, Goto, GotoIfNot, Add, Less, LessEqual, ListGet, ListSet,
+    MapGet, MapSet, Pop, Print, Return, Slice, Take,
 };
-use crate::compiler::ast_pass::Expression::NamedParameter;
-use crate::compiler::ast_pass::{Expression, Function, Parameter, Statement};
+use crate::compiler::ast_pass::Parameter;
+use crate::compiler::ir_pass::{IrExpression, IrExpressionKind, IrFunction, IrStatement, IrStatementKind};
+use crate::compiler::link_pass::Link;
 use crate::compiler::tokens::TokenType;
-use crate::compiler::tokens::TokenType::Unknown;
-use crate::errors::CompilerError::{IncompatibleTypes, UndeclaredVariable};
-use crate::errors::{CompilerError, CompilerErrorAtLine};
-use crate::symbol_builder::{Symbol, calculate_type, infer_type};
 use crate::value::Value;
-use crate::{AsmRegistry, SymbolTable};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::ops::Deref;
-
-pub fn compile(
-    qualified_name: Option<&str>,
-    ast: &Vec<Statement>,
-    symbols: &SymbolTable,
-    registry: &mut AsmRegistry,
-) -> Result<(), CompilerErrorAtLine> {
-    compile_in_namespace(ast, qualified_name, symbols, registry)
-}
 
-pub fn compile_function(
-    function: &Function,
-    symbols: &SymbolTable,
-    registry: &mut AsmRegistry,
-    namespace: &str,
-) -> Result<AsmChunk, CompilerErrorAtLine> {
-    let fn_name = &function.name.lexeme;
-    let mut compiler = AsmPass::new(fn_name);
-    for parm in &function.parameters {
-        let name = parm.name.lexeme.clone();
-        let var_index = compiler.chunk.add_var(&parm.var_type, &parm.name.lexeme);
-
-        compiler.vars.insert(name, var_index);
-    }
-    let mut chunk = compiler.compile(&function.body, symbols, registry, namespace)?;
-    chunk.function_parameters = function.parameters.to_vec();
-    Ok(chunk)
+/// compiles the ir of a source file into a chunk, and its functions into chunks of their own
+pub fn compile(function: &IrFunction, registry: &mut AsmRegistry) {
+    let chunk = AsmPass::new(&function.name).compile(function, registry);
+    registry.insert(function.name.clone(), chunk);
 }
 
-pub fn compile_in_namespace(
-    ast: &Vec<Statement>,
-    namespace: Option<&str>,
-    symbols: &SymbolTable,
-    registry: &mut AsmRegistry,
-) -> Result<(), CompilerErrorAtLine> {
-    let name = namespace.unwrap_or("main");
-    let mut compiler = AsmPass::new(name);
-    let chunk = compiler.compile(ast, symbols, registry, name)?;
-    registry.insert(name.to_string(), chunk);
-    Ok(())
-}
-
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AsmChunk {
     pub(crate) name: String,
     pub code: Vec<Op>,
+    #[serde(with = "crate::compiler::bytecode::constants")]
     pub constants: Vec<Value>,
-    lines: Vec<usize>,
+    pub(crate) lines: Vec<usize>,
     pub(crate) object_defs: HashMap<String, Vec<Parameter>>,
     pub(crate) function_parameters: Vec<Parameter>,
+    /// slots of the enclosing variables a lambda captures, in the order of the captured values
+    pub(crate) captures: Vec<usize>,
     pub vars: Vec<(TokenType, String)>,
+    /// the calls by name and what they link to, once the registry is linked
+    #[serde(skip)]
+    pub(crate) links: Vec<(Op, Link)>,
 }
 
 impl AsmChunk {
@@ -78,7 +44,9 @@ impl AsmChunk {
             lines: vec![],
             object_defs: HashMap::new(),
             function_parameters: vec![],
+            captures: vec![],
             vars: vec![],
+            links: vec![],
         }
     }
 
@@ -87,6 +55,11 @@ impl AsmChunk {
         self.lines.push(line);
     }
 
+    /// the source line of the op at ip
+    pub(crate) fn line(&self, ip: usize) -> usize {
+        self.lines[ip]
+    }
+
     pub(crate) fn add_constant(&mut self, value: impl Into<Value>) -> usize {
         self.constants.push(value.into());
         self.constants.len() - 1
@@ -95,7 +68,7 @@ impl AsmChunk {
     pub(crate) fn find_constant(&self, p0: &String) -> Option<usize> {
         for (i, constant) in self.constants.iter().enumerate() {
             if let Value::String(s) = constant
-                && s == p0
+                && **s == **p0
             {
                 return Some(i);
             }
@@ -103,11 +76,6 @@ impl AsmChunk {
         None
     }
 
-    pub(crate) fn add_var(&mut self, var_type: &TokenType, name: &str) -> usize {
-        self.vars.push((var_type.clone(), name.to_string()));
-        self.vars.len() - 1
-    }
-
     pub(crate) fn add_object_def(&mut self, name: &str, fields: &[Parameter]) {
         self.object_defs.insert(name.to_string(), fields.to_vec());
     }
@@ -115,385 +83,358 @@ impl AsmChunk {
 
 pub struct AsmPass {
     chunk: AsmChunk,
-    _had_error: bool,
     current_line: usize,
-    vars: HashMap<String, usize>,
 }
 
 impl AsmPass {
     pub fn new(name: &str) -> Self {
         Self {
             chunk: AsmChunk::new(name),
-            _had_error: false,
             current_line: 0,
-            vars: HashMap::new(),
         }
     }
 
-    /// compile the entire AST into a chunk, adding a RETURN OP
-    pub fn compile(
-        &mut self,
-        ast: &Vec<Statement>,
-        symbols: &SymbolTable,
-        registry: &mut AsmRegistry,
-        namespace: &str,
-    ) -> Result<AsmChunk, CompilerErrorAtLine> {
-        self.compile_statements(ast, symbols, registry, namespace)?;
+    /// compile a function into a chunk, adding a RETURN OP
+    pub fn compile(mut self, function: &IrFunction, registry: &mut AsmRegistry) -> AsmChunk {
+        self.chunk.vars = function.locals.clone();
+        self.chunk.captures = function.captures.clone();
+        self.chunk.function_parameters = function.parameters.clone();
+        self.compile_statements(&function.body, registry);
         self.emit(Return);
-        let chunk = self.chunk.clone();
-        self.chunk.code.clear(); // in case the compiler is reused, clear it for the next compilation. This is for the REPL
-        Ok(chunk)
+        self.chunk
     }
 
-    /// compile the entire AST into a chunk
-    fn compile_statements(
-        &mut self,
-        ast: &Vec<Statement>,
-        symbols: &SymbolTable,
-        registry: &mut AsmRegistry,
-        namespace: &str,
-    ) -> Result<(), CompilerErrorAtLine> {
-        for statement in ast {
-            self.compile_statement(statement, symbols, registry, namespace)?;
+    fn compile_statements(&mut self, statements: &[IrStatement], registry: &mut AsmRegistry) {
+        for statement in statements {
+            self.compile_statement(statement, registry);
         }
-        Ok(())
     }
 
     /// compile a single statement
-    fn compile_statement(
-        &mut self,
-        statement: &Statement,
-        symbols: &SymbolTable,
-        registry: &mut AsmRegistry,
-        namespace: &str,
-    ) -> Result<(), CompilerErrorAtLine> {
-        self.current_line = statement.line();
-        match statement {
-            Statement::VarStmt {
-                name, initializer, ..
-            } => {
-                let name = name.lexeme.as_str();
-                let var = symbols.get(name);
-                if let Some(Symbol::Variable { var_type, .. }) = var {
-                    let inferred_type = infer_type(initializer, symbols);
-                    let calculated_type =
-                        calculate_type(var_type, &inferred_type).map_err(|e| self.raise(e))?;
-                    if var_type != &Unknown && var_type != &calculated_type {
-                        return Err(
-                            self.raise(IncompatibleTypes(var_type.clone(), calculated_type))
-                        );
-                    }
-                    let name_index = self.chunk.add_var(var_type, name);
-                    self.vars.insert(name.to_string(), name_index);
-                    self.compile_expression(namespace, initializer, symbols, registry)?;
-                    self.emit(Assign(name_index));
-                } else {
-                    return Err(self.raise(UndeclaredVariable(name.to_string())));
-                }
+    fn compile_statement(&mut self, statement: &IrStatement, registry: &mut AsmRegistry) {
+        self.current_line = statement.line;
+        match &statement.kind {
+            IrStatementKind::Let { slot, value } => {
+                self.compile_expression(value, registry);
+                self.emit(Assign(*slot));
             }
             // replace with function
-            Statement::PrintStmt { value } => {
-                self.compile_expression(namespace, value, symbols, registry)?;
+            IrStatementKind::Print(value) => {
+                self.compile_expression(value, registry);
                 self.emit(Print);
             }
-            Statement::ExpressionStmt { expression } => {
-                self.compile_expression(namespace, expression, symbols, registry)?;
+            IrStatementKind::Expression(expression) => {
+                self.compile_expression(expression, registry);
             }
-            Statement::FunctionStmt { function } => {
-                let function_name = function.name.lexeme.clone();
-                let compiled_function = compile_function(function, symbols, registry, namespace)?;
+            IrStatementKind::Function(function) => {
+                let compiled_function = AsmPass::new(&function.name).compile(function, registry);
                 registry.insert(
-                    format!("{}/{}", self.chunk.name, function_name),
+                    format!("{}/{}", self.chunk.name, function.name),
                     compiled_function,
                 );
             }
-            Statement::ObjectStmt { name, fields } => {
-                self.chunk.add_object_def(&name.lexeme, fields);
+            IrStatementKind::Object { name, fields } => {
+                self.chunk.add_object_def(name, fields);
             }
-            Statement::GuardStatement { .. } => {
-                unimplemented!("guard statement")
-            }
-            Statement::IfStatement {
+            IrStatementKind::If {
                 condition,
                 then_branch,
                 else_branch,
             } => {
-                self.compile_expression(namespace, condition, symbols, registry)?;
+                self.compile_expression(condition, registry);
 
                 self.emit(Dup);
                 self.emit(GotoIfNot(0)); // placeholder
                 let goto_addr1 = self.chunk.code.len() - 1;
                 self.emit(Pop);
-                self.compile_statements(then_branch, symbols, registry, namespace)?;
+                self.compile_statements(then_branch, registry);
                 self.emit(Goto(0));
                 let goto_addr2 = self.chunk.code.len() - 1; // placeholder
                 self.chunk.code[goto_addr1] = GotoIfNot(self.chunk.code.len());
-                if else_branch.is_some() {
-                    self.compile_statements(
-                        else_branch.as_ref().unwrap(),
-                        symbols,
-                        registry,
-                        namespace,
-                    )?;
+                if let Some(else_branch) = else_branch {
+                    self.compile_statements(else_branch, registry);
                 }
-                self.chunk.code[goto_addr2] = Op::Goto(self.chunk.code.len());
+                self.chunk.code[goto_addr2] = Goto(self.chunk.code.len());
             }
-            Statement::ForStatement {
-                loop_var,
+            IrStatementKind::RangeLoop {
+                slot,
+                end_slot,
+                inclusive,
                 range,
                 body,
-            } => {
-                // 1. step var index
-                let step_const_index = self.emit_constant(Value::I64(1));
-                // 2. range expression
-                self.compile_expression(namespace, range, symbols, registry)?;
-                //save the constants for lower and upper bounds of the range
-                let start_index = self.chunk.constants.len() - 1;
-                let end_index = self.chunk.constants.len() - 2;
-
-                let name = loop_var.lexeme.as_str();
-                let loop_var_name_index = self.chunk.add_var(&loop_var.token_type, name);
-                self.vars.insert(name.to_string(), loop_var_name_index);
-
-                // 3. start index
-                self.emit(Constant(start_index));
-                self.emit(Assign(loop_var_name_index));
-
-                let return_addr = self.chunk.code.len();
-                self.compile_statements(body, symbols, registry, namespace)?;
-                self.emit(Get(loop_var_name_index));
-                self.emit(Constant(step_const_index));
-                self.emit(Add);
-                self.emit(Assign(loop_var_name_index));
-                self.emit(Constant(end_index));
-                self.emit(Get(loop_var_name_index));
-                self.emit(GreaterEqual);
-                self.emit(GotoIf(return_addr));
-            }
+            } => self.compile_range_loop(*slot, *end_slot, *inclusive, range, body, registry),
+            IrStatementKind::ListLoop {
+                slot,
+                list_slot,
+                index_slot,
+                list,
+                body,
+            } => self.compile_list_loop(*slot, *list_slot, *index_slot, list, body, registry),
         }
-        Ok(())
     }
 
-    fn compile_expression(
+    fn compile_range_loop(
+        &mut self,
+        loop_var: usize,
+        end_var: usize,
+        inclusive: bool,
+        range: &IrExpression,
+        body: &[IrStatement],
+        registry: &mut AsmRegistry,
+    ) {
+        let IrExpressionKind::Range(lower, upper) = &range.kind else {
+            unreachable!("a range loop always has a range")
+        };
+        self.compile_expression(lower, registry);
+        self.emit(Assign(loop_var));
+        self.compile_expression(upper, registry);
+        self.emit(Assign(end_var));
+        let step_const_index = self.chunk.add_constant(Value::I64(1));
+
+        // while loop_var < end (or <= end for a..=b)
+        let return_addr = self.chunk.code.len();
+        self.emit(Get(loop_var));
+        self.emit(Get(end_var));
+        self.emit(if inclusive { LessEqual } else { Less });
+        self.emit(GotoIfNot(0)); // placeholder
+        let exit_addr = self.chunk.code.len() - 1;
+
+        self.compile_statements(body, registry);
+
+        self.emit(Get(loop_var));
+        self.emit(Constant(step_const_index));
+        self.emit(Add);
+        self.emit(Assign(loop_var));
+        self.emit(Goto(return_addr));
+        self.chunk.code[exit_addr] = GotoIfNot(self.chunk.code.len());
+    }
+
+    fn compile_list_loop(
+        &mut self,
+        loop_var: usize,
+        list_var: usize,
+        index_var: usize,
+        list: &IrExpression,
+        body: &[IrStatement],
+        registry: &mut AsmRegistry,
+    ) {
+        self.compile_expression(list, registry);
+        self.emit(Assign(list_var));
+        self.emit_constant(Value::U64(0));
+        self.emit(Assign(index_var));
+        let step_const_index = self.chunk.add_constant(Value::U64(1));
+
+        let len_index = self.string_constant("len");
+        let type_index = self.string_constant(&TokenType::untyped_list().to_string());
+
+        // while index < list.len()
+        let return_addr = self.chunk.code.len();
+        self.emit(Get(index_var));
+        self.emit(Get(list_var));
+        self.emit(CallBuiltin(len_index, type_index, 0));
+        self.emit(Less);
+        self.emit(GotoIfNot(0)); // placeholder
+        let exit_addr = self.chunk.code.len() - 1;
+
+        // loop_var = list[index]
+        self.emit(Get(list_var));
+        self.emit(Get(index_var));
+        self.emit(ListGet);
+        self.emit(Assign(loop_var));
+
+        self.compile_statements(body, registry);
+
+        self.emit(Get(index_var));
+        self.emit(Constant(step_const_index));
+        self.emit(Add);
+        self.emit(Assign(index_var));
+        self.emit(Goto(return_addr));
+        self.chunk.code[exit_addr] = GotoIfNot(self.chunk.code.len());
+    }
+
+    fn compile_lambda(
         &mut self,
         namespace: &str,
-        expression: &Expression,
-        symbols: &SymbolTable,
+        function: &IrFunction,
+        captured: &[IrExpression],
         registry: &mut AsmRegistry,
-    ) -> Result<(), CompilerErrorAtLine> {
-        match expression {
-            Expression::FunctionCall {
-                name, arguments, ..
-            } => {
-                let name_index = self
-                    .chunk
-                    .find_constant(name)
-                    .unwrap_or_else(|| self.chunk.add_constant(Value::String(name.to_string())));
-                let function = symbols.get(name);
-                match function {
-                    Some(Symbol::Function { parameters, .. }) => {
-                        self.get_arguments_in_order(
-                            namespace, symbols, registry, arguments, parameters,
-                        )?;
-
-                        self.emit(Call(name_index, arguments.len()));
-                    }
-                    // constructor function
-                    Some(Symbol::Object { fields, .. }) => {
-                        self.get_arguments_in_order(
-                            namespace, symbols, registry, arguments, fields,
-                        )?;
-                        self.emit(Call(name_index, arguments.len()));
-                    }
-                    // maybe global function
-                    _ => {
-                        if let Some(fun) = GLOBAL_FUNCTIONS.get(name) {
-                            self.emit(Call(name_index, fun.arity()));
-                        } else {
-                            return Err(
-                                self.raise(CompilerError::FunctionNotFound(name.to_string()))
-                            );
-                        }
-                    }
-                }
+    ) {
+        let name = format!("{}/lambda#{}", namespace, registry.len());
+        // reserve the name, so that nested lambdas get their own
+        registry.insert(name.clone(), AsmChunk::new(&name));
+        let chunk = AsmPass::new(&name).compile(function, registry);
+
+        // captured values are copied into the function value when it is created
+        for value in captured {
+            self.compile_expression(value, registry);
+        }
+        let name_index = self.string_constant(&name);
+        self.emit(Closure(name_index, captured.len()));
+        registry.insert(name, chunk);
+    }
+
+    fn string_constant(&mut self, value: &str) -> usize {
+        self.chunk
+            .find_constant(&value.to_string())
+            .unwrap_or_else(|| self.chunk.add_constant(Value::String(value.to_string().into())))
+    }
+
+    fn compile_expression(&mut self, expression: &IrExpression, registry: &mut AsmRegistry) {
+        match &expression.kind {
+            IrExpressionKind::Call(name, arguments) => {
+                let name_index = self.string_constant(name);
+                self.compile_expressions(arguments, registry);
+                self.emit(Call(name_index, arguments.len()));
+            }
+            IrExpressionKind::CallValue(function, arguments) => {
+                self.compile_expression(function, registry);
+                self.compile_expressions(arguments, registry);
+                self.emit(CallValue(arguments.len()));
             }
-            Expression::MethodCall {
+            IrExpressionKind::MethodCall {
                 receiver,
-                method_name,
+                receiver_type,
+                method,
                 arguments,
-                ..
             } => {
-                self.compile_expression(namespace, receiver, symbols, registry)?;
-                let receiver_type = infer_type(receiver, symbols).to_string();
-
-                let type_index = self.chunk.find_constant(&receiver_type).unwrap_or_else(|| {
-                    self.chunk
-                        .add_constant(Value::String(receiver_type.clone()))
-                });
-
-                let name_index = self.chunk.find_constant(method_name).unwrap_or_else(|| {
-                    self.chunk
-                        .add_constant(Value::String(method_name.to_string()))
-                });
-                let signature = lookup(&receiver_type, method_name).map_err(|e| self.raise(e))?;
-                if signature.arity() != arguments.len() {
-                    return Err(self.raise(CompilerError::IllegalArgumentsException(
-                        format!("{}.{}", receiver_type, method_name),
-                        signature.parameters.len(),
-                        arguments.len(),
-                    )));
-                }
-                self.get_arguments_in_order(
-                    namespace,
-                    symbols,
-                    registry,
-                    arguments,
-                    &signature.parameters,
-                )?;
+                self.compile_expression(receiver, registry);
+                let type_index = self.string_constant(receiver_type);
+                let name_index = self.string_constant(method);
+                self.compile_expressions(arguments, registry);
                 self.emit(CallBuiltin(name_index, type_index, arguments.len()));
             }
-            Expression::Variable { name, .. } => {
-                let name_index = self.vars.get(name);
-                if let Some(name_index) = name_index {
-                    self.emit(Get(*name_index));
-                } else {
-                    return Err(self.raise(UndeclaredVariable(name.to_string())));
-                }
+            IrExpressionKind::Get(slot) => self.emit(Get(*slot)),
+            IrExpressionKind::Take(slot) => self.emit(Take(*slot)),
+            IrExpressionKind::FunctionRef(name) => {
+                let name_index = self.string_constant(name);
+                self.emit(Closure(name_index, 0));
             }
-            Expression::Assignment {
-                variable_name,
+            IrExpressionKind::Assign(slot, value) => {
+                self.compile_expression(value, registry);
+                self.emit(Assign(*slot));
+            }
+            IrExpressionKind::Cast(value, target_type) => {
+                self.compile_expression(value, registry);
+                self.emit(Cast(target_type.clone()));
+            }
+            IrExpressionKind::Store {
+                keys,
+                target,
                 value,
-                ..
             } => {
-                self.compile_expression(namespace, value, symbols, registry)?;
-                let name_index = self.vars.get(variable_name);
-                if let Some(name_index) = name_index {
-                    self.emit(Assign(*name_index));
-                } else {
-                    return Err(self.raise(UndeclaredVariable(variable_name.to_string())));
+                for (slot, key) in keys {
+                    self.compile_expression(key, registry);
+                    self.emit(Assign(*slot));
                 }
+                self.compile_expression(value, registry);
+                self.compile_store(target, registry);
             }
-            Expression::Literal { value, .. } => {
+            IrExpressionKind::Constant(value) => {
                 self.emit_constant(value.clone());
             }
-            Expression::List { values, .. } => {
-                for expr in values {
-                    self.compile_expression(namespace, expr, symbols, registry)?;
-                }
+            IrExpressionKind::List(values) => {
+                self.compile_expressions(values, registry);
                 self.emit(DefList(values.len()));
             }
-            Expression::Map { entries, .. } => {
+            IrExpressionKind::Map(entries) => {
                 for (key, value) in entries {
-                    self.compile_expression(namespace, key, symbols, registry)?;
-                    self.compile_expression(namespace, value, symbols, registry)?;
+                    self.compile_expression(key, registry);
+                    self.compile_expression(value, registry);
                 }
                 self.emit(DefMap(entries.len()));
             }
-            Expression::Grouping { expression, .. } => {
-                self.compile_expression(namespace, expression, symbols, registry)?
+            IrExpressionKind::Unary(op, right) => {
+                self.compile_expression(right, registry);
+                self.emit(op.clone());
             }
-            Expression::Unary {
-                operator, right, ..
-            } => {
-                self.compile_expression(namespace, right, symbols, registry)?;
-                match operator.token_type {
-                    TokenType::Minus => {
-                        self.emit(Negate);
-                    }
-                    TokenType::Bang => {
-                        self.emit(Not);
-                    }
-                    _ => unimplemented!("unary other than ! and -"),
-                }
+            IrExpressionKind::Binary(op, left, right) => {
+                self.compile_expression(left, registry);
+                self.compile_expression(right, registry);
+                self.emit(op.clone());
             }
-            Expression::Binary {
-                left,
-                operator,
-                right,
-                ..
+            IrExpressionKind::Stop => {}
+            IrExpressionKind::ListGet(list, index) => {
+                self.compile_expression(list, registry);
+                self.compile_expression(index, registry);
+                self.emit(ListGet);
+            }
+            IrExpressionKind::Slice {
+                operand,
+                lower,
+                upper,
+                inclusive,
             } => {
-                self.compile_expression(namespace, left, symbols, registry)?;
-                self.compile_expression(namespace, right, symbols, registry)?;
-                match operator.token_type {
-                    TokenType::BitAnd => self.emit(BitAnd),
-                    TokenType::BitXor => self.emit(BitXor),
-                    TokenType::Equal => {
-                        if let Expression::Variable { name, .. } = left.deref() {
-                            let index = self.vars.get(name).unwrap();
-                            self.emit(Assign(*index));
-                            self.emit(Pop);
-                        } else {
-                            return Err(self.raise(UndeclaredVariable("".to_string())));
+                self.compile_expression(operand, registry);
+                // a missing bound is void
+                for bound in [lower, upper] {
+                    match bound {
+                        Some(bound) => self.compile_expression(bound, registry),
+                        None => {
+                            self.emit_constant(Value::Void);
                         }
                     }
-                    TokenType::EqualEqual => self.emit(Equal),
-                    TokenType::BangEqual => self.emit(NotEqual),
-                    TokenType::Greater => self.emit(Greater),
-                    TokenType::GreaterEqual => self.emit(GreaterEqual),
-                    TokenType::GreaterGreater => self.emit(Shr),
-                    TokenType::Less => self.emit(Less),
-                    TokenType::LessEqual => self.emit(LessEqual),
-                    TokenType::LessLess => self.emit(Op::Shl),
-                    TokenType::LogicalAnd => self.emit(And),
-                    TokenType::LogicalOr => self.emit(Or),
-                    TokenType::Minus => self.emit(Subtract),
-                    TokenType::Pipe => self.emit(BitOr),
-                    TokenType::Plus => self.emit(Add),
-                    TokenType::Slash => self.emit(Divide),
-                    TokenType::Star => self.emit(Multiply),
-                    _ => unimplemented!("binary other than plus, minus, star, slash"),
                 }
+                self.emit(Slice(*inclusive));
             }
-            Expression::Stop { .. } => {}
-            NamedParameter { value, .. } => {
-                self.compile_expression(namespace, value, symbols, registry)?
+            IrExpressionKind::MapGet(map, key) => {
+                self.compile_expression(map, registry);
+                self.compile_expression(key, registry);
+                self.emit(MapGet);
             }
-            Expression::ListGet { index, list } => {
-                self.compile_expression(namespace, list, symbols, registry)?;
-                self.compile_expression(namespace, index, symbols, registry)?;
-                self.emit(ListGet);
+            IrExpressionKind::FieldGet(receiver, field) => {
+                self.compile_expression(receiver, registry);
+                let name_index = self.string_constant(field);
+                self.emit(FieldGet(name_index));
             }
-            Expression::MapGet { .. } => {}
-            Expression::FieldGet { .. } => {}
-            Expression::Range { lower, upper, .. } => {
+            IrExpressionKind::Interpolation(parts) => {
+                self.compile_expressions(parts, registry);
+                self.emit(Concat(parts.len()));
+            }
+            IrExpressionKind::Closure {
+                namespace,
+                function,
+                captured,
+            } => self.compile_lambda(namespace, function, captured, registry),
+            IrExpressionKind::Range(lower, upper) => {
                 // opposite order, because we have to assign last one first to the loop variable
-                self.compile_expression(namespace, upper, symbols, registry)?;
-                self.compile_expression(namespace, lower, symbols, registry)?;
+                self.compile_expression(upper, registry);
+                self.compile_expression(lower, registry);
             }
         }
-        Ok(())
     }
 
-    // any unnamed parameters must be passed in order
-    // named parameters do not have to be passed in order, but they do need to be evaluated in the order of the called function/constructor
-    fn get_arguments_in_order(
-        &mut self,
-        namespace: &str,
-        symbols: &SymbolTable,
-        registry: &mut AsmRegistry,
-        arguments: &[Expression],
-        parameters: &[Parameter],
-    ) -> Result<(), CompilerErrorAtLine> {
-        for argument in arguments {
-            for parameter in parameters {
-                if let NamedParameter { name, value, .. } = argument {
-                    if name.lexeme == parameter.name.lexeme {
-                        let value_type = infer_type(value, symbols);
-                        if parameter.var_type != value_type {
-                            return Err(self
-                                .raise(IncompatibleTypes(parameter.var_type.clone(), value_type)));
-                        } else {
-                            self.compile_expression(namespace, argument, symbols, registry)?;
-                            break;
-                        }
-                    }
-                } else {
-                    self.compile_expression(namespace, argument, symbols, registry)?;
-                    break;
-                }
+    fn compile_expressions(&mut self, expressions: &[IrExpression], registry: &mut AsmRegistry) {
+        for expression in expressions {
+            self.compile_expression(expression, registry);
+        }
+    }
+
+    // stores the value on top of the stack in the element, then the updated collection in its
+    // own container, up to the variable: xs[0][1] = v updates xs[0] and then xs
+    fn compile_store(&mut self, target: &IrExpression, registry: &mut AsmRegistry) {
+        match &target.kind {
+            IrExpressionKind::Get(slot) | IrExpressionKind::Take(slot) => self.emit(Assign(*slot)),
+            IrExpressionKind::ListGet(list, index) => {
+                self.compile_expression(list, registry);
+                self.compile_expression(index, registry);
+                self.emit(ListSet);
+                self.compile_store(list, registry);
             }
+            IrExpressionKind::MapGet(map, key) => {
+                self.compile_expression(map, registry);
+                self.compile_expression(key, registry);
+                self.emit(MapSet);
+                self.compile_store(map, registry);
+            }
+            IrExpressionKind::FieldGet(receiver, field) => {
+                self.compile_expression(receiver, registry);
+                let name_index = self.string_constant(field);
+                self.emit(FieldSet(name_index));
+                self.compile_store(receiver, registry);
+            }
+            // the ir pass only lets variables and their elements through
+            _ => unreachable!("assignment to {:?}", target.kind),
         }
-        Ok(())
     }
 
     fn emit(&mut self, op: Op) {
@@ -505,13 +446,9 @@ impl AsmPass {
         self.emit(Constant(index));
         index
     }
-
-    fn raise(&self, error: CompilerError) -> CompilerErrorAtLine {
-        CompilerErrorAtLine::raise(error, self.current_line)
-    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Op {
     Constant(usize),
     Add,
@@ -522,6 +459,8 @@ pub enum Op {
     Print,
     Return,
     Call(usize, usize),
+    /// a call after linking, to the link at the index
+    CallLinked(usize, usize),
     And,
     Or,
     Not,
@@ -538,11 +477,25 @@ pub enum Op {
     Shl,
     Pop,
     Get(usize),
+    /// gets the value of a variable and leaves void, so that the value is not shared
+    Take(usize),
     DefList(usize),
     DefMap(usize),
     Assign(usize),
     ListGet,
+    ListSet,
+    Slice(bool),
+    MapGet,
+    MapSet,
+    FieldGet(usize),
+    FieldSet(usize),
+    Remainder,
+    Power,
+    Cast(TokenType),
     CallBuiltin(usize, usize, usize),
+    CallValue(usize),
+    Closure(usize, usize),
+    Concat(usize),
     Dup,
     GotoIf(usize),
     GotoIfNot(usize),
@@ -1,22 +1,28 @@
 use crate::compiler::ast_pass::Expression::{
-    Assignment, FieldGet, FunctionCall, ListGet, MapGet, MethodCall, NamedParameter, Stop, Variable,
+    Assignment, ElementAssignment, FieldGet, FunctionCall, ListGet, MapGet, MethodCall,
+    NamedParameter, Slice, Stop, ValueCall, Variable,
 };
 use crate::compiler::tokens::TokenType::{
-    Bang, Bool, Char, Colon, DateTime, Dot, Else, Eof, Eol, Equal, False, FloatingPoint, Fn, For,
+    As, Bang, Bool, Char, Colon, DateTime, Dot, Else, Eof, Eol, Equal, False, FloatingPoint, Fn, For,
     Greater, GreaterEqual, GreaterGreater, Identifier, If, In, Indent, Integer, LeftBrace,
-    LeftBracket, LeftParen, Less, LessEqual, LessLess, Let, ListType, MapType, Minus, Object, Plus,
-    Print, Range, RightBrace, RightBracket, RightParen, SingleRightArrow, Slash, Star, StringType,
-    True, U32, U64, Unknown,
+    LeftBracket, LeftParen, Less, LessEqual, LessLess, Let, ListType, MapType, Minus, Object,
+    MinusEqual, ObjectType, Percent, PercentEqual, Plus, PlusEqual, Print, Range, RangeInclusive,
+    RightBrace, RightBracket, RightParen, SingleRightArrow, Slash, SlashEqual, Star, StarEqual,
+    StarStar, StringType, True, U32, U64, Unknown, Uuid,
 };
-use crate::compiler::tokens::{Token, TokenType};
+use crate::compiler::tokens::{Span, Token, TokenType};
 use crate::errors::CompilerError::{
-    self, Expected, ParseError, TooManyParameters, UnexpectedIndent, UninitializedVariable,
+    self, Expected, InvalidLiteral, ParseError, TooManyParameters, UnexpectedIndent,
+    UninitializedVariable,
 };
 use crate::errors::CompilerErrorAtLine;
-use crate::symbol_builder::{Symbol, calculate_type, infer_type};
+use crate::symbol_builder::{
+    Symbol, calculate_element_type, calculate_type, infer_element_type, infer_type, is_convertible,
+};
 use crate::value::Value;
-use crate::{DATE_FORMAT_TIMEZONE, Expr, Stmt, SymbolTable};
+use crate::{DATE_FORMAT, DATE_FORMAT_TIMEZONE, Expr, Stmt, SymbolTable};
 use log::debug;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use crate::builtins::globals::GLOBAL_FUNCTIONS;
 
@@ -24,7 +30,7 @@ pub fn compile(
     path: Option<&str>,
     tokens: Vec<Token>,
     symbol_table: &mut SymbolTable,
-) -> Result<Vec<Statement>, CompilerErrorAtLine> {
+) -> Result<Vec<Statement>, Vec<CompilerErrorAtLine>> {
     let mut compiler = AstCompiler::new(path.unwrap_or(""), tokens);
     compiler.compile_tokens(symbol_table)
 }
@@ -40,7 +46,7 @@ pub struct Function {
 struct AstCompiler {
     tokens: Vec<Token>,
     current: usize,
-    had_error: bool,
+    errors: Vec<CompilerErrorAtLine>,
     indent: Vec<usize>,
 }
 
@@ -49,7 +55,7 @@ impl AstCompiler {
         Self {
             tokens,
             current: 0,
-            had_error: false,
+            errors: vec![],
             indent: vec![0],
         }
     }
@@ -61,37 +67,84 @@ impl AstCompiler {
     fn compile_tokens(
         &mut self,
         symbol_table: &mut HashMap<String, Symbol>,
-    ) -> Result<Vec<Statement>, CompilerErrorAtLine> {
+) -> Result<Vec<Statement>, Vec<CompilerErrorAtLine>> {
         self.reset();
-        self.compile(symbol_table)
+        let statements = self.compile(symbol_table);
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
     }
 
-    fn compile(
-        &mut self,
-        symbol_table: &mut SymbolTable,
-    ) -> Result<Vec<Statement>, CompilerErrorAtLine> {
-        if !self.had_error {
-            let mut statements = vec![];
-            while !self.is_at_end() {
-                if self.match_token(&[Eol]) {
-                    continue;
-                }
-                let statement = self.indent(symbol_table)?;
-                if let Some(statement) = statement {
-                    statements.push(statement);
-                } else {
-                    break;
+    /// compiles the statements of a block, collecting errors instead of stopping at the first
+    fn compile(&mut self, symbol_table: &mut SymbolTable) -> Vec<Statement> {
+        let mut statements = vec![];
+        while !self.is_at_end() {
+            if self.match_token(&[Eol]) {
+                continue;
+            }
+            let indent_level = self.indent.len();
+            match self.indent(symbol_table) {
+                Ok(Some(statement)) => statements.push(statement),
+                Ok(None) => break,
+                Err(error) => {
+                    self.errors.push(error);
+                    // blocks that were entered by the failing statement are left
+                    self.indent.truncate(indent_level);
+                    self.synchronize();
                 }
             }
-            debug!("AST {:?}", statements);
-            Ok(statements)
-        } else {
-            Err(self.raise(CompilerError::Failure))
+        }
+        debug!("AST {:?}", statements);
+        statements
+    }
+
+    /// skips to the next statement after an error: the rest of the line
+    /// and any lines indented below it, like the body of a failing function
+    fn synchronize(&mut self) {
+        let expected_indent = *self.indent.last().unwrap();
+        loop {
+            while !self.is_at_end() && !self.match_token(&[Eol]) {
+                self.advance();
+            }
+            let mut lookahead = self.current;
+            while self.tokens[lookahead].token_type == Indent {
+                lookahead += 1;
+            }
+            let indent_on_line = lookahead - self.current;
+            let blank_line = self.tokens[lookahead].token_type == Eol;
+            if self.is_at_end() || (indent_on_line <= expected_indent && !blank_line) {
+                break;
+            }
         }
     }
 
     fn raise(&self, error: CompilerError) -> CompilerErrorAtLine {
-        CompilerErrorAtLine::raise(error, self.current_line())
+        CompilerErrorAtLine::raise(error, self.current_line()).at(self.peek().span)
+    }
+
+    // for errors in an expression that has already been parsed
+    // the parser may already be on the next line, so the line comes from the span
+    fn raise_at(&self, error: CompilerError, span: Span) -> CompilerErrorAtLine {
+        let line = if span.line > 0 { span.line as usize } else { self.current_line() };
+        CompilerErrorAtLine::raise(error, line).at(span)
+    }
+
+    // a literal that cannot be parsed into its value
+    fn literal_error(&self, message: String) -> CompilerErrorAtLine {
+        self.raise_at(ParseError(message), self.previous().span)
+    }
+
+    // a date or uuid literal that is not valid for its type
+    fn invalid_literal(&self, literal_type: TokenType) -> CompilerErrorAtLine {
+        let error = InvalidLiteral(literal_type, self.previous().lexeme.clone());
+        self.raise_at(error, self.previous().span)
+    }
+
+    // the span from start up to the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous().span)
     }
 
     fn indent(
@@ -120,7 +173,9 @@ impl AstCompiler {
     }
 
     fn declaration(&mut self, symbol_table: &mut SymbolTable) -> Stmt {
-        if self.match_token(&[Fn]) {
+        // fn( starts a lambda, that is an expression
+        if self.check(&Fn) && !self.check_next(&LeftParen) {
+            self.advance();
             self.function_declaration(symbol_table)
         } else if self.match_token(&[Let]) {
             self.let_declaration(symbol_table)
@@ -154,6 +209,7 @@ impl AstCompiler {
         }
         Ok(Stop {
             line: self.peek().line,
+            span: self.peek().span,
         })
     }
 
@@ -165,6 +221,7 @@ impl AstCompiler {
         } else {
             Ok(Stop {
                 line: self.peek().line,
+                span: self.peek().span,
             })
         }
     }
@@ -177,6 +234,7 @@ impl AstCompiler {
         } else {
             Ok(Stop {
                 line: self.peek().line,
+                span: self.peek().span,
             })
         }
     }
@@ -205,16 +263,19 @@ impl AstCompiler {
             if !done {
                 let field_name = self.consume(&Identifier, Expected("an object field name."))?;
                 self.consume(&Colon, Expected("':' after field name."))?;
-                let field_type = self.peek().token_type.clone();
-                if field_type.is_type() {
-                    self.advance();
+                let field_type = if self.peek().token_type.is_type() {
+                    self.type_declaration()?
                 } else {
                     Err(self.raise(Expected("a type")))?
-                }
+                };
                 fields.push(Parameter {
                     name: field_name,
                     var_type: field_type,
                 });
+                // the next field
+                if self.check(&Eol) && self.check_next(&Indent) {
+                    self.advance();
+                }
             }
         }
         self.consume(&Eol, Expected("end of line."))?;
@@ -237,9 +298,49 @@ impl AstCompiler {
     fn function_declaration(&mut self, symbol_table: &mut SymbolTable) -> Stmt {
         let name_token = self.consume(&Identifier, Expected("function name."))?;
         if GLOBAL_FUNCTIONS.contains_key(name_token.lexeme.as_str()) {
-            return Err(self.raise(CompilerError::ReservedFunctionName(name_token.lexeme.clone())))
+            let error = CompilerError::ReservedFunctionName(name_token.lexeme.clone());
+            return Err(self.raise_at(error, name_token.span));
         }
         self.consume(&LeftParen, Expected("'(' after function name."))?;
+        let parameters = self.parameters()?;
+        let return_type = if self.check(&SingleRightArrow) {
+            self.consume(&SingleRightArrow, Expected("->"))?;
+            self.type_declaration()?
+        } else {
+            TokenType::Void
+        };
+        self.consume(&Colon, Expected("colon (:) after function declaration."))?;
+        self.consume(&Eol, Expected("end of line."))?;
+
+        // the signature is known before the body, so the function can be used as a value
+        // and in the body itself. The symbol builder adds the body later
+        symbol_table.insert(
+            name_token.lexeme.clone(),
+            Symbol::Function {
+                name: name_token.lexeme.clone(),
+                parameters: parameters.clone(),
+                return_type: return_type.clone(),
+                body: vec![],
+            },
+        );
+
+        self.inc_indent();
+
+        let shadowed = declare_parameters(&parameters, symbol_table);
+        let body = self.compile(symbol_table);
+        restore(shadowed, symbol_table);
+
+        let function = Function {
+            name: name_token.clone(),
+            parameters,
+            return_type,
+            body,
+        };
+
+        Ok(Statement::FunctionStmt { function })
+    }
+
+    fn parameters(&mut self) -> Result<Vec<Parameter>, CompilerErrorAtLine> {
         let mut parameters = vec![];
         while !self.check(&RightParen) {
             if parameters.len() >= 25 {
@@ -248,9 +349,7 @@ impl AstCompiler {
             let parm_name = self.consume(&Identifier, Expected("a parameter name."))?;
 
             self.consume(&Colon, Expected(": after parameter name"))?;
-            let var_type = self.peek().token_type.clone();
-
-            self.advance();
+            let var_type = self.type_declaration()?;
             parameters.push(Parameter {
                 name: parm_name,
                 var_type,
@@ -260,27 +359,100 @@ impl AstCompiler {
             }
         }
         self.consume(&RightParen, Expected(" ')' after parameters."))?;
-        let return_type = if self.check(&SingleRightArrow) {
-            self.consume(&SingleRightArrow, Expected("->"))?;
-            self.advance().token_type.clone()
+        Ok(parameters)
+    }
+
+    // fn(x: i64) -> i64: x * 2
+    fn lambda(&mut self, symbol_table: &mut SymbolTable) -> Expr {
+        let fn_token = self.previous().clone();
+        self.consume(&LeftParen, Expected("'(' after fn."))?;
+        let parameters = self.parameters()?;
+        let declared_type = if self.match_token(&[SingleRightArrow]) {
+            self.type_declaration()?
         } else {
-            TokenType::Void
+            Unknown
         };
-        self.consume(&Colon, Expected("colon (:) after function declaration."))?;
-        self.consume(&Eol, Expected("end of line."))?;
-
-        self.inc_indent();
-
-        let body = self.compile(symbol_table)?;
-
-        let function = Function {
-            name: name_token.clone(),
-            parameters,
-            return_type,
-            body,
+        self.consume(&Colon, Expected("colon (:) after lambda parameters."))?;
+        let shadowed = declare_parameters(&parameters, symbol_table);
+        let typed_body = self.expression(symbol_table).and_then(|body| {
+            let inferred_type = self.type_of(&body, symbol_table)?;
+            Ok((body, inferred_type))
+        });
+        restore(shadowed, symbol_table);
+        let (body, inferred_type) = typed_body?;
+        let return_type = if inferred_type == Unknown {
+            declared_type
+        } else {
+            calculate_type(&declared_type, &inferred_type)
+                .map_err(|e| self.raise_at(e, body.span()))?
         };
+        Ok(Expression::Lambda {
+            line: fn_token.line,
+            span: self.span_from(fn_token.span),
+            function: Box::new(Function {
+                name: fn_token,
+                parameters,
+                return_type,
+                body: vec![Statement::ExpressionStmt { expression: body }],
+            }),
+        })
+    }
 
-        Ok(Statement::FunctionStmt { function })
+    // i64, string, Customer, list<Customer>, map<string, list<i64>>, fn(i64) -> i64, etc
+    fn type_declaration(&mut self) -> Result<TokenType, CompilerErrorAtLine> {
+        let token = self.advance().clone();
+        Ok(match token.token_type {
+            Fn => {
+                self.consume(&LeftParen, Expected("'(' after fn."))?;
+                let mut parameter_types = vec![];
+                while !self.match_token(&[RightParen]) {
+                    parameter_types.push(self.type_declaration()?);
+                    if !self.match_token(&[TokenType::Comma]) {
+                        self.consume(&RightParen, Expected("')' after parameter types."))?;
+                        break;
+                    }
+                }
+                let return_type = if self.match_token(&[SingleRightArrow]) {
+                    self.type_declaration()?
+                } else {
+                    TokenType::Void
+                };
+                TokenType::FunctionType(parameter_types, Box::new(return_type))
+            }
+            ListType(_) => {
+                if self.match_token(&[Less]) {
+                    let element_type = self.type_declaration()?;
+                    self.close_type_arguments()?;
+                    ListType(Box::new(element_type))
+                } else {
+                    TokenType::untyped_list()
+                }
+            }
+            MapType(_, _) => {
+                if self.match_token(&[Less]) {
+                    let key_type = self.type_declaration()?;
+                    self.consume(&TokenType::Comma, Expected("',' after map key type."))?;
+                    let value_type = self.type_declaration()?;
+                    self.close_type_arguments()?;
+                    MapType(Box::new(key_type), Box::new(value_type))
+                } else {
+                    TokenType::untyped_map()
+                }
+            }
+            Identifier => ObjectType(token.lexeme),
+            token_type => token_type,
+        })
+    }
+
+    fn close_type_arguments(&mut self) -> Result<(), CompilerErrorAtLine> {
+        // nested type arguments like list<list<i64>> are scanned as a shift operator
+        if self.check(&GreaterGreater) {
+            self.tokens[self.current].token_type = Greater;
+            Ok(())
+        } else {
+            self.consume(&Greater, Expected("'>' after type arguments."))
+                .map(|_| ())
+        }
     }
 
     fn let_declaration(&mut self, symbol_table: &mut SymbolTable) -> Stmt {
@@ -293,7 +465,7 @@ impl AstCompiler {
 
         let declared_type = if self.check(&Colon) {
             self.advance();
-            Some(self.advance().token_type.clone())
+            Some(self.type_declaration()?)
         } else {
             None
         };
@@ -301,9 +473,9 @@ impl AstCompiler {
         if self.match_token(&[Equal]) {
             let initializer = self.expression(symbol_table)?;
             let declared_type = declared_type.unwrap_or(Unknown);
-            let inferred_type = infer_type(&initializer, symbol_table);
-            let var_type =
-                calculate_type(&declared_type, &inferred_type).map_err(|e| self.raise(e))?;
+            let inferred_type = self.type_of(&initializer, symbol_table)?;
+            let var_type = calculate_type(&declared_type, &inferred_type)
+                .map_err(|e| self.raise_at(e, initializer.span()))?;
             symbol_table.insert(
                 name_token.lexeme.clone(),
                 Symbol::Variable {
@@ -340,10 +512,22 @@ impl AstCompiler {
         let loop_var = self.consume(&Identifier, Expected("loop variable name."))?;
         self.consume(&In, Expected("'in' after loop variable name."))?;
         let range = self.expression(symbol_table)?;
+        let loop_var_type = match self.type_of(&range, symbol_table)? {
+            ListType(element_type) if *element_type != TokenType::Any => *element_type,
+            ListType(_) => Unknown,
+            range_type => calculate_type(&Unknown, &range_type).map_err(|e| self.raise(e))?,
+        };
         self.consume(&Colon, Expected("colon after range expression"))?;
         self.consume(&Eol, Expected("end of line after for expression."))?;
+        symbol_table.insert(
+            loop_var.lexeme.clone(),
+            Symbol::Variable {
+                name: loop_var.lexeme.clone(),
+                var_type: loop_var_type,
+            },
+        );
         self.inc_indent();
-        let body = self.compile(symbol_table)?;
+        let body = self.compile(symbol_table);
 
         Ok(Statement::ForStatement {
             loop_var,
@@ -357,14 +541,14 @@ impl AstCompiler {
         self.consume(&Colon, Expected("':' after if condition."))?;
 
         self.inc_indent();
-        let then_branch = self.compile(symbol_table)?;
+        let then_branch = self.compile(symbol_table);
 
         let else_branch = if self.check(&Else) {
             self.consume(&Else, Expected("'else' after if condition."))?;
             self.consume(&Colon, Expected("':' after 'else'."))?;
 
             self.inc_indent();
-            Some(self.compile(symbol_table)?)
+            Some(self.compile(symbol_table))
         } else {
             None
         };
@@ -399,45 +583,75 @@ impl AstCompiler {
 
     fn or(&mut self, symbol_table: &mut SymbolTable) -> Expr {
         let expr = self.and(symbol_table)?;
-        self.binary(&[TokenType::LogicalOr], expr, symbol_table)
+        self.binary(&[TokenType::LogicalOr], expr, Self::and, symbol_table)
     }
 
     fn and(&mut self, symbol_table: &mut SymbolTable) -> Expr {
         let expr = self.bit_and(symbol_table)?;
-        self.binary(&[TokenType::LogicalAnd], expr, symbol_table)
+        self.binary(&[TokenType::LogicalAnd], expr, Self::bit_and, symbol_table)
     }
 
     fn bit_and(&mut self, symbol_table: &mut SymbolTable) -> Expr {
         let expr = self.bit_or(symbol_table)?;
-        self.binary(&[TokenType::BitAnd], expr, symbol_table)
+        self.binary(&[TokenType::BitAnd], expr, Self::bit_or, symbol_table)
     }
 
     fn bit_or(&mut self, symbol_table: &mut SymbolTable) -> Expr {
         let expr = self.bit_xor(symbol_table)?;
-        self.binary(&[TokenType::Pipe], expr, symbol_table)
+        self.binary(&[TokenType::Pipe], expr, Self::bit_xor, symbol_table)
     }
 
     fn bit_xor(&mut self, symbol_table: &mut SymbolTable) -> Expr {
         let expr = self.assignment(symbol_table)?;
-        self.binary(&[TokenType::BitXor], expr, symbol_table)
+        self.binary(&[TokenType::BitXor], expr, Self::assignment, symbol_table)
     }
 
     fn assignment(&mut self, symbol_table: &mut SymbolTable) -> Expr {
         let expr = self.equality(symbol_table)?;
-        if self.match_token(&[Equal]) {
-            let operator = self.previous().clone();
-            let right = self.comparison(symbol_table)?;
-            if let Variable { name, .. } = expr {
-                Ok(Assignment {
-                    line: operator.line,
-                    variable_name: name.to_string(),
-                    value: Box::new(right),
-                })
-            } else {
-                Err(self.raise(CompilerError::Failure))
-            }
-        } else {
-            Ok(expr)
+        if !self.match_token(&[Equal, PlusEqual, MinusEqual, StarEqual, SlashEqual, PercentEqual]) {
+            return Ok(expr);
+        }
+        let operator = self.previous().clone();
+        let mut right = self.expression(symbol_table)?;
+        let compound = compound_operator(&operator.token_type);
+        if let Some(binary_operator) = &compound {
+            // x += 1 is x = x + 1
+            right = Expression::Binary {
+                line: operator.line,
+                span: expr.span().to(right.span()),
+                left: Box::new(expr.clone()),
+                operator: Token::new(
+                    binary_operator.clone(),
+                    binary_operator.to_string(),
+                    operator.line,
+                    operator.span,
+                ),
+                right: Box::new(right),
+            };
+        }
+        // the value must fit the variable, element or field, with the same type rules as
+        // declaring it
+        let target_type = self.type_of(&expr, symbol_table)?;
+        if !matches!(target_type, Unknown | TokenType::Any) {
+            calculate_type(&target_type, &self.type_of(&right, symbol_table)?)
+                .map_err(|e| CompilerErrorAtLine::raise(e, operator.line).at(right.span()))?;
+        }
+        let span = expr.span().to(right.span());
+        match expr {
+            Variable { name, .. } => Ok(Assignment {
+                line: operator.line,
+                span,
+                variable_name: name.to_string(),
+                value: Box::new(right),
+            }),
+            ListGet { .. } | MapGet { .. } | FieldGet { .. } => Ok(ElementAssignment {
+                line: operator.line,
+                span,
+                target: Box::new(expr),
+                value: Box::new(right),
+                compound: compound.is_some(),
+            }),
+            _ => Err(self.raise_at(CompilerError::IllegalAssignmentTarget, expr.span())),
         }
     }
 
@@ -446,6 +660,7 @@ impl AstCompiler {
         self.binary(
             &[TokenType::EqualEqual, TokenType::BangEqual],
             expr,
+            Self::comparison,
             symbol_table,
         )
     }
@@ -455,19 +670,22 @@ impl AstCompiler {
         self.binary(
             &[Greater, GreaterEqual, Less, LessEqual],
             expr,
+            Self::range,
             symbol_table,
         )
     }
 
     fn range(&mut self, symbol_table: &mut SymbolTable) -> Expr {
         let mut expr = self.bitshift(symbol_table)?;
-        if self.match_token(&[Range]) {
+        if self.match_token(&[Range, RangeInclusive]) {
             let operator = self.previous().clone();
             let right = self.expression(symbol_table)?;
             expr = Expression::Range {
                 line: operator.line,
+                span: expr.span().to(right.span()),
                 lower: Box::new(expr),
                 upper: Box::new(right),
+                inclusive: operator.token_type == RangeInclusive,
             };
         }
         Ok(expr)
@@ -475,34 +693,67 @@ impl AstCompiler {
 
     fn bitshift(&mut self, symbol_table: &mut SymbolTable) -> Expr {
         let expr = self.term(symbol_table)?;
-        self.binary(&[GreaterGreater, LessLess], expr, symbol_table)
+        self.binary(&[GreaterGreater, LessLess], expr, Self::term, symbol_table)
     }
 
     fn term(&mut self, symbol_table: &mut SymbolTable) -> Expr {
         let expr = self.factor(symbol_table)?;
-        self.binary(&[Minus, Plus], expr, symbol_table)
+        self.binary(&[Minus, Plus], expr, Self::factor, symbol_table)
     }
 
     fn factor(&mut self, symbol_table: &mut SymbolTable) -> Expr {
-        let expr = self.unary(symbol_table)?;
-        self.binary(&[Slash, Star], expr, symbol_table)
+        let expr = self.cast(symbol_table)?;
+        self.binary(&[Slash, Star, Percent], expr, Self::cast, symbol_table)
+    }
+
+    // x as u32, only between types that can be converted
+    fn cast(&mut self, symbol_table: &mut SymbolTable) -> Expr {
+        let mut expr = self.unary(symbol_table)?;
+        while self.match_token(&[As]) {
+            let line = self.previous().line;
+            let target_type = self.type_declaration()?;
+            let value_type = self.type_of(&expr, symbol_table)?;
+            let span = self.span_from(expr.span());
+            if !is_convertible(&value_type, &target_type) {
+                let error = CompilerError::IllegalConversion(value_type, target_type);
+                return Err(self.raise_at(error, span));
+            }
+            if value_type == target_type {
+                continue;
+            }
+            expr = Expression::Cast {
+                line,
+                span,
+                value: Box::new(expr),
+                target_type,
+            };
+        }
+        Ok(expr)
     }
 
+    // left associative, the right operand is parsed at the next precedence level
     fn binary(
         &mut self,
         types: &[TokenType],
         mut expr: Expression,
+        operand: fn(&mut Self, &mut SymbolTable) -> Expr,
         symbol_table: &mut SymbolTable,
     ) -> Expr {
         while self.match_token(types) {
             let operator = self.previous().clone();
-            let right = self.comparison(symbol_table)?;
+            let right = operand(self, symbol_table)?;
+            let line = operator.line;
+            let span = expr.span().to(right.span());
             expr = Expression::Binary {
-                line: operator.line,
+                line,
+                span,
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
             };
+            // operands that cannot be combined fail here, at the line of the operator
+            infer_type(&expr, symbol_table)
+                .map_err(|e| CompilerErrorAtLine::raise(e, line).at(span))?;
         }
         Ok(expr)
     }
@@ -513,57 +764,168 @@ impl AstCompiler {
             let right = self.unary(symbol_table)?;
             Ok(Expression::Unary {
                 line: self.peek().line,
+                span: operator.span.to(right.span()),
                 operator,
                 right: Box::new(right),
             })
         } else {
-            self.get(symbol_table)
+            self.power(symbol_table)
         }
     }
 
+    // right associative and binds tighter than a unary minus: -2 ** 2 is -4
+    fn power(&mut self, symbol_table: &mut SymbolTable) -> Expr {
+        let base = self.get(symbol_table)?;
+        if self.match_token(&[StarStar]) {
+            let operator = self.previous().clone();
+            let exponent = self.unary(symbol_table)?;
+            return Ok(Expression::Binary {
+                line: operator.line,
+                span: base.span().to(exponent.span()),
+                left: Box::new(base),
+                operator,
+                right: Box::new(exponent),
+            });
+        }
+        Ok(base)
+    }
+
     fn get(&mut self, symbol_table: &mut SymbolTable) -> Expr {
-        let expr = self.primary(symbol_table)?;
+        let mut expr = self.primary(symbol_table)?;
+
+        // calls, indexes and methods can be chained: xs.map(f).filter(g)[0]
+        loop {
+            expr = if self.match_token(&[LeftParen]) {
+                // the function an expression gives, like make_adder(5)(1) or fs[0](x)
+                let arguments = self.arguments(symbol_table)?;
+                ValueCall {
+                    line: self.previous().line,
+                    span: self.span_from(expr.span()),
+                    function: Box::new(expr),
+                    arguments,
+                }
+            } else if self.match_token(&[LeftBracket]) {
+                self.index_or_slice(expr, symbol_table)?
+            } else if self.match_token(&[Dot]) {
+                let name = self.peek().clone();
+                self.advance();
+                self.field_or_method(expr, name, symbol_table)?
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
 
-        if self.match_token(&[LeftParen]) {
-            let name = self.peek().clone();
-            self.advance();
-            self.function_call(name, symbol_table)
-        } else if self.match_token(&[LeftBracket]) {
-            let index = self.expression(symbol_table)?;
-            self.index(expr, index)
-        } else if self.match_token(&[Dot]) {
-            let name = self.peek().clone();
-            self.advance();
-            self.field_or_method(expr, name, symbol_table)
+    // xs[i], xs[lower..upper], xs[lower..=upper], where both bounds are optional
+    fn index_or_slice(&mut self, operand: Expression, symbol_table: &mut SymbolTable) -> Expr {
+        let lower = if self.match_token(&[Range, RangeInclusive]) {
+            None
         } else {
-            Ok(expr)
+            let index = self.bitshift(symbol_table)?;
+            if !self.match_token(&[Range, RangeInclusive]) {
+                return self.index(operand, index, symbol_table);
+            }
+            Some(index)
+        };
+        let range = self.previous().clone();
+        let upper = if self.check(&RightBracket) {
+            if range.token_type == RangeInclusive {
+                return Err(self.raise(Expected("upper bound of inclusive range")));
+            }
+            None
+        } else {
+            Some(self.bitshift(symbol_table)?)
+        };
+        let operand_type = self.type_of(&operand, symbol_table)?;
+        if !matches!(operand_type, ListType(_) | StringType) {
+            let error = CompilerError::IllegalTypeToIndex(operand_type.to_string());
+            return Err(self.raise_at(error, operand.span()));
+        }
+        for bound in lower.iter().chain(upper.iter()) {
+            self.check_index_type(&self.type_of(bound, symbol_table)?, bound.span())?;
         }
+        self.consume(&RightBracket, Expected("']' after slice."))?;
+        Ok(Slice {
+            line: range.line,
+            span: self.span_from(operand.span()),
+            operand: Box::new(operand),
+            lower: lower.map(Box::new),
+            upper: upper.map(Box::new),
+            inclusive: range.token_type == RangeInclusive,
+        })
     }
 
-    fn index(&mut self, operand: Expression, index: Expression) -> Expr {
-        let get = match &operand {
-            Expression::Map { .. } => MapGet {
-                map: Box::new(operand),
-                key: Box::new(index),
-            },
-            Expression::List { .. } => ListGet {
-                list: Box::new(operand),
-                index: Box::new(index),
-            },
-            Variable { var_type, .. } => match var_type {
-                ListType => ListGet {
+    /// the type of an expression, failing at the current line for operands that don't combine
+    fn type_of(
+        &self,
+        expr: &Expression,
+        symbol_table: &SymbolTable,
+    ) -> Result<TokenType, CompilerErrorAtLine> {
+        infer_type(expr, symbol_table).map_err(|e| self.raise_at(e, expr.span()))
+    }
+
+    fn check_index_type(
+        &self,
+        index_type: &TokenType,
+        span: Span,
+    ) -> Result<(), CompilerErrorAtLine> {
+        if !matches!(
+            index_type,
+            Integer
+                | TokenType::SignedInteger
+                | U32
+                | U64
+                | TokenType::I32
+                | TokenType::I64
+                | Unknown
+        ) {
+            let error = CompilerError::IllegalIndexArgument(index_type.clone());
+            return Err(self.raise_at(error, span));
+        }
+        Ok(())
+    }
+
+    fn index(
+        &mut self,
+        operand: Expression,
+        index: Expression,
+        symbol_table: &mut SymbolTable,
+    ) -> Expr {
+        let index_type = self.type_of(&index, symbol_table)?;
+        let line = self.previous().line;
+        // up to the closing bracket
+        let span = operand.span().to(self.peek().span);
+        let get = match self.type_of(&operand, symbol_table)? {
+            // a string is indexed by char
+            ListType(_) | StringType => {
+                self.check_index_type(&index_type, index.span())?;
+                ListGet {
+                    line,
+                    span,
                     list: Box::new(operand),
                     index: Box::new(index),
-                },
-                MapType => MapGet {
+                }
+            }
+            MapType(key_type, _) => {
+                if *key_type != TokenType::Any && index_type != Unknown {
+                    calculate_element_type(&key_type, &index_type)
+                        .map_err(|e| self.raise_at(e, index.span()))?;
+                }
+                MapGet {
+                    line,
+                    span,
                     map: Box::new(operand),
                     key: Box::new(index),
-                },
-                _ => {
-                    return Err(self.raise(CompilerError::IllegalTypeToIndex(var_type.to_string())));
                 }
-            },
-            _ => return Err(self.raise(CompilerError::IllegalTypeToIndex("Unknown".to_string()))),
+            }
+            Unknown => {
+                let error = CompilerError::IllegalTypeToIndex("Unknown".to_string());
+                return Err(self.raise_at(error, operand.span()));
+            }
+            operand_type => {
+                let error = CompilerError::IllegalTypeToIndex(operand_type.to_string());
+                return Err(self.raise_at(error, operand.span()));
+            }
         };
         self.consume(&RightBracket, Expected("']' after index."))?;
         Ok(get)
@@ -579,6 +941,7 @@ impl AstCompiler {
         if self.match_token(&[LeftParen]) {
             let arguments = self.arguments(symbol_table)?;
             Ok(MethodCall {
+                span: self.span_from(receiver.span()),
                 receiver: Box::new(receiver.clone()),
                 method_name: op.lexeme,
                 arguments,
@@ -587,6 +950,8 @@ impl AstCompiler {
         } else {
             // no test yet
             Ok(FieldGet {
+                line: op.line,
+                span: receiver.span().to(op.span),
                 receiver: Box::new(receiver.clone()),
                 field: op.lexeme.clone(),
             })
@@ -601,101 +966,185 @@ impl AstCompiler {
         } else if self.match_token(&[False]) {
             Expression::Literal {
                 line: self.peek().line,
+                span: self.previous().span,
                 literaltype: Bool,
                 value: Value::Bool(false),
             }
         } else if self.match_token(&[True]) {
             Expression::Literal {
                 line: self.peek().line,
+                span: self.previous().span,
                 literaltype: Bool,
                 value: Value::Bool(true),
             } //, FloatingPoint, Text
         } else if self.match_token(&[Integer]) {
             Expression::Literal {
                 line: self.peek().line,
+                span: self.previous().span,
                 literaltype: Integer,
                 value: Value::I64(
                     self.previous()
                         .lexeme
                         .parse()
-                        .map_err(|e| self.raise(ParseError(format!("{:?}", e))))?,
+                        .map_err(|e| self.literal_error(format!("{:?}", e)))?,
                 ),
             }
         } else if self.match_token(&[U32]) {
             Expression::Literal {
                 line: self.peek().line,
+                span: self.previous().span,
                 literaltype: Integer,
                 value: Value::U32(
                     u32::from_str_radix(self.previous().lexeme.trim_start_matches("0x"), 16)
-                        .map_err(|e| self.raise(ParseError(format!("{:?}", e))))?,
+                        .map_err(|e| self.literal_error(format!("{:?}", e)))?,
                 ),
             }
         } else if self.match_token(&[U64]) {
             Expression::Literal {
                 line: self.peek().line,
+                span: self.previous().span,
                 literaltype: Integer,
                 value: Value::U64(
                     u64::from_str_radix(self.previous().lexeme.trim_start_matches("0x"), 16)
-                        .map_err(|e| self.raise(ParseError(format!("{:?}", e))))?,
+                        .map_err(|e| self.literal_error(format!("{:?}", e)))?,
                 ),
             }
         } else if self.match_token(&[FloatingPoint]) {
             Expression::Literal {
                 line: self.peek().line,
+                span: self.previous().span,
                 literaltype: FloatingPoint,
                 value: Value::F64(
                     self.previous()
                         .lexeme
                         .parse()
-                        .map_err(|e| self.raise(ParseError(format!("{:?}", e))))?,
+                        .map_err(|e| self.literal_error(format!("{:?}", e)))?,
+                ),
+            }
+        } else if self.match_token(&[TokenType::Decimal]) {
+            Expression::Literal {
+                line: self.peek().line,
+                span: self.previous().span,
+                literaltype: TokenType::Decimal,
+                value: Value::Decimal(
+                    rust_decimal::Decimal::from_scientific(&self.previous().lexeme)
+                        .or_else(|_| self.previous().lexeme.parse())
+                        .map_err(|e| self.literal_error(format!("{:?}", e)))?,
                 ),
             }
         } else if self.match_token(&[StringType]) {
             Expression::Literal {
                 line: self.peek().line,
+                span: self.previous().span,
                 literaltype: StringType,
-                value: Value::String(self.previous().lexeme.clone()),
+                value: Value::String(self.previous().lexeme.clone().into()),
             }
         } else if self.match_token(&[Char]) {
             Expression::Literal {
                 line: self.peek().line,
+                span: self.previous().span,
                 literaltype: Char,
                 value: Value::Char(self.previous().lexeme.chars().next().unwrap()),
             }
         } else if self.match_token(&[DateTime]) {
+            // d"..." is either a datetime with timezone, or a date
+            let lexeme = &self.previous().lexeme;
+            if let Ok(datetime) = chrono::DateTime::parse_from_str(lexeme, DATE_FORMAT_TIMEZONE) {
+                Expression::Literal {
+                    line: self.peek().line,
+                    span: self.previous().span,
+                    literaltype: DateTime,
+                    value: Value::DateTime(Box::new(datetime)),
+                }
+            } else {
+                Expression::Literal {
+                    line: self.peek().line,
+                    span: self.previous().span,
+                    literaltype: TokenType::Date,
+                    value: Value::Date(
+                        chrono::NaiveDate::parse_from_str(lexeme, DATE_FORMAT)
+                            .map_err(|_| self.invalid_literal(TokenType::Date))?,
+                    ),
+                }
+            }
+        } else if self.match_token(&[Uuid]) {
             Expression::Literal {
                 line: self.peek().line,
-                literaltype: DateTime,
-                value: Value::DateTime(Box::new(
-                    chrono::DateTime::parse_from_str(&self.previous().lexeme, DATE_FORMAT_TIMEZONE)
-                        .map_err(|_| self.raise(ParseError(self.previous().lexeme.clone())))?
-                        .into(),
-                )),
+                span: self.previous().span,
+                literaltype: Uuid,
+                value: Value::Uuid(
+                    uuid::Uuid::parse_str(&self.previous().lexeme)
+                        .map_err(|_| self.invalid_literal(Uuid))?,
+                ),
             }
+        } else if self.match_token(&[TokenType::InterpolationStart]) {
+            self.interpolation(symbol_table)?
+        } else if self.match_token(&[Fn]) {
+            self.lambda(symbol_table)?
         } else if self.match_token(&[LeftParen]) {
+            let start = self.previous().span;
             let expr = self.expression(symbol_table)?;
             self.consume(&RightParen, Expected("')' after expression."))?;
             Expression::Grouping {
                 line: self.peek().line,
+                span: self.span_from(start),
                 expression: Box::new(expr),
             }
         } else {
+            if !self.check(&Identifier) {
+                // an end of line belongs to the next line, the missing expression to this one
+                let line = self.previous().line;
+                let error = CompilerErrorAtLine::raise(Expected("an expression."), line);
+                return Err(error.at(self.peek().span));
+            }
             let token = self.advance().clone();
             debug!("{:?}", token);
             if self.match_token(&[LeftParen]) {
                 self.function_call(token.clone(), symbol_table)?
-            } else if self.match_token(&[Colon]) {
-                self.named_parameter(&token, symbol_table)?
             } else {
                 self.variable_lookup(&token, symbol_table)?
             }
         })
     }
 
+    // f"hello {name}"
+    fn interpolation(&mut self, symbol_table: &mut SymbolTable) -> Expr {
+        let line = self.previous().line;
+        let start = self.previous().span;
+        let mut parts = vec![];
+        while !self.match_token(&[TokenType::InterpolationEnd]) {
+            if self.match_token(&[StringType]) {
+                parts.push(Expression::Literal {
+                    line,
+                    span: self.previous().span,
+                    literaltype: StringType,
+                    value: Value::String(self.previous().lexeme.clone().into()),
+                });
+            } else {
+                self.consume(&LeftBrace, Expected("'{' in interpolated string."))?;
+                let expression = self.expression(symbol_table)?;
+                // anything with a value can be formatted
+                let expression_type = self.type_of(&expression, symbol_table)?;
+                if expression_type == TokenType::Void {
+                    let error = CompilerError::UnexpectedType(expression_type);
+                    return Err(self.raise_at(error, expression.span()));
+                }
+                self.consume(&RightBrace, Expected("'}' after interpolated expression."))?;
+                parts.push(expression);
+            }
+        }
+        Ok(Expression::Interpolation {
+            line,
+            span: self.span_from(start),
+            parts,
+        })
+    }
+
     fn named_parameter(&mut self, name: &Token, symbol_table: &mut SymbolTable) -> Expr {
         let value = self.expression(symbol_table)?;
         let line = name.line;
         Ok(NamedParameter {
+            span: name.span.to(value.span()),
             name: name.clone(),
             value: Box::new(value),
             line,
@@ -703,6 +1152,7 @@ impl AstCompiler {
     }
 
     fn list(&mut self, symbol_table: &mut SymbolTable) -> Expr {
+        let start = self.previous().span;
         let mut list = vec![];
         while !self.match_token(&[RightBracket]) {
             list.push(self.expression(symbol_table)?);
@@ -713,14 +1163,19 @@ impl AstCompiler {
                 break;
             }
         }
+        let span = self.span_from(start);
+        let element_type = infer_element_type(list.iter(), symbol_table)
+            .map_err(|e| self.raise_at(e, span))?;
         Ok(Expression::List {
             values: list,
-            literaltype: ListType,
+            literaltype: ListType(Box::new(element_type)),
             line: self.peek().line,
+            span,
         })
     }
 
     fn map(&mut self, symbol_table: &mut SymbolTable) -> Expr {
+        let start = self.previous().span;
         let mut entries = vec![];
         while !self.match_token(&[RightBrace]) {
             let key = self.expression(symbol_table)?;
@@ -734,10 +1189,17 @@ impl AstCompiler {
                 break;
             }
         }
+        let span = self.span_from(start);
+        let key_type = infer_element_type(entries.iter().map(|(key, _)| key), symbol_table)
+            .map_err(|e| self.raise_at(e, span))?;
+        let value_type =
+            infer_element_type(entries.iter().map(|(_, value)| value), symbol_table)
+                .map_err(|e| self.raise_at(e, span))?;
         Ok(Expression::Map {
             entries,
-            literaltype: MapType,
+            literaltype: MapType(Box::new(key_type), Box::new(value_type)),
             line: self.peek().line,
+            span,
         })
     }
 
@@ -752,6 +1214,7 @@ impl AstCompiler {
             name: name.lexeme.to_string(),
             var_type: var_type.clone(),
             line: name.line,
+            span: name.span,
         })
     }
 
@@ -759,6 +1222,7 @@ impl AstCompiler {
         let arguments = self.arguments(symbol_table)?;
         Ok(FunctionCall {
             line: self.peek().line,
+            span: self.span_from(name.span),
             name: name.lexeme.to_string(),
             arguments,
         })
@@ -773,7 +1237,13 @@ impl AstCompiler {
             if arguments.len() >= 25 {
                 return Err(self.raise(TooManyParameters));
             }
-            let arg = self.expression(symbol_table)?;
+            let arg = if self.check(&Identifier) && self.check_next(&Colon) {
+                let name = self.advance().clone();
+                self.advance();
+                self.named_parameter(&name, symbol_table)?
+            } else {
+                self.expression(symbol_table)?
+            };
             arguments.push(arg);
             if self.peek().token_type == TokenType::Comma {
                 self.advance();
@@ -793,7 +1263,6 @@ impl AstCompiler {
         if self.check(token_type) {
             self.advance();
         } else {
-            self.had_error = true;
             return Err(self.raise(message));
         }
         Ok(self.previous().clone())
@@ -817,6 +1286,12 @@ impl AstCompiler {
         }
     }
 
+    fn check_next(&self, token_type: &TokenType) -> bool {
+        self.tokens
+            .get(self.current + 1)
+            .is_some_and(|token| &token.token_type == token_type)
+    }
+
     fn peek(&self) -> &Token {
         &self.tokens[self.current]
     }
@@ -890,9 +1365,24 @@ impl Statement {
             Statement::ForStatement { loop_var, .. } => loop_var.line,
         }
     }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::ExpressionStmt { expression } => expression.span(),
+            Statement::VarStmt {
+                name, initializer, ..
+            } => name.span.to(initializer.span()),
+            Statement::PrintStmt { value } => value.span(),
+            Statement::FunctionStmt { function, .. } => function.name.span,
+            Statement::ObjectStmt { name, .. } => name.span,
+            Statement::GuardStatement { if_expr, .. } => if_expr.span(),
+            Statement::IfStatement { condition, .. } => condition.span(),
+            Statement::ForStatement { loop_var, .. } => loop_var.span,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Parameter {
     pub name: Token,
     pub var_type: TokenType,
@@ -905,6 +1395,7 @@ impl Parameter {
                 token_type: TokenType::StringType,
                 lexeme: name.into(),
                 line: 0,
+                span: Span::default(),
             },
             var_type: value_type,
         }
@@ -915,80 +1406,140 @@ impl Parameter {
 pub enum Expression {
     Binary {
         line: usize,
+        span: Span,
         left: Box<Expression>,
         operator: Token,
         right: Box<Expression>,
     },
     Unary {
         line: usize,
+        span: Span,
         operator: Token,
         right: Box<Expression>,
     },
     Grouping {
         line: usize,
+        span: Span,
         expression: Box<Expression>,
     },
     Literal {
         line: usize,
+        span: Span,
         literaltype: TokenType,
         value: Value,
     },
     Range {
         line: usize,
+        span: Span,
         lower: Box<Expression>,
         upper: Box<Expression>,
+        // a..=b includes the upper bound, a..b does not
+        inclusive: bool,
     },
     List {
         line: usize,
+        span: Span,
         literaltype: TokenType,
         values: Vec<Expression>,
     },
     Map {
         line: usize,
+        span: Span,
         literaltype: TokenType,
         entries: Vec<(Expression, Expression)>,
     },
     Variable {
         line: usize,
+        span: Span,
         name: String,
         var_type: TokenType,
     },
     Assignment {
         line: usize,
+        span: Span,
         variable_name: String,
         value: Box<Expression>,
     },
     FunctionCall {
         line: usize,
+        span: Span,
         name: String,
         arguments: Vec<Expression>,
     },
     MethodCall {
         line: usize,
+        span: Span,
         receiver: Box<Expression>,
         method_name: String,
         arguments: Vec<Expression>,
     },
+    ValueCall {
+        line: usize,
+        span: Span,
+        function: Box<Expression>,
+        arguments: Vec<Expression>,
+    },
     Stop {
         line: usize,
+        span: Span,
     },
     NamedParameter {
         line: usize,
+        span: Span,
         name: Token,
         value: Box<Expression>,
     },
     MapGet {
+        line: usize,
+        span: Span,
         map: Box<Expression>,
         key: Box<Expression>,
     },
     ListGet {
+        line: usize,
+        span: Span,
         list: Box<Expression>,
         index: Box<Expression>,
     },
+    Cast {
+        line: usize,
+        span: Span,
+        value: Box<Expression>,
+        target_type: TokenType,
+    },
+    /// assignment to a list element, map entry or object field
+    ElementAssignment {
+        line: usize,
+        span: Span,
+        target: Box<Expression>,
+        value: Box<Expression>,
+        /// xs[i] += 1, of which the value is the binary expression xs[i] + 1
+        compound: bool,
+    },
+    Slice {
+        line: usize,
+        span: Span,
+        operand: Box<Expression>,
+        lower: Option<Box<Expression>>,
+        upper: Option<Box<Expression>>,
+        inclusive: bool,
+    },
     FieldGet {
+        line: usize,
+        span: Span,
         receiver: Box<Expression>,
         field: String,
     },
+    Lambda {
+        line: usize,
+        span: Span,
+        function: Box<Function>,
+    },
+    Interpolation {
+        line: usize,
+        span: Span,
+        parts: Vec<Expression>,
+    },
 }
 
 impl Expression {
@@ -1005,11 +1556,87 @@ impl Expression {
             Assignment { line, .. } => *line,
             FunctionCall { line, .. } => *line,
             MethodCall { line, .. } => *line,
-            Stop { line } => *line,
+            ValueCall { line, .. } => *line,
+            Stop { line, .. } => *line,
             NamedParameter { line, .. } => *line,
-            MapGet { .. } => 0,
-            ListGet { .. } => 0,
-            FieldGet { .. } => 0,
+            MapGet { line, .. } => *line,
+            ListGet { line, .. } => *line,
+            Slice { line, .. } => *line,
+            ElementAssignment { line, .. } => *line,
+            Self::Cast { line, .. } => *line,
+            FieldGet { line, .. } => *line,
+            Self::Lambda { line, .. } => *line,
+            Self::Interpolation { line, .. } => *line,
+        }
+    }
+
+    /// the source of the expression, from its first to its last token
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Binary { span, .. }
+            | Self::Unary { span, .. }
+            | Self::Grouping { span, .. }
+            | Self::Literal { span, .. }
+            | Self::Range { span, .. }
+            | Self::List { span, .. }
+            | Self::Map { span, .. }
+            | Variable { span, .. }
+            | Assignment { span, .. }
+            | FunctionCall { span, .. }
+            | MethodCall { span, .. }
+            | ValueCall { span, .. }
+            | Stop { span, .. }
+            | NamedParameter { span, .. }
+            | MapGet { span, .. }
+            | ListGet { span, .. }
+            | Slice { span, .. }
+            | ElementAssignment { span, .. }
+            | Self::Cast { span, .. }
+            | FieldGet { span, .. }
+            | Self::Lambda { span, .. }
+            | Self::Interpolation { span, .. } => *span,
         }
     }
 }
+
+// the binary operator of a compound assignment like +=
+// parameters are declared as variables while the body is type checked. Returns the symbols
+// they shadow
+fn declare_parameters(
+    parameters: &[Parameter],
+    symbol_table: &mut SymbolTable,
+) -> Vec<(String, Option<Symbol>)> {
+    parameters
+        .iter()
+        .map(|parameter| {
+            let name = parameter.name.lexeme.clone();
+            let symbol = Symbol::Variable {
+                name: name.clone(),
+                var_type: parameter.var_type.clone(),
+            };
+            let shadowed = symbol_table.insert(name.clone(), symbol);
+            (name, shadowed)
+        })
+        .collect()
+}
+
+// puts back what the parameters shadowed, after the body
+fn restore(shadowed: Vec<(String, Option<Symbol>)>, symbol_table: &mut SymbolTable) {
+    for (name, symbol) in shadowed.into_iter().rev() {
+        match symbol {
+            Some(symbol) => symbol_table.insert(name, symbol),
+            None => symbol_table.remove(&name),
+        };
+    }
+}
+
+fn compound_operator(token_type: &TokenType) -> Option<TokenType> {
+    match token_type {
+        PlusEqual => Some(Plus),
+        MinusEqual => Some(Minus),
+        StarEqual => Some(Star),
+        SlashEqual => Some(Slash),
+        PercentEqual => Some(Percent),
+        _ => None,
+    }
+}
@@ -0,0 +1,218 @@
+use crate::AsmRegistry;
+use crate::compiler::assembly_pass::AsmChunk;
+use crate::compiler::link_pass::{link, unlink};
+use crate::errors::TipiLangError;
+use crate::errors::TipiLangError::Bytecode;
+use std::fs;
+
+/// the first bytes of every .tpc file
+const MAGIC: &[u8; 4] = b"TIPC";
+/// changes whenever the layout of the chunks changes, so that old files are rebuilt
+pub const FORMAT_VERSION: u32 = 2;
+// magic, version and checksum
+const HEADER_LEN: usize = 12;
+
+/// writes the registry as a .tpc file: a header with the magic, the format version
+/// and a checksum of the payload, followed by the chunks in registry order.
+/// Calls are stored by name and linked again when the file is read.
+pub fn write(registry: &AsmRegistry) -> Result<Vec<u8>, TipiLangError> {
+    let chunks: Vec<(String, AsmChunk)> = registry
+        .iter()
+        .map(|(name, chunk)| {
+            let mut chunk = chunk.clone();
+            unlink(&mut chunk);
+            (name.clone(), chunk)
+        })
+        .collect();
+    let payload = postcard::to_allocvec(&chunks).map_err(|e| Bytecode(e.to_string()))?;
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+/// reads a registry from the contents of a .tpc file, and links it
+pub fn read(bytes: &[u8]) -> Result<AsmRegistry, TipiLangError> {
+    if bytes.len() < HEADER_LEN || &bytes[0..4] != MAGIC {
+        return Err(Bytecode("not a tipi bytecode file".to_string()));
+    }
+    let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+    if version != FORMAT_VERSION {
+        return Err(Bytecode(format!(
+            "bytecode format version {} is not supported, expected {}. Rebuild from source",
+            version, FORMAT_VERSION
+        )));
+    }
+    let checksum = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+    let payload = &bytes[HEADER_LEN..];
+    if crc32fast::hash(payload) != checksum {
+        return Err(Bytecode("checksum mismatch, the file is damaged".to_string()));
+    }
+    let chunks: Vec<(String, AsmChunk)> =
+        postcard::from_bytes(payload).map_err(|e| Bytecode(e.to_string()))?;
+    let mut registry: AsmRegistry = chunks.into_iter().collect();
+    link(&mut registry);
+    Ok(registry)
+}
+
+pub fn save(registry: &AsmRegistry, path: &str) -> Result<(), TipiLangError> {
+    fs::write(path, write(registry)?).map_err(|e| Bytecode(format!("{}: {}", path, e)))
+}
+
+pub fn load(path: &str) -> Result<AsmRegistry, TipiLangError> {
+    read(&fs::read(path).map_err(|e| Bytecode(format!("{}: {}", path, e)))?)
+}
+
+/// (de)serializes the constants of a chunk. Value has a serde implementation of its own,
+/// for json responses, that does not keep the type of a value, so constants go through
+/// a mirror of it that does
+pub(crate) mod constants {
+    use crate::value::{Closure, Object, Value};
+    use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, TimeDelta};
+    use rust_decimal::Decimal;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use uuid::Uuid;
+
+    #[derive(Serialize, Deserialize)]
+    enum StoredValue {
+        U32(u32),
+        I32(i32),
+        U64(u64),
+        I64(i64),
+        F32(f32),
+        F64(f64),
+        Decimal([u8; 16]),
+        String(String),
+        Char(char),
+        Bool(bool),
+        // seconds and nanoseconds since the epoch, and the offset in seconds
+        DateTime(i64, u32, i32),
+        // days since the common era
+        Date(i32),
+        // seconds and nanoseconds
+        Duration(i64, i32),
+        Uuid(u128),
+        Enum,
+        List(Vec<StoredValue>),
+        Map(Vec<(StoredValue, StoredValue)>),
+        Object(String, Vec<(String, StoredValue)>),
+        Function(String, Vec<StoredValue>),
+        Error(String),
+        Void,
+    }
+
+    impl From<&Value> for StoredValue {
+        fn from(value: &Value) -> Self {
+            match value {
+                Value::U32(v) => StoredValue::U32(*v),
+                Value::I32(v) => StoredValue::I32(*v),
+                Value::U64(v) => StoredValue::U64(*v),
+                Value::I64(v) => StoredValue::I64(*v),
+                Value::F32(v) => StoredValue::F32(*v),
+                Value::F64(v) => StoredValue::F64(*v),
+                Value::Decimal(v) => StoredValue::Decimal(v.serialize()),
+                Value::String(v) => StoredValue::String(v.to_string()),
+                Value::Char(v) => StoredValue::Char(*v),
+                Value::Bool(v) => StoredValue::Bool(*v),
+                Value::DateTime(v) => StoredValue::DateTime(
+                    v.timestamp(),
+                    v.timestamp_subsec_nanos(),
+                    v.offset().local_minus_utc(),
+                ),
+                Value::Date(v) => StoredValue::Date(v.num_days_from_ce()),
+                Value::Duration(v) => StoredValue::Duration(v.num_seconds(), v.subsec_nanos()),
+                Value::Uuid(v) => StoredValue::Uuid(v.as_u128()),
+                Value::Enum => StoredValue::Enum,
+                Value::List(v) => StoredValue::List(v.iter().map(StoredValue::from).collect()),
+                Value::Map(v) => StoredValue::Map(
+                    v.iter().map(|(k, v)| (StoredValue::from(k), StoredValue::from(v))).collect(),
+                ),
+                Value::ObjectType(v) => StoredValue::Object(
+                    v.definition.clone(),
+                    v.fields.iter().map(|(n, v)| (n.clone(), StoredValue::from(v))).collect(),
+                ),
+                Value::Function(v) => StoredValue::Function(
+                    v.name.clone(),
+                    v.captured.iter().map(StoredValue::from).collect(),
+                ),
+                Value::Error(v) => StoredValue::Error(v.clone()),
+                Value::Void => StoredValue::Void,
+            }
+        }
+    }
+
+    impl TryFrom<StoredValue> for Value {
+        type Error = String;
+
+        fn try_from(value: StoredValue) -> Result<Self, String> {
+            Ok(match value {
+                StoredValue::U32(v) => Value::U32(v),
+                StoredValue::I32(v) => Value::I32(v),
+                StoredValue::U64(v) => Value::U64(v),
+                StoredValue::I64(v) => Value::I64(v),
+                StoredValue::F32(v) => Value::F32(v),
+                StoredValue::F64(v) => Value::F64(v),
+                StoredValue::Decimal(v) => Value::Decimal(Decimal::deserialize(v)),
+                StoredValue::String(v) => Value::String(v.into()),
+                StoredValue::Char(v) => Value::Char(v),
+                StoredValue::Bool(v) => Value::Bool(v),
+                StoredValue::DateTime(secs, nanos, offset) => {
+                    let offset = FixedOffset::east_opt(offset).ok_or("invalid offset")?;
+                    let utc = DateTime::from_timestamp(secs, nanos).ok_or("invalid datetime")?;
+                    Value::DateTime(Box::new(utc.with_timezone(&offset)))
+                }
+                StoredValue::Date(days) => {
+                    Value::Date(NaiveDate::from_num_days_from_ce_opt(days).ok_or("invalid date")?)
+                }
+                StoredValue::Duration(secs, nanos) => Value::Duration(
+                    TimeDelta::try_seconds(secs)
+                        .and_then(|d| d.checked_add(&TimeDelta::nanoseconds(nanos as i64)))
+                        .ok_or("invalid duration")?,
+                ),
+                StoredValue::Uuid(v) => Value::Uuid(Uuid::from_u128(v)),
+                StoredValue::Enum => Value::Enum,
+                StoredValue::List(v) => Value::List(values(v)?.into()),
+                StoredValue::Map(v) => Value::Map(
+                    v.into_iter()
+                        .map(|(k, v)| Ok((Value::try_from(k)?, Value::try_from(v)?)))
+                        .collect::<Result<std::collections::HashMap<_, _>, String>>()?
+                        .into(),
+                ),
+                StoredValue::Object(definition, fields) => Value::ObjectType(Box::new(Object {
+                    definition,
+                    fields: fields
+                        .into_iter()
+                        .map(|(n, v)| Ok((n, Value::try_from(v)?)))
+                        .collect::<Result<_, String>>()?,
+                })),
+                StoredValue::Function(name, captured) => Value::Function(Box::new(Closure {
+                    name,
+                    captured: values(captured)?,
+                })),
+                StoredValue::Error(v) => Value::Error(v),
+                StoredValue::Void => Value::Void,
+            })
+        }
+    }
+
+    fn values(stored: Vec<StoredValue>) -> Result<Vec<Value>, String> {
+        stored.into_iter().map(Value::try_from).collect()
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        constants: &[Value],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let stored: Vec<StoredValue> = constants.iter().map(StoredValue::from).collect();
+        stored.serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Value>, D::Error> {
+        values(Vec::<StoredValue>::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
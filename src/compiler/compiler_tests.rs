@@ -1,12 +1,33 @@
 #[cfg(test)]
 mod tests {
-    use crate::compiler::{compile, run};
-    use crate::errors::CompilerError::{IllegalArgumentsException, ReservedFunctionName};
-    use crate::errors::CompilerErrorAtLine;
-    use crate::errors::RuntimeError::{IllegalArgumentException, IndexOutOfBounds};
-    use crate::errors::TipiLangError::{Compiler, Runtime};
+    use crate::compiler::assembly_pass::Op::{self, Dup, Goto, GotoIfNot, Pop};
+    use crate::bundle;
+    use crate::compiler::bytecode;
+    use crate::compiler::disassembler::disassemble;
+    use crate::compiler::ir_pass::IrExpressionKind::{Binary, Call, Closure, Constant, Get};
+    use crate::compiler::ir_pass::IrStatementKind::{Expression, Let};
+    use crate::compiler::link_pass::link;
+    use crate::compiler::{
+        compile, compile_sourcedir, compile_unoptimized, ir, run, run_unoptimized,
+        transpile_sourcedir, warnings,
+    };
+    use crate::compiler::tokens::Span;
+    use crate::compiler::tokens::TokenType::{Any, I32, I64, ListType, StringType, U32, U64, Uuid};
+    use crate::errors::CompilerError::{
+        IllegalArgumentsException, IncompatibleTypes, InvalidLiteral, ReservedFunctionName,
+    };
+    use crate::errors::{CompilerErrorAtLine, Diagnostic};
+    use crate::errors::RuntimeError::{
+        CannotParse, DivisionByZero, IllegalArgumentException, IllegalParameter, IndexOutOfBounds,
+        KeyNotFound, LossyConversion, Overflow,
+    };
+    use crate::errors::TipiLangError;
+    use crate::errors::TipiLangError::{Bytecode, Compiler, Diagnostics, Platform, Runtime};
     use crate::value::{Value, string};
-    use chrono::DateTime;
+    use crate::vm::interpret_async;
+    use chrono::{DateTime, NaiveDate};
+    use rust_decimal::Decimal;
+    use std::collections::HashMap;
     use crate::DATE_FORMAT_TIMEZONE;
 
     #[test]
@@ -33,7 +54,7 @@ mod tests {
     fn literal_list() {
         assert_eq!(
             run(r#"["abc","def"]"#),
-            Ok(Value::List(vec![string("abc"), string("def")]))
+            Ok(Value::List(vec![string("abc"), string("def")].into()))
         );
     }
 
@@ -52,204 +73,2302 @@ a[1]"#),
     }
 
     #[test]
-    fn infer_type() {
+    fn typed_list() {
         assert_eq!(
-            run(r#"let a=1
-a"#),
-            Ok(Value::I64(1))
+            run(r#"let a:list<string> = ["abc","def"]
+a[1]"#),
+            Ok(string("def"))
+        )
+    }
+
+    #[test]
+    fn typed_list_wrong_element_type() {
+        assert_eq!(
+            run(r#"let a:list<string> = ["abc", 1]"#),
+            Err(Compiler(CompilerErrorAtLine {
+                error: IncompatibleTypes(
+                    ListType(Box::new(StringType)),
+                    ListType(Box::new(Any))
+                ),
+                line: 1,
+                span: Some(Span { start: 21, end: 31, line: 1, column: 22 })
+            }))
+        )
+    }
+
+    #[test]
+    fn infer_list_element_type() {
+        let r = compile(
+            r#"let a = [1, 2]
+let b:list<string> = a"#,
+        );
+        assert_eq!(
+            r.err().unwrap().to_string(),
+            "Compilation failed: error at line 2, Expected list<string>, found list<i64>"
         );
     }
 
     #[test]
-    fn define_u32() {
+    fn nested_typed_list() {
         assert_eq!(
-            run(r#"let a:u32=1
-a"#),
+            run(r#"let a:list<list<u32>> = [[1], [2, 3]]
+a[1]"#),
+            Ok(Value::List(vec![Value::U32(2), Value::U32(3)].into()))
+        )
+    }
+
+    #[test]
+    fn typed_list_push_wrong_type() {
+        let r = compile(
+            r#"let a:list<i64> = [1]
+a.push("two")"#,
+        );
+        assert_eq!(
+            r.err().unwrap().to_string(),
+            "Compilation failed: error at line 2, Expected i64, found string"
+        );
+    }
+
+    #[test]
+    fn typed_list_element_type_flows() {
+        assert_eq!(run("let a:list<i64> = [1]\nlet c:i64 = a[0]\nc"), Ok(Value::I64(1)));
+        assert_eq!(
+            run("let a:list<i64> = [1]\nlet c:string = a[0]"),
+            Err(Compiler(CompilerErrorAtLine {
+                error: IncompatibleTypes(StringType, I64),
+                line: 2,
+                span: Some(Span { start: 37, end: 41, line: 2, column: 16 }),
+            }))
+        );
+    }
+
+    #[test]
+    fn typed_map() {
+        assert_eq!(
+            run(r#"let m:map<string, u32> = {"a": 1}
+m["a"]"#),
             Ok(Value::U32(1))
         );
+        // the keys are converted like the values
+        assert_eq!(
+            run(r#"let m:map<u32, string> = {1: "a"}
+let k: u32 = 1
+m[k]"#),
+            Ok(string("a"))
+        );
     }
 
     #[test]
-    fn define_char() {
+    fn typed_map_wrong_key_type() {
+        let r = compile(
+            r#"let m:map<string, u32> = {"a": 1}
+m[1]"#,
+        );
         assert_eq!(
-            run(r#"let a:char='a'
-a"#),
-            Ok(Value::Char('a'))
+            r.err().unwrap().to_string(),
+            "Compilation failed: error at line 2, Expected string, found integer"
         );
     }
 
     #[test]
-    fn define_u32_invalid_value_negative() {
-        let r = compile("let a:u32=-1");
-        assert!(r.is_err());
-        if let Err(e) = &r {
-            assert_eq!(
-                e.to_string(),
-                "Compilation failed: error at line 1, Expected u32, found i32/64"
-            );
-        }
+    fn map_key_not_found() {
+        assert_eq!(
+            run(r#"let m = {"a": 1}
+m["b"]"#),
+            Err(Runtime(KeyNotFound("b".to_string())))
+        )
     }
 
     #[test]
-    fn define_u64_invalid_value_negative() {
-        let r = compile("let a:u64=-1");
-        assert!(r.is_err());
-        if let Err(e) = &r {
-            assert_eq!(
-                e.to_string(),
-                "Compilation failed: error at line 1, Expected u64, found i32/64"
-            );
-        }
+    fn list_index_out_of_bounds() {
+        assert_eq!(run(r#"[1, 2][2]"#), Err(Runtime(IndexOutOfBounds(2, 2))))
     }
 
     #[test]
-    fn let_u64_invalid_value_string() {
-        let r = compile(r#"let a:u64="not ok""#);
-        assert!(r.is_err());
-        if let Err(e) = &r {
-            assert_eq!(
-                e.to_string(),
-                "Compilation failed: error at line 1, Expected u64, found string"
-            );
-        }
+    fn overflow() {
+        assert_eq!(
+            run(r#"let x = 9223372036854775807
+x + 1"#),
+            Err(Runtime(Overflow(2)))
+        );
+        assert_eq!(
+            run(r#"let x: u32 = 1
+let y: u32 = 2
+x - y"#),
+            Err(Runtime(Overflow(3)))
+        );
+        assert_eq!(run("3037000500 * 3037000500"), Err(Runtime(Overflow(1))));
+        assert_eq!(run("2 ** 64"), Err(Runtime(Overflow(1))));
+        assert_eq!(run("1 << 64"), Err(Runtime(Overflow(1))));
+        assert_eq!(run("3 << 62"), Err(Runtime(Overflow(1))));
+        assert_eq!(run("let x: u32 = 2\nx << (31 as u32)"), Err(Runtime(Overflow(2))));
+        assert_eq!(run("-1 << 63"), Ok(Value::I64(i64::MIN)));
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(run("10 / 0"), Err(Runtime(DivisionByZero(1))));
+        assert_eq!(run("10 % 0"), Err(Runtime(DivisionByZero(1))));
+        assert_eq!(run("10.5m / 0"), Err(Runtime(DivisionByZero(1))));
+    }
+
+    #[test]
+    fn division_by_zero_display() {
+        assert_eq!(
+            run(r#"let x = 0
+
+1 / x"#)
+            .map_err(|e| e.to_string()),
+            Err("Division by zero at line 3".to_string())
+        );
+    }
+
+    #[test]
+    fn index_expression_line() {
+        assert_eq!(
+            run(r#"let xs = [1]
+let x = 0
+
+xs[1 / x]"#)
+            .map_err(|e| e.to_string()),
+            Err("Division by zero at line 4".to_string())
+        );
+    }
+
+    #[test]
+    fn wrapping_and_saturating() {
+        assert_eq!(
+            run(r#"let x: u32 = 4294967295
+x.wrapping_add(1)"#),
+            Ok(Value::U32(0))
+        );
+        assert_eq!(
+            run(r#"let x = 9223372036854775807
+x.saturating_add(1)"#),
+            Ok(Value::I64(i64::MAX))
+        );
+        assert_eq!(
+            run(r#"let x: u64 = 1
+x.saturating_sub(5)"#),
+            Ok(Value::U64(0))
+        );
+        assert_eq!(
+            run(r#"let x = -9223372036854775807
+x.wrapping_sub(2)"#),
+            Ok(Value::I64(i64::MAX))
+        );
+    }
+
+    #[test]
+    fn cast() {
+        assert_eq!(
+            run(r#"let x = 42
+x as u32"#),
+            Ok(Value::U32(42))
+        );
+        assert_eq!(run("3 as f64 / 2.0"), Ok(Value::F64(1.5)));
+        assert_eq!(run("4.0 as i64"), Ok(Value::I64(4)));
+        assert_eq!(run("2.5m as f64"), Ok(Value::F64(2.5)));
+        assert_eq!(run("'a' as u32"), Ok(Value::U32(97)));
+        assert_eq!(run("98 as char"), Ok(Value::Char('b')));
+        assert_eq!(run("true as i64 + 1"), Ok(Value::I64(2)));
+    }
+
+    #[test]
+    fn cast_typed_declaration() {
+        assert_eq!(
+            run(r#"let x: i64 = 7
+let y: u64 = x as u64
+y"#),
+            Ok(Value::U64(7))
+        );
+    }
+
+    #[test]
+    fn cast_out_of_range() {
+        assert_eq!(
+            run(r#"let x = -1
+x as u32"#),
+            Err(Runtime(LossyConversion("-1".to_string(), U32, 2)))
+        );
+        assert_eq!(
+            run("5000000000 as i32"),
+            Err(Runtime(LossyConversion("5000000000".to_string(), I32, 1)))
+        );
+    }
+
+    #[test]
+    fn implicit_narrowing_is_checked() {
+        assert_eq!(
+            run("let a = -3\nlet b: u32 = a\nb"),
+            Err(Runtime(LossyConversion("-3".to_string(), U32, 2)))
+        );
+        assert_eq!(
+            run("let b: u32 = 1\nlet a = -3\nb = a\nb"),
+            Err(Runtime(LossyConversion("-3".to_string(), U32, 3)))
+        );
+        assert_eq!(
+            run("let a = 5000000000\nlet b: i32 = a\nb"),
+            Err(Runtime(LossyConversion("5000000000".to_string(), I32, 2)))
+        );
+        assert_eq!(
+            run("let a: i32 = -1\nlet b: u64 = a\nb"),
+            Err(Runtime(LossyConversion("-1".to_string(), U64, 2)))
+        );
+        assert_eq!(run("let a = 3\nlet b: u32 = a\nb"), Ok(Value::U32(3)));
+    }
+
+    #[test]
+    fn cast_lossy() {
+        assert!(run("2.5 as i64").is_err());
+        assert!(run("9007199254740993 as f64").is_err());
+        assert!(run("1.5m as u32").is_err());
+    }
+
+    #[test]
+    fn cast_not_allowed() {
+        assert_eq!(
+            run(r#""42" as i64"#).map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 1, Cannot convert string to i64".to_string())
+        );
+    }
+
+    #[test]
+    fn to_string_on_every_type() {
+        assert_eq!(run("42.to_string()"), Ok(string("42")));
+        assert_eq!(run("true.to_string()"), Ok(string("true")));
+        assert_eq!(run("19.99m.to_string()"), Ok(string("19.99")));
+        assert_eq!(run(r#"d"2025-11-09".to_string()"#), Ok(string("2025-11-09")));
+        assert_eq!(
+            run(r#"let x: u32 = 7
+x.to_string() + "!""#),
+            Ok(string("7!"))
+        );
+        assert_eq!(run("[9, 2].to_string()"), Ok(string("[9, 2]")));
+        assert_eq!(
+            run(r#"let xs = [["a"], []]
+f"{xs}""#),
+            Ok(string(r#"[["a"], []]"#))
+        );
+        assert_eq!(run(r#"{"k": [1.5]}.to_string()"#), Ok(string(r#"{"k": [1.5]}"#)));
+        assert_eq!(run(r#""m: " + {"k": 1}"#), Ok(string(r#"m: {"k": 1}"#)));
+    }
+
+    #[test]
+    fn string_parse_typed() {
+        assert_eq!(run(r#""7".parse_u32()"#), Ok(Value::U32(7)));
+        assert_eq!(run(r#""19.99".parse_decimal()"#), Ok(Value::Decimal(Decimal::new(1999, 2))));
+        assert_eq!(run(r#""true".parse_bool()"#), Ok(Value::Bool(true)));
+        assert_eq!(
+            run(r#""-1".parse_u64()"#),
+            Err(Runtime(CannotParse("-1".to_string(), "u64")))
+        );
+    }
+
+    #[test]
+    fn remainder() {
+        assert_eq!(run("17 % 5"), Ok(Value::I64(2)));
+        assert_eq!(run("7.5 % 2.0"), Ok(Value::F64(1.5)));
+        assert_eq!(run("7.5m % 2"), Ok(Value::Decimal(Decimal::new(15, 1))));
+    }
+
+    #[test]
+    fn power() {
+        assert_eq!(run("2 ** 10"), Ok(Value::I64(1024)));
+        assert_eq!(run("2 ** 3 ** 2"), Ok(Value::I64(512)));
+        assert_eq!(run("-2 ** 2"), Ok(Value::I64(-4)));
+        assert_eq!(run("2.0 ** 0.5"), Ok(Value::F64(2.0f64.sqrt())));
+    }
+
+    #[test]
+    fn decimal_power() {
+        assert_eq!(run("1.5m ** 2"), Ok(Value::Decimal(Decimal::new(225, 2))));
+        assert_eq!(run("let n = 3\n1.5m ** n"), Ok(Value::Decimal(Decimal::new(3375, 3))));
+        assert_eq!(run("2m ** -1"), Ok(Value::Decimal(Decimal::new(5, 1))));
+        assert_eq!(run("10m ** 100"), Err(Runtime(Overflow(1))));
+        assert!(run("1.5m ** 0.5m").is_err());
+        let function = "fn get(d: decimal) -> decimal:\n    d ** 2";
+        let rust = transpiled("decimal_power", &[("web.tp", function)]).unwrap();
+        assert!(rust.contains(".checked_powi("));
+    }
+
+    #[test]
+    fn operator_precedence() {
+        assert_eq!(run("2 * 3 + 1"), Ok(Value::I64(7)));
+        assert_eq!(run("10 - 4 - 3"), Ok(Value::I64(3)));
+        assert_eq!(run("1 + 10 % 4 * 2"), Ok(Value::I64(5)));
+        assert_eq!(run("2 * 3 ** 2"), Ok(Value::I64(18)));
+    }
+
+    #[test]
+    fn page_offset() {
+        assert_eq!(
+            run(r#"let page = 3
+let size = 20
+let offset = (page - 1) * size
+let last_page = 95 / size + 1
+let on_last_page = 95 % size
+[offset, last_page, on_last_page]"#),
+            Ok(Value::List(vec![Value::I64(40), Value::I64(5), Value::I64(15)].into()))
+        );
+    }
+
+    #[test]
+    fn compound_assignment() {
+        assert_eq!(
+            run(r#"let x = 10
+x += 5
+x -= 3
+x *= 4
+x /= 6
+x %= 5
+x"#),
+            Ok(Value::I64(3))
+        );
+    }
+
+    #[test]
+    fn compound_assignment_string() {
+        assert_eq!(
+            run(r#"let s = "a"
+s += "b"
+s"#),
+            Ok(string("ab"))
+        );
+    }
+
+    #[test]
+    fn compound_assignment_no_implicit_conversion() {
+        assert_eq!(
+            run(r#"let x = 10
+x += 1.5"#)
+            .map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 2, Cannot apply '+' to i64 and float".to_string())
+        );
+    }
+
+    #[test]
+    fn compound_assignment_unsigned() {
+        assert_eq!(run("let x:u32 = 5\nx += 1\nx"), Ok(Value::U32(6)));
+        assert_eq!(run("let xs:list<u32> = [1, 2]\nxs[0] += 1\nxs[0]"), Ok(Value::U32(2)));
+    }
+
+    #[test]
+    fn compound_assignment_computes_the_index_once() {
+        let src = r#"fn second() -> i64:
+    1
+let xs = [10, 20]
+xs[second()] += 5
+xs"#;
+        let calls = compile(src).unwrap()["main"]
+            .code
+            .iter()
+            .filter(|op| matches!(op, Op::CallLinked(..)))
+            .count();
+        assert_eq!(calls, 1);
+        assert_eq!(run(src), Ok(Value::List(vec![Value::I64(10), Value::I64(25)].into())));
+    }
+
+    #[test]
+    fn declared_type_error_reports_its_own_line() {
+        assert_eq!(
+            run("let x = 1\nlet y: i64 = \"s\"\nlet z = 2").map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 2, Expected i64, found string".to_string())
+        );
+    }
+
+    #[test]
+    fn element_assignment_is_type_checked() {
+        assert_eq!(
+            run(r#"let xs = [1, 2]
+xs[0] = "a""#)
+            .map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 2, Expected i64, found string".to_string())
+        );
+    }
+
+    #[test]
+    fn field_assignment_is_type_checked() {
+        assert_eq!(
+            run(r#"object Point:
+    x: i64
+
+let p = Point(x: 1)
+p.x = "a""#)
+            .map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 5, Expected i64, found string".to_string())
+        );
+    }
+
+    #[test]
+    fn list_element_assignment() {
+        assert_eq!(
+            run(r#"let xs = [1, 2, 3]
+xs[0] = 10
+xs[1] += 5
+xs[-1] *= 2
+xs"#),
+            Ok(Value::List(vec![Value::I64(10), Value::I64(7), Value::I64(6)].into()))
+        );
+    }
+
+    #[test]
+    fn nested_list_element_assignment() {
+        assert_eq!(
+            run(r#"let grid = [[1, 2], [3, 4]]
+grid[1][0] += 10
+grid[1]"#),
+            Ok(Value::List(vec![Value::I64(13), Value::I64(4)].into()))
+        );
+    }
+
+    #[test]
+    fn list_element_assignment_out_of_bounds() {
+        assert_eq!(
+            run(r#"let xs = [1]
+xs[3] = 1"#),
+            Err(Runtime(IndexOutOfBounds(3, 1)))
+        );
+    }
+
+    #[test]
+    fn map_entry_assignment() {
+        assert_eq!(
+            run(r#"let counts = {"a": 1}
+counts["a"] += 1
+counts["b"] = 5
+counts["a"] + counts["b"]"#),
+            Ok(Value::I64(7))
+        );
+    }
+
+    #[test]
+    fn illegal_assignment_target() {
+        assert!(run(r#"1 + 2 = 3"#).is_err());
+    }
+
+    #[test]
+    fn list_negative_index() {
+        assert_eq!(run(r#"[1, 2, 3][-1]"#), Ok(Value::I64(3)));
+        assert_eq!(run(r#"[1, 2, 3][-4]"#), Err(Runtime(IndexOutOfBounds(4, 3))));
+    }
+
+    #[test]
+    fn list_slice() {
+        let list = |values: &[i64]| {
+            Ok(Value::List(values.iter().map(|v| Value::I64(*v)).collect::<Vec<_>>().into()))
+        };
+        assert_eq!(run(r#"[1, 2, 3, 4][1..3]"#), list(&[2, 3]));
+        assert_eq!(run(r#"[1, 2, 3, 4][..2]"#), list(&[1, 2]));
+        assert_eq!(run(r#"[1, 2, 3, 4][2..]"#), list(&[3, 4]));
+        assert_eq!(run(r#"[1, 2, 3, 4][1..=2]"#), list(&[2, 3]));
+        assert_eq!(run(r#"[1, 2, 3, 4][..]"#), list(&[1, 2, 3, 4]));
+        assert_eq!(run(r#"[1, 2, 3, 4][-2..]"#), list(&[3, 4]));
+        assert_eq!(run(r#"[1, 2, 3, 4][..-1]"#), list(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn list_slice_with_variables() {
+        assert_eq!(
+            run(r#"let xs = [1, 2, 3, 4]
+let from = 1
+let to = from + 2
+xs[from..to].len()"#),
+            Ok(Value::U64(2))
+        );
+    }
+
+    #[test]
+    fn list_slice_out_of_bounds() {
+        assert_eq!(run(r#"[1, 2, 3][1..5]"#), Err(Runtime(IndexOutOfBounds(5, 3))));
+        assert_eq!(run(r#"[1, 2, 3][2..1]"#), Err(Runtime(IndexOutOfBounds(2, 1))));
+    }
+
+    #[test]
+    fn string_slice_by_char() {
+        assert_eq!(run(r#""héllo"[1..3]"#), Ok(string("él")));
+        assert_eq!(run(r#""héllo"[-3..]"#), Ok(string("llo")));
+        assert_eq!(run(r#""héllo"[1]"#), Ok(Value::Char('é')));
+    }
+
+    #[test]
+    fn slice_inclusive_needs_upper_bound() {
+        assert!(run(r#"[1, 2][0..=]"#).is_err());
+    }
+
+    #[test]
+    fn slice_of_a_number() {
+        assert!(run(r#"let x = 1
+x[0..1]"#).is_err());
+    }
+
+    #[test]
+    fn list_loop() {
+        assert_eq!(
+            run(r#"
+let sum=0
+let numbers:list<i64> = [1, 2, 3]
+for a in numbers:
+    sum = sum + a
+sum
+"#),
+            Ok(Value::I64(6))
+        );
+    }
+
+    #[test]
+    fn infer_type() {
+        assert_eq!(
+            run(r#"let a=1
+a"#),
+            Ok(Value::I64(1))
+        );
+    }
+
+    #[test]
+    fn incompatible_operands() {
+        assert_eq!(
+            run(r#"let a = 1
+let b = "x"
+
+let c = a * b"#)
+            .map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 4, Cannot apply '*' to i64 and string".to_string())
+        );
+    }
+
+    #[test]
+    fn incompatible_operands_nested() {
+        assert_eq!(
+            run(r#"let b = true
+let c = [b - 1]"#)
+            .map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 2, Cannot apply '-' to bool and integer".to_string())
+        );
+    }
+
+    #[test]
+    fn literal_does_not_combine_with_other_types() {
+        for src in [r#"1 + true"#, r#"2.0 * true"#, r#"1 + d"2025-01-01""#, r#"1 - "a""#] {
+            let error = run(src).unwrap_err().to_string();
+            assert!(error.contains("Cannot apply"), "{}: {}", src, error);
+        }
+    }
+
+    #[test]
+    fn literal_takes_the_type_of_the_other_operand() {
+        assert_eq!(run("let x:u32 = 5\nx + 1"), Ok(Value::U32(6)));
+        assert_eq!(run("let x:i32 = 5\nx - 1"), Ok(Value::I32(4)));
+        assert_eq!(run("let x:u64 = 5\nx * 2"), Ok(Value::U64(10)));
+        assert_eq!(run("let x:f32 = 1.5\n2 * x"), Ok(Value::F32(3.0)));
+        assert_eq!(run("let x:u32 = 5\nx > 1"), Ok(Value::Bool(true)));
+        assert_eq!(run("let x = -1 - 1\nx"), Ok(Value::I64(-2)));
+        assert_eq!(run("1 + 2.5"), Ok(Value::F64(3.5)));
+    }
+
+    #[test]
+    fn negative_literal_does_not_combine_with_unsigned() {
+        assert_eq!(
+            run("let x:u32 = 5\nx - -1").map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 2, Cannot apply '-' to u32 and i32/64"
+                .to_string())
+        );
+    }
+
+    #[test]
+    fn literal_that_does_not_fit() {
+        assert!(matches!(
+            run("let x:u32 = 5\nx + 5000000000"),
+            Err(Runtime(LossyConversion(..)))
+        ));
+    }
+
+    #[test]
+    fn missing_operand() {
+        assert_eq!(
+            run("let a = 1 +\nlet b = 2").map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 1, Expected an expression.".to_string())
+        );
+    }
+
+    #[test]
+    fn untyped_map_value_in_arithmetic() {
+        assert_eq!(
+            run(r#"let m:map = {"a": 1}
+m["a"] + 1"#),
+            Ok(Value::I64(2))
+        );
+    }
+
+    #[test]
+    fn multiple_errors() {
+        assert_eq!(
+            run(r#"let a = 1 * "x"
+let b = 2
+let c = true - 1
+b"#)
+            .map_err(|e| e.to_string()),
+            Err("Compilation failed with 2 error(s):
+error at line 1, Cannot apply '*' to integer and string
+error at line 3, Cannot apply '-' to bool and integer"
+                .to_string())
+        );
+    }
+
+    #[test]
+    fn errors_in_multiple_functions() {
+        assert_eq!(
+            run(r#"fn f(:
+    let x = 1
+    x
+
+fn g() -> i64:
+    let y = true - 1
+    y
+
+g()"#)
+            .map_err(|e| e.to_string()),
+            Err("Compilation failed with 2 error(s):
+error at line 1, Expected a parameter name.
+error at line 6, Cannot apply '-' to bool and integer"
+                .to_string())
+        );
+    }
+
+    // runs the test on a temp dir with the files, and removes it afterwards
+    fn in_source_dir<T>(name: &str, files: &[(&str, &str)], test: impl FnOnce(&str) -> T) -> T {
+        let dir = std::env::temp_dir().join(format!("tipi_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, source) in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+        let result = test(dir.to_str().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    #[test]
+    fn errors_in_all_source_files() {
+        let files = [("a.tp", "let a = 1 +\n"), ("b.tp", "let b = 1\nlet c = b * \"x\"\n")];
+        let result = in_source_dir("errors", &files, compile_sourcedir);
+
+        match result {
+            Err(Diagnostics(diagnostics)) => {
+                let mut paths: Vec<_> = diagnostics
+                    .iter()
+                    .map(|d| (d.path.rsplit('/').next().unwrap(), d.error.line))
+                    .collect();
+                paths.sort();
+                assert_eq!(paths, vec![("a.tp", 1), ("b.tp", 2)]);
+            }
+            other => panic!("expected diagnostics, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    // compiles the files in a temp dir, returning the warnings with their line
+    fn lints(name: &str, files: &[(&str, &str)]) -> Vec<(String, usize)> {
+        in_source_dir(name, files, warnings)
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.error.error.to_string(), d.error.line))
+            .collect()
+    }
+
+    #[test]
+    fn unused_variables_and_parameters() {
+        let warnings = lints(
+            "unused",
+            &[(
+                "a.tp",
+                r#"fn f(a: i64, _b: i64) -> i64:
+    let x = 1
+    let y = 2
+    y
+let total = 0
+total = f(1, 2)
+"#,
+            )],
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                ("unused parameter 'a'".to_string(), 1),
+                ("unused variable 'x'".to_string(), 2),
+                ("unused variable 'total'".to_string(), 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn shadowing() {
+        let warnings = lints(
+            "shadowing",
+            &[(
+                "a.tp",
+                r#"fn f(a: i64) -> i64:
+    let a = a + 1
+    a
+let sum = 0
+print f(sum)
+for sum in 1..4:
+    print sum
+for i in 1..4:
+    print i
+for i in [1, 2]:
+    print i
+"#,
+            )],
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                ("'a' shadows an earlier declaration".to_string(), 2),
+                ("'sum' shadows an earlier declaration".to_string(), 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn constant_condition() {
+        let warnings = lints(
+            "constant",
+            &[("a.tp", "let a = 1
+if (1 == 1):
+    a
+if a == 1:
+    a
+")],
+        );
+        assert_eq!(
+            warnings,
+            vec![("the condition is constant, so one of the branches never runs".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn unroutable_handler() {
+        let warnings = lints(
+            "unroutable",
+            &[(
+                "web.tp",
+                r#"fn get(path: string) -> string:
+    greet(path)
+fn greet(name: string) -> string:
+    "hello " + name
+fn all() -> string:
+    "all"
+"#,
+            )],
+        );
+        assert_eq!(
+            warnings,
+            vec![("'all' is not an http method, so requests are never routed to it".to_string(), 5)]
+        );
+    }
+
+    #[test]
+    fn unused_object_in_any_file() {
+        let warnings = lints(
+            "objects",
+            &[
+                ("a.tp", "object Person:
+    name: string
+object Pet:
+    name: string
+"),
+                ("b.tp", "fn f(p: list<Pet>) -> list<Pet>:
+    p
+"),
+            ],
+        );
+        assert_eq!(warnings, vec![("object 'Person' is never used".to_string(), 1)]);
+    }
+
+    #[test]
+    fn warnings_are_configured_in_tipi_toml() {
+        let source = "let x = 1
+if true:
+    print 1
+";
+        let toml = "[warnings]
+unused_variable = \"allow\"
+constant_condition = \"deny\"
+";
+        let warnings = lints("configured", &[("a.tp", source), ("tipi.toml", toml)]);
+        assert_eq!(
+            warnings,
+            vec![("the condition is constant, so one of the branches never runs".to_string(), 2)]
+        );
+
+        let files = [("a.tp", source), ("tipi.toml", toml)];
+        let result = in_source_dir("denied", &files, compile_sourcedir);
+        let Err(Diagnostics(diagnostics)) = result else {
+            panic!("expected the denied warning to fail compilation")
+        };
+        assert_eq!(
+            diagnostics[0].to_string().lines().last(),
+            Some("  = help: 'constant_condition' is set to deny in tipi.toml")
+        );
+    }
+
+    #[test]
+    fn unknown_warning_in_tipi_toml() {
+        let toml = "[warnings]
+unused = \"allow\"
+";
+        let result = in_source_dir("unknown", &[("tipi.toml", toml)], compile_sourcedir);
+        assert_eq!(
+            result.map(|_| ()),
+            Err(Platform("tipi.toml: unknown warning 'unused'".to_string()))
+        );
+    }
+
+    #[test]
+    fn warnings_do_not_fail_compilation() {
+        let result = in_source_dir("warn", &[("a.tp", "let x = 1\n")], compile_sourcedir);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn ir_resolves_variables_and_calls() {
+        let ir = ir(r#"fn add(a: i64, b: i64) -> i64:
+    a + b
+let x = 1
+let y = 2
+add(x, y)"#)
+        .unwrap();
+        assert_eq!(ir.locals, vec![(I64, "x".to_string()), (I64, "y".to_string())]);
+        let [_, x, y, call] = ir.body.as_slice() else {
+            panic!("expected 4 statements")
+        };
+        assert!(matches!(&x.kind, Let { slot: 0, value } if matches!(value.kind, Constant(_))));
+        assert!(matches!(&y.kind, Let { slot: 1, .. }));
+        let Expression(call) = &call.kind else {
+            panic!("expected a call")
+        };
+        assert_eq!(call.ir_type, I64);
+        let Call(name, arguments) = &call.kind else {
+            panic!("expected a call")
+        };
+        assert_eq!(name, "add");
+        assert!(matches!(
+            arguments.as_slice(),
+            [a, b] if matches!((&a.kind, &b.kind), (Get(0), Get(1)))
+        ));
+    }
+
+    #[test]
+    fn ir_captures_enclosing_variables() {
+        let ir = ir(r#"let n = 2
+let times = fn(x: i64) -> i64: x * n"#)
+        .unwrap();
+        let Let { value, .. } = &ir.body[1].kind else {
+            panic!("expected a let")
+        };
+        let Closure { function, captured, .. } = &value.kind else {
+            panic!("expected a closure")
+        };
+        assert_eq!(function.captures, vec![1]);
+        assert_eq!(function.locals, vec![(I64, "x".to_string()), (I64, "n".to_string())]);
+        assert!(matches!(captured.as_slice(), [n] if matches!(n.kind, Get(0))));
+        let Expression(body) = &function.body[0].kind else {
+            panic!("expected an expression")
+        };
+        assert!(matches!(&body.kind, Binary(..)));
+    }
+
+    #[test]
+    fn disassembler() {
+        let registry = compile("let a = 1\nprint a + 2\nlet s = \"x\"").unwrap();
+        assert_eq!(
+            disassemble("main", &registry["main"]),
+            r#"== main ==
+0000    1 Constant(0)          1
+0001    | Assign(0)            a: i64
+0002    2 Get(0)               a: i64
+0003    | Constant(1)          2
+0004    | Add
+0005    | Print
+0006    3 Constant(2)          "x"
+0007    | Assign(1)            s: string
+0008    | Return
+"#
+        );
+    }
+
+    #[test]
+    fn calls_are_linked() {
+        let registry = compile(
+            r#"object Person:
+    name: string
+
+fn greet(p: Person) -> string:
+    "hello " + p.name
+
+let p = Person(name: "Sander")
+let d = days(1)
+greet(p).len()"#,
+        )
+        .unwrap();
+        let main = &registry["main"];
+        assert!(!main.code.iter().any(|op| matches!(op, Op::Call(..) | Op::CallBuiltin(..))));
+        let linked: Vec<_> = main
+            .links
+            .iter()
+            .map(|(call, _)| match call {
+                Op::Call(name, _) => main.constants[*name].to_string(),
+                Op::CallBuiltin(name, receiver, _) => {
+                    format!("{}.{}", main.constants[*receiver], main.constants[*name])
+                }
+                _ => panic!("not a call"),
+            })
+            .collect();
+        assert_eq!(linked, vec!["Person", "days", "greet", "string.len"]);
+        assert_eq!(crate::compiler::execute(registry), Ok(Value::U64(12)));
+    }
+
+    #[test]
+    fn object_constructed_in_function() {
+        assert_eq!(
+            run(r#"object Person:
+    name: string
+
+fn person(name: string) -> Person:
+    Person(name: name)
+
+person("Sander").name"#),
+            Ok(string("Sander"))
+        );
+    }
+
+    #[test]
+    fn linking_again_keeps_links() {
+        let mut registry = compile(
+            r#"fn add(a: i64, b: i64) -> i64:
+    a + b
+
+add(40, 2)"#,
+        )
+        .unwrap();
+        registry.insert("other".to_string(), registry["main"].clone());
+        link(&mut registry);
+        assert_eq!(crate::compiler::execute(registry), Ok(Value::I64(42)));
+    }
+
+    #[test]
+    fn bytecode_runs_the_same() {
+        let src = r##"object Person:
+    name: string
+
+fn greet(p: Person) -> string:
+    "hello " + p.name
+
+let n = 2
+let times = fn(x: i64) -> i64: x * n
+greet(Person(name: "Sander")) + f" {times(21)}""##;
+        let bytes = bytecode::write(&compile(src).unwrap()).unwrap();
+        let registry = bytecode::read(&bytes).unwrap();
+        assert!(!registry["main"].links.is_empty());
+        assert_eq!(crate::compiler::execute(registry), run(src));
+    }
+
+    #[test]
+    fn bytecode_keeps_the_arguments_of_each_call() {
+        let src = r#"object Point:
+    x: i64
+    y: i64
+
+let a = Point(x: 1, y: 2)
+let b = Point(x: 3)"#;
+        let bytes = bytecode::write(&compile(src).unwrap()).unwrap();
+        let registry = bytecode::read(&bytes).unwrap();
+        let missing_field = crate::errors::RuntimeError::IllegalArgumentsException;
+        let missing_field = Err(Runtime(missing_field("Point".to_string(), 2, 1)));
+        assert_eq!(run(src), missing_field);
+        assert_eq!(crate::compiler::execute(registry), missing_field);
+    }
+
+    #[test]
+    fn bytecode_keeps_all_value_kinds() {
+        let mut chunk = crate::compiler::assembly_pass::AsmChunk::new("main");
+        let date_time =
+            DateTime::parse_from_str("2025-11-09 16:44:28.000 +0100", DATE_FORMAT_TIMEZONE)
+                .unwrap();
+        let mut map = HashMap::new();
+        map.insert(string("key"), Value::List(vec![Value::I32(1), Value::Void].into()));
+        chunk.constants = vec![
+            Value::U32(1),
+            Value::I32(-1),
+            Value::U64(2),
+            Value::I64(-2),
+            Value::F32(1.5),
+            Value::F64(2.5),
+            Value::Decimal(Decimal::new(31415, 4)),
+            string("text"),
+            Value::Char('x'),
+            Value::Bool(true),
+            Value::DateTime(Box::new(date_time)),
+            Value::Date(NaiveDate::from_ymd_opt(2025, 11, 9).unwrap()),
+            Value::Duration(chrono::TimeDelta::milliseconds(-1500)),
+            Value::Uuid(uuid::Uuid::from_u128(42)),
+            Value::Enum,
+            Value::Map(map.into()),
+            Value::ObjectType(Box::new(crate::value::Object {
+                definition: "Person".to_string(),
+                fields: vec![("name".to_string(), string("Sander"))],
+            })),
+            Value::Function(Box::new(crate::value::Closure {
+                name: "main/lambda".to_string(),
+                captured: vec![Value::I64(2)],
+            })),
+            Value::Error("failed".to_string()),
+            Value::Void,
+        ];
+        let mut registry = crate::AsmRegistry::new();
+        registry.insert("main".to_string(), chunk.clone());
+        let read = bytecode::read(&bytecode::write(&registry).unwrap()).unwrap();
+        // enums, errors and objects are never equal, so the debug output is compared
+        assert_eq!(format!("{:?}", read["main"].constants), format!("{:?}", chunk.constants));
+    }
+
+    #[test]
+    fn bytecode_version_is_checked() {
+        let mut bytes = bytecode::write(&compile("1").unwrap()).unwrap();
+        bytes[4] += 1;
+        assert!(matches!(
+            bytecode::read(&bytes),
+            Err(Bytecode(message)) if message.contains("Rebuild from source")
+        ));
+    }
+
+    #[test]
+    fn bytecode_checksum_is_checked() {
+        let mut bytes = bytecode::write(&compile("1").unwrap()).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        assert!(matches!(
+            bytecode::read(&bytes),
+            Err(Bytecode(message)) if message.contains("checksum")
+        ));
+    }
+
+    #[test]
+    fn not_a_bytecode_file() {
+        assert!(matches!(bytecode::read(b"1 + 1"), Err(Bytecode(_))));
+    }
+
+    #[test]
+    fn bundle_is_appended_to_the_runtime() {
+        let src = r#"fn add(a: i64, b: i64) -> i64:
+    a + b
+
+add(40, 2)"#;
+        let mut assets = HashMap::new();
+        assets.insert("/index.html".to_string(), b"<h1>hello</h1>".to_vec());
+        let runtime = b"runtime".to_vec();
+        assert!(bundle::unpack(&runtime).unwrap().is_none());
+
+        let executable = bundle::pack(&runtime, &compile(src).unwrap(), &assets).unwrap();
+        assert!(executable.starts_with(&runtime));
+        let unpacked = bundle::unpack(&executable).unwrap().unwrap();
+        assert_eq!(unpacked.assets, assets);
+        assert_eq!(crate::compiler::execute(unpacked.registry), Ok(Value::I64(42)));
+
+        // bundling a bundled executable replaces its bundle
+        let rebundled = bundle::pack(&executable, &compile("1").unwrap(), &HashMap::new()).unwrap();
+        assert!(rebundled.len() < executable.len());
+        let unpacked = bundle::unpack(&rebundled).unwrap().unwrap();
+        assert_eq!(crate::compiler::execute(unpacked.registry), Ok(Value::I64(1)));
+    }
+
+    #[test]
+    fn assets_are_served_on_their_path() {
+        let files = [("css/site.css", "h1 {}")];
+        let assets = in_source_dir("assets", &files, bundle::read_assets).unwrap();
+        assert_eq!(assets.keys().collect::<Vec<_>>(), vec!["/css/site.css"]);
+
+        // a route would read it as a parameter
+        let files = [("users/{id}.json", "{}")];
+        assert_eq!(
+            in_source_dir("route_assets", &files, bundle::read_assets).map(|_| ()),
+            Err(Platform(
+                "/users/{id}.json: a static file cannot have '{', '}', '*' or a leading ':' in its path"
+                    .to_string()
+            ))
+        );
+    }
+
+    // transpiles the files in a temp dir to rust
+    fn transpiled(name: &str, files: &[(&str, &str)]) -> Result<String, TipiLangError> {
+        in_source_dir(name, files, transpile_sourcedir).map(|rust| rust.main_rs)
+    }
+
+    #[test]
+    fn web_functions_are_transpiled_to_routes() {
+        let rust = transpiled(
+            "routes",
+            &[(
+                "api/greeting/web.tp",
+                r#"object Greeting:
+    text: string
+
+fn get(name: string, times: i64) -> list:
+    let words = [f"hi {name}"]
+    words.push(name.to_uppercase())
+
+fn post(path: string) -> i64:
+    path.len() as i64 * 2"#,
+            )],
+        )
+        .unwrap();
+        assert!(rust.contains("struct Greeting {\n    text: String,\n}"));
+        assert!(rust.contains(
+            "fn api_greeting_web_get(mut name: String, mut times: i64) -> Result<Vec<String>, Error>"
+        ));
+        assert!(rust.contains("parameter::<i64>(&query, \"times\", &mut path_param)?"));
+        assert!(rust.contains("api_greeting_web_post(uri.to_string())"));
+        assert!(rust.contains(
+            ".route(\"/api/greeting\", get(api_greeting_web_get_route).post(api_greeting_web_post_route))"
+        ));
+        assert!(rust.contains(".route(\"/api/greeting/{param}\""));
+    }
+
+    #[test]
+    fn unsupported_code_is_not_transpiled() {
+        let result = transpiled("unsupported", &[("web.tp", "fn get() -> date:\n    today()")]);
+        match result {
+            Err(Diagnostics(diagnostics)) => {
+                assert_eq!(diagnostics.len(), 1);
+                assert_eq!(diagnostics[0].error.line, 2);
+                assert_eq!(
+                    diagnostics[0].error.error.to_string(),
+                    "function today cannot be transpiled to Rust yet"
+                );
+            }
+            other => panic!("expected diagnostics, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    // the errors are those of the vm, by name, and the values their display
+    fn vm_result(result: Result<Value, TipiLangError>) -> String {
+        match result {
+            Ok(value) => format!("Ok({})", value),
+            Err(Runtime(e)) => {
+                let error = format!("{:?}", e);
+                format!("Err({})", error.split('(').next().unwrap())
+            }
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn transpiled_code_raises_the_errors_of_the_vm() {
+        let functions = r#"fn divide(a: i64, b: i64) -> i64:
+    a / b
+
+fn add(a: i32, b: i32) -> i32:
+    a + b
+
+fn power(a: i64, b: i64) -> i64:
+    a ** b
+
+fn shift(a: u32, b: u32) -> u32:
+    a << b
+
+fn negate(a: i64) -> i64:
+    -a
+
+fn narrow(a: i64) -> i32:
+    a as i32
+
+fn truncate(a: f64) -> i64:
+    a as i64
+
+fn at(xs: list<i64>, i: i64) -> i64:
+    xs[i]
+
+fn increment(xs: list<i64>, i: i64) -> i64:
+    xs[i] += 1
+    xs[i]
+
+fn lookup(m: map<string, i64>, k: string) -> i64:
+    m[k]"#;
+        // the call in tipi and in rust
+        let calls = [
+            ("divide(7, 2)", "calc_divide(7, 2)"),
+            ("divide(7, 0)", "calc_divide(7, 0)"),
+            ("divide(-9223372036854775807 - 1, -1)", "calc_divide(i64::MIN, -1)"),
+            ("add(2147483647 as i32, 1 as i32)", "calc_add(i32::MAX, 1)"),
+            ("power(3, 4)", "calc_power(3, 4)"),
+            ("power(10, 19)", "calc_power(10, 19)"),
+            ("shift(1 as u32, 31 as u32)", "calc_shift(1, 31)"),
+            ("shift(2 as u32, 31 as u32)", "calc_shift(2, 31)"),
+            ("negate(-9223372036854775807 - 1)", "calc_negate(i64::MIN)"),
+            ("narrow(5)", "calc_narrow(5)"),
+            ("narrow(3000000000)", "calc_narrow(3000000000)"),
+            ("truncate(2.0)", "calc_truncate(2.0)"),
+            ("truncate(2.5)", "calc_truncate(2.5)"),
+            ("at([1, 2, 3], -1)", "calc_at(vec![1, 2, 3], -1)"),
+            ("at([1, 2, 3], 3)", "calc_at(vec![1, 2, 3], 3)"),
+            ("at([1, 2, 3], -4)", "calc_at(vec![1, 2, 3], -4)"),
+            ("increment([1, 2, 3], -2)", "calc_increment(vec![1, 2, 3], -2)"),
+            (r#"lookup({"a": 1}, "a")"#, r#"calc_lookup(HashMap::from([("a".into(), 1)]), "a".into())"#),
+            (r#"lookup({"a": 1}, "b")"#, r#"calc_lookup(HashMap::from([("a".into(), 1)]), "b".into())"#),
+        ];
+
+        let prints: String = calls
+            .iter()
+            .map(|(_, call)| format!("    println!(\"{{:?}}\", {});\n", call))
+            .collect();
+        let main = format!("fn main() {{\n{}}}\n", prints);
+        let stdout = in_source_dir("vm", &[("calc.tp", functions)], |dir| {
+            let modules = crate::compiler::transpile_modules(dir).unwrap();
+            let dir = std::path::Path::new(dir);
+            let program = crate::compiler::rust_pass::program(&modules, &main);
+            std::fs::write(dir.join("main.rs"), program).unwrap();
+            let compiled = std::process::Command::new("rustc")
+                .args(["--edition", "2024", "-o"])
+                .arg(dir.join("main"))
+                .arg(dir.join("main.rs"))
+                .output()
+                .unwrap();
+            if !compiled.status.success() {
+                return Err(String::from_utf8_lossy(&compiled.stderr).to_string());
+            }
+            let output = std::process::Command::new(dir.join("main")).output().unwrap();
+            Ok(String::from_utf8(output.stdout).unwrap())
+        })
+        .unwrap_or_else(|errors| panic!("{}", errors));
+
+        let rust: Vec<_> = stdout.lines().collect();
+        let vm: Vec<_> = calls
+            .iter()
+            .map(|(call, _)| vm_result(run(&format!("{}\n{}", functions, call))))
+            .collect();
+        assert_eq!(rust, vm);
+    }
+
+    // the optimized code gives the same result, with fewer ops
+    fn assert_optimized(src: &str, expected: Value) -> crate::compiler::assembly_pass::AsmChunk {
+        assert_eq!(run_unoptimized(src), Ok(expected.clone()));
+        assert_eq!(run(src), Ok(expected));
+        let optimized = compile(src).unwrap().swap_remove("main").unwrap();
+        let unoptimized = compile_unoptimized(src).unwrap().swap_remove("main").unwrap();
+        assert!(
+            optimized.code.len() < unoptimized.code.len(),
+            "{}",
+            disassemble("main", &optimized)
+        );
+        optimized
+    }
+
+    #[test]
+    fn constant_folding() {
+        let chunk = assert_optimized("let a = (1 + 2) * 3 - 12\na", Value::I64(-3));
+        assert_eq!(
+            disassemble("main", &chunk),
+            r#"== main ==
+0000    1 Constant(0)          -3
+0001    | Assign(0)            a: i64
+0002    2 Get(0)               a: i64
+0003    | Return
+"#
+        );
+        assert_optimized(r#""total: " + 2 * 21"#, Value::String("total: 42".into()));
+        assert_optimized("1 < 2 && !false", Value::Bool(true));
+        assert_optimized("(2.0 as i64) * 4", Value::I64(8));
+    }
+
+    #[test]
+    fn folding_leaves_errors_to_runtime() {
+        let src = "let a = 1\nlet b = 9223372036854775807 + 1";
+        assert_eq!(run(src), Err(Runtime(Overflow(2))));
+        assert_eq!(run("let a = 1\nlet b = 1 / 0"), Err(Runtime(DivisionByZero(2))));
+    }
+
+    #[test]
+    fn dead_branches_are_removed() {
+        let chunk = assert_optimized(
+            r#"let a = 1
+if 1 > 2:
+    a
+else:
+    a + 1"#,
+            Value::I64(2),
+        );
+        assert!(!chunk.code.iter().any(|op| matches!(op, GotoIfNot(_) | Goto(_))));
+    }
+
+    #[test]
+    fn if_without_dup_and_pop() {
+        let chunk = assert_optimized(
+            r#"let a = 2
+if a == 2:
+    a * 10
+else:
+    a"#,
+            Value::I64(20),
+        );
+        assert!(!chunk.code.contains(&Dup));
+        assert!(!chunk.code.contains(&Pop));
+    }
+
+    #[test]
+    fn redundant_gotos_are_removed() {
+        let chunk = assert_optimized(
+            r#"let sum = 0
+for i in 1..=5:
+    if i % 2 == 0:
+        sum = sum + i
+sum"#,
+            Value::I64(6),
+        );
+        for (ip, op) in chunk.code.iter().enumerate() {
+            assert_ne!(op, &Goto(ip + 1));
+        }
+    }
+
+    #[test]
+    fn constants_are_deduplicated() {
+        let src = r#"let a = 1
+let b = 1
+let c = 1.0
+let d = "x"
+let e = "x"
+a + b"#;
+        assert_eq!(run_unoptimized(src), run(src));
+        let chunk = compile(src).unwrap().swap_remove("main").unwrap();
+        assert_eq!(
+            chunk.constants,
+            vec![Value::I64(1), Value::F64(1.0), Value::String("x".into())]
+        );
+    }
+
+    #[test]
+    fn negative_zero_is_not_merged_with_zero() {
+        assert_eq!(
+            run(r#"let a = 0.0
+let b = -0.0
+1.0 / b"#),
+            Ok(Value::F64(f64::NEG_INFINITY))
+        );
+    }
+
+    #[test]
+    fn decimals_keep_their_scale() {
+        assert_eq!(
+            run(r#"let a = 2.0m
+let b = 2.00m
+f"{a} {b}""#),
+            Ok(string("2.0 2.00"))
+        );
+    }
+
+    #[test]
+    fn datetimes_keep_their_offset() {
+        assert_eq!(
+            run(r#"let a = d"2025-11-09 10:00:00.000 +0200"
+let b = d"2025-11-09 08:00:00.000 +0000"
+b.to_string()"#),
+            Ok(string("2025-11-09 08:00:00.000 +0000"))
+        );
+    }
+
+    #[test]
+    fn diagnostic_snippet() {
+        let source = "let b = 1\nlet c = b * 2.5\n";
+        let Err(Compiler(error)) = run(source) else {
+            panic!("expected a compiler error")
+        };
+        assert_eq!(
+            Diagnostic::new("source/a.tp", source, error).to_string(),
+            r#"error: Cannot apply '*' to i64 and float
+ --> source/a.tp:2:9
+  |
+2 | let c = b * 2.5
+  |         ^^^^^^^
+  = help: convert one of the operands with 'as', like x as f64"#
+        );
+    }
+
+    #[test]
+    fn diagnostic_columns_count_chars() {
+        let source = r#"let s = "é" + ("ü" - 1)"#;
+        let Err(Compiler(error)) = run(source) else {
+            panic!("expected a compiler error")
+        };
+        assert_eq!(error.span.map(|s| (s.start, s.end, s.column)), Some((16, 24, 16)));
+        assert_eq!(
+            Diagnostic::new("", source, error).to_string(),
+            r#"error: Cannot apply '-' to string and integer
+ --> 1:16
+  |
+1 | let s = "é" + ("ü" - 1)
+  |                ^^^^^^^"#
+        );
+    }
+
+    #[test]
+    fn diagnostic_in_interpolation() {
+        let source = r#"let s = f"a{1 - "b"}""#;
+        let Err(Compiler(error)) = run(source) else {
+            panic!("expected a compiler error")
+        };
+        let span = error.span.unwrap();
+        assert_eq!(&source[span.start as usize..span.end as usize], r#"1 - "b""#);
+        assert_eq!(span.column, 13);
+    }
+
+    #[test]
+    fn define_u32() {
+        assert_eq!(
+            run(r#"let a:u32=1
+a"#),
+            Ok(Value::U32(1))
+        );
+    }
+
+    #[test]
+    fn define_char() {
+        assert_eq!(
+            run(r#"let a:char='a'
+a"#),
+            Ok(Value::Char('a'))
+        );
+    }
+
+    #[test]
+    fn define_u32_invalid_value_negative() {
+        let r = compile("let a:u32=-1");
+        assert!(r.is_err());
+        if let Err(e) = &r {
+            assert_eq!(
+                e.to_string(),
+                "Compilation failed: error at line 1, Expected u32, found i32/64"
+            );
+        }
+    }
+
+    #[test]
+    fn define_u64_invalid_value_negative() {
+        let r = compile("let a:u64=-1");
+        assert!(r.is_err());
+        if let Err(e) = &r {
+            assert_eq!(
+                e.to_string(),
+                "Compilation failed: error at line 1, Expected u64, found i32/64"
+            );
+        }
+    }
+
+    #[test]
+    fn let_u64_invalid_value_string() {
+        let r = compile(r#"let a:u64="not ok""#);
+        assert!(r.is_err());
+        if let Err(e) = &r {
+            assert_eq!(
+                e.to_string(),
+                "Compilation failed: error at line 1, Expected u64, found string"
+            );
+        }
+    }
+
+    #[test]
+    fn call_fn_with_args_returns_value() {
+        assert_eq!(
+            run(r#"fn add_hello(name: string) -> string:
+    "Hello " + name
+add_hello("world")"#),
+            Ok(string("Hello world"))
+        );
+    }
+
+    #[test]
+    fn define_object() {
+        let r = compile(
+            r#"
+object Person:
+   name: string"#,
+        );
+        assert!(r.is_ok()); // does nothing runtime
+    }
+
+    #[test]
+    fn declare_and_instantiate_object() {
+        let r = run(r#"
+object Person:
+   name: string
+
+let p = Person(name: "Sander")
+p"#);
+        assert!(r.is_ok());
+        assert_eq!(
+            r#"Person(name: "Sander")"#,
+            format!("{}", r.unwrap())
+        );
+    }
+
+    #[test]
+    fn declare_and_instantiate_object_wrong_type() {
+        let r = run(r#"
+object Person:
+   name: string
+
+let p = Person(name: 0x42)
+p"#);
+        // assert!(r.is_err());
+        assert_eq!(
+            r#"Compilation failed: error at line 5, Expected string, found integer"#,
+            format!("{}", r.unwrap_err())
+        );
+    }
+
+    #[test]
+    fn object_field() {
+        assert_eq!(
+            run(r#"
+object Person:
+   name: string
+   age: i64
+
+let p = Person(name: "Sander", age: 40)
+p.age += 1
+p.name = p.name + "!"
+f"{p.name} {p.age}""#),
+            Ok(string("Sander! 41"))
+        );
+    }
+
+    #[test]
+    fn object_field_compound_wrong_type() {
+        assert!(run(r#"
+object Person:
+   age: i64
+
+let p = Person(age: 40)
+p.age += 1.5"#).is_err());
+    }
+
+    #[test]
+    fn literal_map() {
+        let result = run(r#"{"name": "Dent", "age": 40 }"#);
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        if let Value::Map(map) = result {
+            assert_eq!(map.get(&string("name")).unwrap(), &string("Dent"));
+            assert_eq!(map.get(&string("age")).unwrap(), &Value::I64(40));
+        }
+    }
+
+    #[test]
+    fn assign_map() {
+        let result = run(r#"let m = {"name": "Dent"}
+m"#);
+
+        let result = result.unwrap();
+        if let Value::Map(map) = result {
+            assert_eq!(map.get(&string("name")).unwrap(), &string("Dent"));
+        }
+    }
+
+    #[test]
+    fn access_map() {
+        let result = run(r#"let m = {"name": "Dent"}
+m["name"]"#);
+
+        let result = result.unwrap();
+        if let Value::String(v) = result {
+            assert_eq!(&*v, "Dent");
+        }
+    }
+
+    #[test]
+    fn keyword_error() {
+        let result = run(r#"let map = {"name": "Dent"}"#);
+        assert!(result.is_err());
+        assert_eq!(
+            "Compilation failed: error at line 1, 'map' is a keyword. You cannot use it as an identifier",
+            result.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn add_strings() {
+        assert_eq!(run(r#""a"+"b""#), Ok(string("ab")));
+    }
+
+    #[test]
+    fn add_string_and_int() {
+        assert_eq!(run(r#""a"+42"#), Ok(string("a42")));
+    }
+
+    #[test]
+    fn add_string_and_bool() {
+        assert_eq!(run(r#""a"+false"#), Ok(string("afalse")));
+    }
+
+    #[test]
+    fn add_string_and_scientific_float() {
+        assert_eq!(
+            run(r#""a"+4.2e10"#),
+            Ok(Value::String("a42000000000".into()))
+        );
+    }
+
+    #[test]
+    fn add_hex_ints() {
+        assert_eq!(run(r#"0x10 + 0x20"#), Ok(Value::U32(48)));
+    }
+
+    #[test]
+    fn date_literal() {
+        assert_eq!(
+            run(r#"let moment:datetime = d"2025-11-09 16:44:28.000 +0100"
+moment"#),
+            Ok(Value::DateTime(Box::new(
+                DateTime::parse_from_str(
+                    "2025-11-09 16:44:28.000 +0100", DATE_FORMAT_TIMEZONE
+                )
+                .unwrap()
+            )))
+        );
+    }
+
+    #[test]
+    fn date_only_literal() {
+        assert_eq!(
+            run(r#"let day:date = d"2025-11-09"
+day"#),
+            Ok(Value::Date(NaiveDate::from_ymd_opt(2025, 11, 9).unwrap()))
+        );
+    }
+
+    #[test]
+    fn datetime_plus_duration() {
+        assert_eq!(
+            run(r#"let moment:datetime = d"2025-11-09 16:44:28.000 +0100" + days(2) + hours(1)
+moment"#)
+            .map(|v| v.to_string()),
+            Ok("2025-11-11 17:44:28.000 +0100".to_string())
+        );
+    }
+
+    #[test]
+    fn datetime_minus_datetime() {
+        assert_eq!(
+            run(r#"let elapsed:duration = d"2025-11-09 16:44:28.000 +0100" - d"2025-11-09 15:44:28.000 +0100"
+elapsed.minutes()"#),
+            Ok(Value::I64(60))
+        );
+    }
+
+    #[test]
+    fn date_minus_date() {
+        assert_eq!(
+            run(r#"(d"2025-03-01" - d"2025-02-01").days()"#),
+            Ok(Value::I64(28))
+        );
+    }
+
+    #[test]
+    fn date_plus_duration() {
+        assert_eq!(
+            run(r#"d"2025-12-31" + days(1)"#),
+            Ok(Value::Date(NaiveDate::from_ymd_opt(2026, 1, 1).unwrap()))
+        );
+    }
+
+    #[test]
+    fn datetime_components() {
+        assert_eq!(
+            run(r#"let moment = d"2025-11-09 16:44:28.000 +0100"
+moment.year() + moment.month() + moment.hour()"#),
+            Ok(Value::I64(2025 + 11 + 16))
+        );
+    }
+
+    #[test]
+    fn datetime_format() {
+        assert_eq!(
+            run(r#"d"2025-11-09 16:44:28.000 +0100".format("%d/%m/%Y %H:%M")"#),
+            Ok(string("09/11/2025 16:44"))
+        );
+    }
+
+    #[test]
+    fn date_parse() {
+        assert_eq!(
+            run(r#"parse_date("09/11/2025", "%d/%m/%Y")"#),
+            Ok(Value::Date(NaiveDate::from_ymd_opt(2025, 11, 9).unwrap()))
+        );
+    }
+
+    #[test]
+    fn date_parse_invalid() {
+        assert!(matches!(
+            run(r#"parse_date("2025", "%d/%m/%Y")"#),
+            Err(Runtime(IllegalArgumentException(_)))
+        ));
+    }
+
+    #[test]
+    fn datetime_to_timezone() {
+        assert_eq!(
+            run(r#"d"2025-07-01 12:00:00.000 +0000".to_timezone("Europe/Amsterdam")"#)
+                .map(|v| v.to_string()),
+            Ok("2025-07-01 14:00:00.000 +0200".to_string())
+        );
+    }
+
+    #[test]
+    fn datetime_to_unknown_timezone() {
+        assert_eq!(
+            run(r#"d"2025-07-01 12:00:00.000 +0000".to_timezone("Mars/Olympus")"#),
+            Err(Runtime(IllegalArgumentException(
+                "Unknown timezone 'Mars/Olympus'".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn uuid_literal() {
+        assert_eq!(
+            run(r#"let id:uuid = u"67e55044-10b1-426f-9247-bb680e5fe0c8"
+id"#),
+            Ok(Value::Uuid(
+                uuid::Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn uuid_literal_invalid() {
+        assert_eq!(
+            run(r#"u"not-a-uuid""#),
+            Err(Compiler(CompilerErrorAtLine {
+                error: InvalidLiteral(Uuid, "not-a-uuid".to_string()),
+                line: 1,
+                span: Some(Span { start: 0, end: 13, line: 1, column: 1 })
+            }))
+        );
+    }
+
+    #[test]
+    fn date_literal_invalid() {
+        assert_eq!(
+            run(r#"d"2025-13-01""#).map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 1, Invalid date literal '2025-13-01'".to_string())
+        );
+    }
+
+    #[test]
+    fn uuid_equality() {
+        assert_eq!(
+            run(r#"u"67e55044-10b1-426f-9247-bb680e5fe0c8" == u"67e55044-10b1-426f-9247-bb680e5fe0c8""#),
+            Ok(Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn uuid_as_map_key() {
+        assert_eq!(
+            run(r#"let m = {u"67e55044-10b1-426f-9247-bb680e5fe0c8": "Dent"}
+m[u"67e55044-10b1-426f-9247-bb680e5fe0c8"]"#),
+            Ok(string("Dent"))
+        );
+    }
+
+    #[test]
+    fn uuid_generation() {
+        let v4 = run("uuid_v4()").unwrap();
+        let v7 = run("uuid_v7()").unwrap();
+        assert!(matches!(v4, Value::Uuid(u) if u.get_version_num() == 4));
+        assert!(matches!(v7, Value::Uuid(u) if u.get_version_num() == 7));
+    }
+
+    #[test]
+    fn uuid_serializes_as_string() {
+        let value = run(r#"u"67e55044-10b1-426f-9247-bb680e5fe0c8""#).unwrap();
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#""67e55044-10b1-426f-9247-bb680e5fe0c8""#
+        );
+    }
+
+    #[tokio::test]
+    async fn uuid_from_path_segment() {
+        let registry = compile(
+            r#"fn get(id: uuid) -> uuid:
+    id"#,
+        )
+        .unwrap();
+        let registry = arc_swap::ArcSwap::from(std::sync::Arc::new(registry));
+        let result = interpret_async(
+            registry.load(),
+            "main/get",
+            "/customer/67e55044-10b1-426f-9247-bb680e5fe0c8",
+            Some("67e55044-10b1-426f-9247-bb680e5fe0c8"),
+            HashMap::new(),
+            HashMap::new(),
+        )
+        .await;
+        assert_eq!(
+            result.map(|v| v.to_string()),
+            Ok("67e55044-10b1-426f-9247-bb680e5fe0c8".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn uuid_from_query_param_invalid() {
+        let registry = compile(
+            r#"fn get(id: uuid) -> uuid:
+    id"#,
+        )
+        .unwrap();
+        let registry = arc_swap::ArcSwap::from(std::sync::Arc::new(registry));
+        let query = HashMap::from([("id".to_string(), "42".to_string())]);
+        let result =
+            interpret_async(registry.load(), "main/get", "/", None, query, HashMap::new()).await;
+        assert_eq!(
+            result,
+            Err(IllegalParameter(
+                "Illegal value for parameter id: expected uuid, got '42'".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn handler_parameters_in_declared_order() {
+        let registry = compile(
+            r#"fn get(headers: map<string, string>, id: i64, path: string) -> string:
+    f"{path} {id} " + headers["accept"]"#,
+        )
+        .unwrap();
+        let registry = arc_swap::ArcSwap::from(std::sync::Arc::new(registry));
+        let headers = HashMap::from([("accept".to_string(), "text/plain".to_string())]);
+        let result = interpret_async(
+            registry.load(),
+            "main/get",
+            "/customer/7",
+            Some("7"),
+            HashMap::new(),
+            headers,
+        )
+        .await;
+        assert_eq!(result, Ok(string("/customer/7 7 text/plain")));
+    }
+
+    #[test]
+    fn decimal_literal() {
+        assert_eq!(run("19.99m"), Ok(Value::Decimal(Decimal::new(1999, 2))));
+    }
+
+    #[test]
+    fn decimal_arithmetic_is_exact() {
+        assert_eq!(
+            run(r#"let total: decimal = 0.1m + 0.2m
+total == 0.3m"#),
+            Ok(Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn decimal_times_integer() {
+        assert_eq!(
+            run(r#"let price = 19.99m
+price * 3"#),
+            Ok(Value::Decimal(Decimal::new(5997, 2)))
+        );
+    }
+
+    #[test]
+    fn decimal_from_integer() {
+        assert_eq!(
+            run(r#"let price: decimal = 20
+price - 0.01m"#),
+            Ok(Value::Decimal(Decimal::new(1999, 2)))
+        );
+    }
+
+    #[test]
+    fn decimal_negative() {
+        assert_eq!(run("-1.5m + 1"), Ok(Value::Decimal(Decimal::new(-5, 1))));
+    }
+
+    #[test]
+    fn decimal_comparison() {
+        assert_eq!(run("10.50m > 10.49m"), Ok(Value::Bool(true)));
+    }
+
+    #[test]
+    fn decimal_compared_to_integer() {
+        assert_eq!(run("19.99m > 0"), Ok(Value::Bool(true)));
+        assert_eq!(run("2.0m == 2"), Ok(Value::Bool(true)));
+        assert_eq!(run("2.5m != 2"), Ok(Value::Bool(true)));
+        assert_eq!(run("let x:u32 = 3\n2.5m < x"), Ok(Value::Bool(true)));
+    }
+
+    #[test]
+    fn decimal_key_finds_integer_key() {
+        assert_eq!(run(r#"let m = {2.0m: "a"}
+m[2]"#), Ok(string("a")));
+    }
+
+    #[test]
+    fn decimal_rounding() {
+        assert_eq!(run("2.345m.round_half_up(2)"), Ok(Value::Decimal(Decimal::new(235, 2))));
+        assert_eq!(run("2.345m.round_half_down(2)"), Ok(Value::Decimal(Decimal::new(234, 2))));
+        assert_eq!(run("2.345m.round_half_even(2)"), Ok(Value::Decimal(Decimal::new(234, 2))));
+        assert_eq!(run("2.341m.round_up(2)"), Ok(Value::Decimal(Decimal::new(235, 2))));
+        assert_eq!(run("2.349m.round_down(2)"), Ok(Value::Decimal(Decimal::new(234, 2))));
+    }
+
+    #[test]
+    fn decimal_serializes_as_string() {
+        let value = run("1234567890.123456789m").unwrap();
+        assert_eq!(serde_json::to_string(&value).unwrap(), r#""1234567890.123456789""#);
+    }
+
+    #[test]
+    fn decimal_maps_to_numeric() {
+        use tokio_postgres::types::{FromSql, ToSql, Type};
+        let value = run("19.99m").unwrap();
+        let mut bytes = bytes::BytesMut::new();
+        value.to_sql_checked(&Type::NUMERIC, &mut bytes).unwrap();
+        assert_eq!(Value::from_sql(&Type::NUMERIC, &bytes).unwrap(), value);
+        assert!(value.to_sql_checked(&Type::BOOL, &mut bytes::BytesMut::new()).is_err());
+    }
+
+    #[test]
+    fn lambda_in_variable() {
+        assert_eq!(
+            run(r#"let double = fn(x: i64) -> i64: x * 2
+double(21)"#),
+            Ok(Value::I64(42))
+        );
+    }
+
+    #[test]
+    fn lambda_return_type_is_inferred() {
+        assert_eq!(
+            run(r#"let greet = fn(name: string): "Hello " + name
+greet("world")"#),
+            Ok(string("Hello world"))
+        );
+    }
+
+    #[test]
+    fn lambda_wrong_return_type() {
+        assert_eq!(
+            run(r#"let f = fn(x: string) -> i64: x + "!""#).map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 1, Expected i64, found string".to_string())
+        );
+    }
+
+    #[test]
+    fn lambda_wrong_number_of_arguments() {
+        assert!(run(r#"let double = fn(x: i64) -> i64: x * 2
+double(1, 2)"#).is_err());
+    }
+
+    #[test]
+    fn function_type_parameter() {
+        assert_eq!(
+            run(r#"fn apply(f: fn(i64) -> i64, x: i64) -> i64:
+    f(x)
+apply(fn(x: i64) -> i64: x + 1, 41)"#),
+            Ok(Value::I64(42))
+        );
+    }
+
+    #[test]
+    fn function_type_argument_is_checked() {
+        let apply = "fn apply(f: fn(string) -> string, v: string) -> string:\n    f(v)\n";
+        let error = |call: &str| {
+            run(&format!("{}{}", apply, call)).map_err(|e| e.to_string()).unwrap_err()
+        };
+        let expected = |found: &str| {
+            format!(
+                "Compilation failed: error at line 3, Expected fn(string) -> string, found {}",
+                found
+            )
+        };
+        assert_eq!(error(r#"apply(fn(x: i64) -> i64: x + 1, "a")"#), expected("fn(i64) -> i64"));
+        assert_eq!(
+            error(r#"apply(f: fn(x: i64) -> i64: x + 1, v: "a")"#),
+            expected("fn(i64) -> i64")
+        );
+        assert_eq!(error(r#"apply(1, "a")"#), expected("integer"));
+        assert_eq!(
+            run(&format!("{}{}", apply, r#"apply(fn(x: string) -> string: x + "!", "a")"#)),
+            Ok(string("a!"))
+        );
+        // and for function values
+        assert!(run(r#"let apply = fn(f: fn(i64) -> i64) -> i64: f(1)
+apply(fn(x: string) -> string: x)"#).is_err());
+    }
+
+    #[test]
+    fn named_function_as_value() {
+        assert_eq!(
+            run(r#"fn double(x: i64) -> i64:
+    x * 2
+fn apply(f: fn(i64) -> i64, x: i64) -> i64:
+    f(x)
+apply(double, 21)"#),
+            Ok(Value::I64(42))
+        );
+    }
+
+    #[test]
+    fn named_function_in_variable() {
+        assert_eq!(
+            run(r#"fn double(x: i64) -> i64:
+    x * 2
+let f = double
+f(4)"#),
+            Ok(Value::I64(8))
+        );
+    }
+
+    #[test]
+    fn lambda_is_returned_directly() {
+        assert_eq!(
+            run(r#"fn make_adder(n: i64) -> fn(i64) -> i64:
+    fn(x: i64) -> i64: x + n
+let add = make_adder(5)
+add(1)"#),
+            Ok(Value::I64(6))
+        );
+    }
+
+    #[test]
+    fn calls_chain() {
+        assert_eq!(
+            run(r#"fn make_adder(n: i64) -> fn(i64) -> i64:
+    let add = fn(x: i64) -> i64: x + n
+    add
+make_adder(5)(1)"#),
+            Ok(Value::I64(6))
+        );
+        assert_eq!(
+            run(r#"let fs = [fn(x: i64) -> i64: x * 2, fn(x: i64) -> i64: x + 1]
+let x = 20
+fs[0](x) + fs[1](x)"#),
+            Ok(Value::I64(61))
+        );
+        assert_eq!(
+            run("let fs = [fn(x: i64) -> i64: x * 2]\nfs[0](1, 2)").map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 2, The number of of arguments for fn(i64) -> i64 \
+                 is not correct. Should be 1, got 2"
+                .to_string())
+        );
+        assert_eq!(
+            run("[1][0](2)").map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 1, Unexpected type i64".to_string())
+        );
+    }
+
+    #[test]
+    fn lambda_parameter_does_not_leak() {
+        assert_eq!(
+            run(r#"let name = "a"
+let f = fn(name: i64) -> i64: name * 2
+let s:string = name
+s"#),
+            Ok(string("a"))
+        );
+    }
+
+    #[test]
+    fn parameter_is_scoped_to_its_function() {
+        assert_eq!(
+            run(r#"fn apply(f: fn(i64) -> i64) -> i64:
+    f(3)
+fn other(f: i64) -> i64:
+    f + 1
+apply(fn(x: i64) -> i64: x * 2) + other(1)"#),
+            Ok(Value::I64(8))
+        );
+    }
+
+    #[test]
+    fn declared_function_type() {
+        assert_eq!(
+            run(r#"let f: fn(i64, i64) -> i64 = fn(a: i64, b: i64) -> i64: a * b
+f(6, 7)"#),
+            Ok(Value::I64(42))
+        );
     }
 
     #[test]
-    fn call_fn_with_args_returns_value() {
+    fn declared_function_type_mismatch() {
+        assert!(run(r#"let f: fn(i64) -> i64 = fn(a: string) -> string: a"#).is_err());
+    }
+
+    #[test]
+    fn closure_captures_local() {
         assert_eq!(
-            run(r#"fn add_hello(name: string) -> string:
-    "Hello " + name
-add_hello("world")"#),
-            Ok(string("Hello world"))
+            run(r#"let factor = 3
+let times = fn(x: i64) -> i64: x * factor
+times(14)"#),
+            Ok(Value::I64(42))
         );
     }
 
     #[test]
-    fn define_object() {
-        let r = compile(
-            r#"
-object Person:
-   name: string"#,
+    fn closure_captures_value_at_creation() {
+        assert_eq!(
+            run(r#"let factor = 3
+let times = fn(x: i64) -> i64: x * factor
+factor = 10
+times(14)"#),
+            Ok(Value::I64(42))
         );
-        assert!(r.is_ok()); // does nothing runtime
     }
 
     #[test]
-    fn declare_and_instantiate_object() {
-        let r = run(r#"
-object Person:
-   name: string
+    fn redeclared_variable_reads_the_earlier_one() {
+        assert_eq!(
+            run(r#"let x = 20
+let x = x * 2 + 2
+x"#),
+            Ok(Value::I64(42))
+        );
+    }
 
-let p = Person(name: "Sander")
-p"#);
-        assert!(r.is_ok());
+    #[test]
+    fn nested_closures() {
         assert_eq!(
-            r#"Person: [("name", String("Sander"))]"#,
-            format!("{}", r.unwrap().to_string())
+            run(r#"let a = 40
+let outer = fn(b: i64) -> fn(i64) -> i64: fn(c: i64) -> i64: a + b + c
+let inner = outer(1)
+inner(1)"#),
+            Ok(Value::I64(42))
         );
     }
 
     #[test]
-    fn declare_and_instantiate_object_wrong_type() {
-        let r = run(r#"
-object Person:
-   name: string
+    fn function_calls_function() {
+        assert_eq!(
+            run(r#"fn double(x: i64) -> i64:
+    x * 2
+fn quadruple(x: i64) -> i64:
+    double(double(x))
+quadruple(10)"#),
+            Ok(Value::I64(40))
+        );
+    }
 
-let p = Person(name: 0x42)
-p"#);
-        // assert!(r.is_err());
+    #[test]
+    fn interpolated_string() {
         assert_eq!(
-            r#"Compilation failed: error at line 5, Expected string, found integer"#,
-            format!("{}", r.unwrap_err().to_string())
+            run(r#"let name = "Bob"
+let age = 42
+f"hello {name}, you are {age} years old""#),
+            Ok(string("hello Bob, you are 42 years old"))
         );
     }
 
     #[test]
-    fn literal_map() {
-        let result = run(r#"{"name": "Dent", "age": 40 }"#);
-        assert!(result.is_ok());
-        let result = result.unwrap();
-        if let Value::Map(map) = result {
-            assert_eq!(map.get(&string("name")).unwrap(), &string("Dent"));
-            assert_eq!(map.get(&string("age")).unwrap(), &Value::I64(40));
-        }
+    fn interpolated_expressions() {
+        assert_eq!(
+            run(r#"let xs = [1, 2, 3]
+f"{xs.len()} items, first {xs[0] + 1}, {{braces}}""#),
+            Ok(string("3 items, first 2, {braces}"))
+        );
     }
 
     #[test]
-    fn assign_map() {
-        let result = run(r#"let m = {"name": "Dent"}
-m"#);
+    fn interpolated_formats_any_value() {
+        assert_eq!(
+            run(r#"f"{19.99m} on {d"2025-11-09"} is {true}""#),
+            Ok(string("19.99 on 2025-11-09 is true"))
+        );
+    }
 
-        let result = result.unwrap();
-        if let Value::Map(map) = result {
-            assert_eq!(map.get(&string("name")).unwrap(), &string("Dent"));
-        }
+    #[test]
+    fn interpolated_undeclared_variable() {
+        assert_eq!(
+            run(r#"f"hello {nobody}""#).map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 1, Undeclared variable: 'nobody'".to_string())
+        );
     }
 
     #[test]
-    fn access_map() {
-        let result = run(r#"let m = {"name": "Dent"}
-m["name"]"#);
+    fn interpolated_unterminated() {
+        assert!(run(r#"f"hello {name""#).is_err());
+    }
 
-        let result = result.unwrap();
-        if let Value::String(v) = result {
-            assert_eq!(v.as_str(), "Dent");
-        }
+    #[test]
+    fn string_escapes() {
+        assert_eq!(
+            run(r#""a\tb\n\"c\" \\ \u{1F600}""#),
+            Ok(string("a\tb\n\"c\" \\ \u{1F600}"))
+        );
     }
 
     #[test]
-    fn keyword_error() {
-        let result = run(r#"let map = {"name": "Dent"}"#);
-        assert!(result.is_err());
+    fn interpolated_escapes() {
         assert_eq!(
-            "Compilation failed: error at line 1, 'map' is a keyword. You cannot use it as an identifier",
-            result.unwrap_err().to_string()
+            run(r#"let x = 1
+f"\"{x}\"\n""#),
+            Ok(string("\"1\"\n"))
         );
     }
 
     #[test]
-    fn add_strings() {
-        assert_eq!(run(r#""a"+"b""#), Ok(string("ab")));
+    fn char_escape() {
+        assert_eq!(run(r#"'\n'"#), Ok(Value::Char('\n')));
     }
 
     #[test]
-    fn add_string_and_int() {
-        assert_eq!(run(r#""a"+42"#), Ok(string("a42")));
+    fn illegal_escape() {
+        assert_eq!(
+            run(r#""a\qb""#).map_err(|e| e.to_string()),
+            Err("Compilation failed: error at line 1, Illegal escape sequence \\q".to_string())
+        );
     }
 
     #[test]
-    fn add_string_and_bool() {
-        assert_eq!(run(r#""a"+false"#), Ok(string("afalse")));
+    fn illegal_unicode_escape() {
+        assert!(run(r#""\u{110000}""#).is_err());
+        assert!(run(r#""\u0041""#).is_err());
     }
 
     #[test]
-    fn add_string_and_scientific_float() {
-        assert_eq!(
-            run(r#""a"+4.2e10"#),
-            Ok(Value::String("a42000000000".into()))
-        );
+    fn raw_string() {
+        assert_eq!(run(r#"r"C:\temp\new""#), Ok(string("C:\\temp\\new")));
     }
 
     #[test]
-    fn add_hex_ints() {
-        assert_eq!(run(r#"0x10 + 0x20"#), Ok(Value::U32(48)));
+    fn multiline_string() {
+        assert_eq!(
+            run(r#"let text = """
+    select *
+      from customers
+    where id = \"1\"
+    """
+text"#),
+            Ok(string("select *\n  from customers\nwhere id = \"1\""))
+        );
     }
 
     #[test]
-    fn date_literal() {
+    fn multiline_string_with_quotes() {
         assert_eq!(
-            run(r#"let date:datetime = d"2025-11-09 16:44:28.000 +0100"
-date"#),
-            Ok(Value::DateTime(Box::new(
-                DateTime::parse_from_str(
-                    "2025-11-09 16:44:28.000 +0100", DATE_FORMAT_TIMEZONE
-                )
-                .unwrap()
-                .into()
-            )))
+            run(r#""""say "hi\"""""#),
+            Ok(string("say \"hi\""))
         );
     }
 
+    #[test]
+    fn multiline_string_unterminated() {
+        assert!(run(r#""""never ends"#).is_err());
+    }
+
     #[test]
     fn string_reverse() {
         assert_eq!(run(r#""abc".reverse()"#), Ok(string("cba")));
@@ -276,7 +2395,8 @@ date"#),
             run(r#""Hello".replace_all("l")"#),
             Err(Compiler(CompilerErrorAtLine {
                 error: IllegalArgumentsException("string.replace_all".to_string(), 2, 1),
-                line: 1
+                line: 1,
+                span: Some(Span { start: 0, end: 24, line: 1, column: 1 })
             }))
         );
     }
@@ -296,6 +2416,120 @@ date"#),
         assert_eq!(run(r#""Hello".contains("l")"#), Ok(Value::Bool(true)));
     }
 
+    #[test]
+    fn string_split_and_join() {
+        assert_eq!(
+            run(r#""a,b,c".split(",").join(" - ")"#),
+            Ok(string("a - b - c"))
+        );
+    }
+
+    #[test]
+    fn string_split_is_typed() {
+        assert_eq!(
+            run(r#"let parts: list<string> = "a,b".split(",")
+parts.map(fn(s: string) -> string: s.to_uppercase())"#),
+            Ok(Value::List(vec![string("A"), string("B")].into()))
+        );
+    }
+
+    #[test]
+    fn string_starts_and_ends_with() {
+        assert_eq!(
+            run(r#""tipi-lang".starts_with("tipi") && "tipi-lang".ends_with("lang")"#),
+            Ok(Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn string_find() {
+        assert_eq!(run(r#""héllo".find("llo")"#), Ok(Value::I64(2)));
+        assert_eq!(run(r#""hello".find("x")"#), Ok(Value::I64(-1)));
+    }
+
+    #[test]
+    fn string_substring_by_char_index() {
+        assert_eq!(run(r#""héllo".substring(1, 3)"#), Ok(string("él")));
+    }
+
+    #[test]
+    fn string_substring_out_of_bounds() {
+        assert_eq!(run(r#""hello".substring(2, 10)"#), Err(Runtime(IndexOutOfBounds(10, 5))));
+    }
+
+    #[test]
+    fn string_chars_and_lines() {
+        assert_eq!(
+            run(r#""ab".chars()"#),
+            Ok(Value::List(vec![Value::Char('a'), Value::Char('b')].into()))
+        );
+        assert_eq!(
+            run(r#""a\nb".lines()"#),
+            Ok(Value::List(vec![string("a"), string("b")].into()))
+        );
+    }
+
+    #[test]
+    fn string_pad() {
+        assert_eq!(run(r#""42".pad_start(5, "0")"#), Ok(string("00042")));
+        assert_eq!(run(r#""ab".pad_end(5, "-=")"#), Ok(string("ab-=-")));
+        assert_eq!(run(r#""hello".pad_start(2, " ")"#), Ok(string("hello")));
+        assert_eq!(
+            run(r#""a".pad_end(9223372036854775807, " ")"#),
+            Err(Runtime(IllegalArgumentException(
+                "The string would be longer than 1073741824 bytes".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn string_repeat() {
+        assert_eq!(run(r#""ab".repeat(3)"#), Ok(string("ababab")));
+        assert_eq!(
+            run(r#""ab".repeat(9223372036854775807)"#),
+            Err(Runtime(IllegalArgumentException(
+                "The string would be longer than 1073741824 bytes".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn invalid_regex() {
+        let error = run(r#""a".matches("(")"#).unwrap_err().to_string();
+        assert!(error.starts_with("Invalid regex: regex parse error"), "{}", error);
+        assert!(error.ends_with("unclosed group"), "{}", error);
+    }
+
+    #[test]
+    fn string_parse() {
+        assert_eq!(run(r#""42".parse_int() + 1"#), Ok(Value::I64(43)));
+        assert_eq!(run(r#""2.5".parse_float()"#), Ok(Value::F64(2.5)));
+    }
+
+    #[test]
+    fn string_parse_error() {
+        assert_eq!(
+            run(r#""4x2".parse_int()"#),
+            Err(Runtime(CannotParse("4x2".to_string(), "i64")))
+        );
+    }
+
+    #[test]
+    fn string_regex() {
+        assert_eq!(run(r#""2025-11-09".matches(r"^\d{4}-\d{2}-\d{2}$")"#), Ok(Value::Bool(true)));
+        assert_eq!(
+            run(r#""order 42 of 7".captures(r"(\d+) of (\d+)")"#),
+            Ok(Value::List(vec![string("42 of 7"), string("42"), string("7")].into()))
+        );
+        assert_eq!(run(r#""none".captures(r"\d")"#), Ok(Value::List(vec![].into())));
+    }
+
+    #[test]
+    fn string_url_encoding() {
+        assert_eq!(run(r#""a b&c=é".url_encode()"#), Ok(string("a%20b%26c%3D%C3%A9")));
+        assert_eq!(run(r#""a%20b%26c%3D%C3%A9".url_decode()"#), Ok(string("a b&c=é")));
+    }
+
     #[test]
     fn list_length() {
         assert_eq!(run(r#"[1,2,3].len()"#), Ok(Value::U64(3)));
@@ -309,7 +2543,43 @@ date"#),
                 Value::I64(1),
                 Value::I64(2),
                 Value::I64(3)
-            ]))
+            ].into()))
+        );
+    }
+
+    #[test]
+    fn reassigned_list_is_moved() {
+        let src = r#"let squares = []
+for i in 1..=4:
+    squares = squares.push(i * i)
+squares"#;
+        let expected = Value::List([1, 4, 9, 16].map(Value::I64).to_vec().into());
+        assert_eq!(run(src), Ok(expected));
+        assert!(compile(src).unwrap()["main"].code.contains(&Op::Take(0)));
+    }
+
+    #[test]
+    fn list_read_in_arguments_is_not_moved() {
+        let src = r#"let xs = [1]
+xs = xs.push(xs[0])
+xs[xs[0]] = xs[0] + 4
+xs"#;
+        assert_eq!(run(src), Ok(Value::List(vec![Value::I64(1), Value::I64(5)].into())));
+        assert!(!compile(src).unwrap()["main"].code.contains(&Op::Take(0)));
+    }
+
+    #[test]
+    fn changing_a_copy_leaves_the_original() {
+        assert_eq!(
+            run(r#"let a = [1, 2]
+let b = a
+b = b.push(3)
+b[0] = 10
+let m = {"x": 1}
+let n = m
+n["x"] = 2
+[a[0], a[1], b[0], b[2], m["x"], n["x"]]"#),
+            Ok(Value::List([1, 2, 10, 3, 1, 2].map(Value::I64).to_vec().into()))
         );
     }
 
@@ -317,7 +2587,7 @@ date"#),
     fn list_remove() {
         assert_eq!(
             run(r#"[1,2,3].remove(0)"#),
-            Ok(Value::List(vec![Value::I64(2), Value::I64(3)]))
+            Ok(Value::List(vec![Value::I64(2), Value::I64(3)].into()))
         );
     }
 
@@ -329,6 +2599,136 @@ date"#),
         );
     }
 
+    #[test]
+    fn list_map() {
+        assert_eq!(
+            run(r#"let doubled: list<i64> = [1, 2, 3].map(fn(x: i64) -> i64: x * 2)
+doubled"#),
+            Ok(Value::List(vec![Value::I64(2), Value::I64(4), Value::I64(6)].into()))
+        );
+    }
+
+    #[test]
+    fn list_map_wrong_result_type() {
+        assert!(run(r#"let names: list<string> = [1, 2, 3].map(fn(x: i64) -> i64: x * 2)"#).is_err());
+    }
+
+    #[test]
+    fn list_functions_are_type_checked() {
+        let error = |src: &str| run(src).map_err(|e| e.to_string()).unwrap_err();
+        assert_eq!(
+            error("[1, 2].filter(fn(x: string) -> bool: true)"),
+            "Compilation failed: error at line 1, Expected fn(i64) -> bool, found fn(string) -> bool"
+        );
+        assert_eq!(
+            error("[1, 2].any(fn(x: i64) -> i64: x)"),
+            "Compilation failed: error at line 1, Expected fn(i64) -> bool, found fn(i64) -> i64"
+        );
+        assert_eq!(
+            error("[1, 2].map(fn(x: i64, y: i64) -> i64: x)"),
+            "Compilation failed: error at line 1, Expected fn(i64) -> i64, found fn(i64, i64) -> i64"
+        );
+        assert!(run("[1, 2].reduce(0, fn(sum: i64, x: string) -> i64: sum)").is_err());
+        // the elements of a mixed list can be anything
+        assert!(run(r#"[1, "a"].map(fn(x: i64) -> i64: x)"#).is_ok());
+    }
+
+    #[test]
+    fn list_filter() {
+        assert_eq!(
+            run(r#"[1, 2, 3, 4].filter(fn(x: i64): x > 2)"#),
+            Ok(Value::List(vec![Value::I64(3), Value::I64(4)].into()))
+        );
+    }
+
+    #[test]
+    fn list_reduce() {
+        assert_eq!(
+            run(r#"[1, 2, 3, 4].reduce(0, fn(sum: i64, x: i64) -> i64: sum + x)"#),
+            Ok(Value::I64(10))
+        );
+    }
+
+    #[test]
+    fn list_any_all() {
+        assert_eq!(run(r#"[1, 2, 3].any(fn(x: i64): x > 2)"#), Ok(Value::Bool(true)));
+        assert_eq!(run(r#"[1, 2, 3].all(fn(x: i64): x > 2)"#), Ok(Value::Bool(false)));
+    }
+
+    #[test]
+    fn list_find() {
+        assert_eq!(run(r#"[1, 2, 3].find(fn(x: i64): x > 1)"#), Ok(Value::I64(2)));
+        assert_eq!(
+            run(r#"[1, 2, 3].find(fn(x: i64): x > 5)"#),
+            Err(Runtime(crate::errors::RuntimeError::NoMatch))
+        );
+    }
+
+    #[test]
+    fn list_sort_by() {
+        assert_eq!(
+            run(r#"["ccc", "a", "bb"].sort_by(fn(s: string): s.len())"#),
+            Ok(Value::List(vec![string("a"), string("bb"), string("ccc")].into()))
+        );
+        assert_eq!(
+            run(r#"[0.0, 0.0].sort_by(fn(x: f64): 1.0 / x - 1.0 / x)"#),
+            Err(Runtime(crate::errors::RuntimeError::Incomparable("NaN".into(), "NaN".into())))
+        );
+    }
+
+    #[test]
+    fn list_group_by() {
+        assert_eq!(
+            run(r#"let groups = ["apple", "banana", "mango"].group_by(fn(s: string): s.contains("an"))
+groups[true]"#),
+            Ok(Value::List(vec![string("banana"), string("mango")].into()))
+        );
+    }
+
+    #[test]
+    fn list_flat_map() {
+        assert_eq!(
+            run(r#"[1, 2].flat_map(fn(x: i64): [x, x * 10])"#),
+            Ok(Value::List([1, 10, 2, 20].map(Value::I64).to_vec().into()))
+        );
+    }
+
+    #[test]
+    fn list_zip_and_enumerate() {
+        assert_eq!(
+            run(r#"[1, 2].zip(["a", "b"])"#),
+            Ok(Value::List(vec![
+                Value::List(vec![Value::I64(1), string("a")].into()),
+                Value::List(vec![Value::I64(2), string("b")].into()),
+            ].into()))
+        );
+        assert_eq!(
+            run(r#"["a"].enumerate()"#),
+            Ok(Value::List(vec![Value::List(vec![Value::I64(0), string("a")].into())].into()))
+        );
+    }
+
+    #[test]
+    fn list_method_chain() {
+        assert_eq!(
+            run(r#"let big = [1, 2, 3, 4].filter(fn(x: i64): x > 1).map(fn(x: i64) -> i64: x * 10)
+let total = big.reduce(0, fn(sum: i64, x: i64) -> i64: sum + x)
+total"#),
+            Ok(Value::I64(90))
+        );
+    }
+
+    #[test]
+    fn list_map_with_closure_and_named_function() {
+        assert_eq!(
+            run(r#"fn double(x: i64) -> i64:
+    x * 2
+let offset = 1
+[1, 2].map(double).map(fn(x: i64) -> i64: x + offset)"#),
+            Ok(Value::List(vec![Value::I64(3), Value::I64(5)].into()))
+        );
+    }
+
     #[test]
     fn reassign() {
         assert_eq!(
@@ -400,7 +2800,7 @@ else:
         assert_eq!(
             run(r#"
 let sum=0
-for a in 1..4:
+for a in 1..=4:
     sum = sum + a
 sum
 "#),
@@ -408,6 +2808,25 @@ sum
         );
     }
 
+    #[test]
+    fn range_loop_excludes_upper_bound() {
+        let src = |range: &str| {
+            format!("let xs = []\nfor i in {}:\n    xs = xs.push(i)\nxs", range)
+        };
+        let list = |values: &[i64]| {
+            Ok(Value::List(values.iter().copied().map(Value::I64).collect::<Vec<_>>().into()))
+        };
+        assert_eq!(run(&src("0..2")), list(&[0, 1]));
+        assert_eq!(run(&src("0..=2")), list(&[0, 1, 2]));
+        assert_eq!(run(&src("2..2")), list(&[]));
+        assert_eq!(run(&src("5..2")), list(&[]));
+        let bound_by_variable = "let n = 3\nlet xs = []\nfor i in 0..n:\n    xs = xs.push(i)\nxs";
+        assert_eq!(run(bound_by_variable), list(&[0, 1, 2]));
+        // slices follow the same rules
+        assert_eq!(run("[0, 1, 2][0..2]"), list(&[0, 1]));
+        assert_eq!(run("[0, 1, 2][0..=2]"), list(&[0, 1, 2]));
+    }
+
     #[test]
     fn global_function_call() {
         let value = run(r#"now()"#);
@@ -420,7 +2839,14 @@ sum
     #[test]
     fn global_fns_are_not_allowed() {
         let value = run(r#"fn now():"#);
-        assert_eq!(value, Err(Compiler(CompilerErrorAtLine { error: ReservedFunctionName("now".to_string()), line: 1 })));
+        assert_eq!(
+            value,
+            Err(Compiler(CompilerErrorAtLine {
+                error: ReservedFunctionName("now".to_string()),
+                line: 1,
+                span: Some(Span { start: 3, end: 6, line: 1, column: 4 })
+            }))
+        );
     }
 
     // #[test]
@@ -0,0 +1,72 @@
+use crate::compiler::assembly_pass::{AsmChunk, Op};
+use crate::value::Value;
+
+/// lists the ops of a chunk, one per line, like:
+/// ```text
+/// == main ==
+/// 0000    1 Constant(0)          42
+/// 0001    | Assign(0)            a: i64
+/// ```
+/// the source line is a | when it is the same as that of the previous op
+pub fn disassemble(name: &str, chunk: &AsmChunk) -> String {
+    let mut listing = format!("== {} ==\n", name);
+    for (ip, op) in chunk.code.iter().enumerate() {
+        let line = chunk.line(ip);
+        let line = if ip > 0 && chunk.line(ip - 1) == line {
+            "|".to_string()
+        } else {
+            line.to_string()
+        };
+        let row = format!(
+            "{:04} {:>4} {:<20} {}",
+            ip,
+            line,
+            format!("{:?}", op),
+            operands(chunk, op)
+        );
+        listing.push_str(row.trim_end());
+        listing.push('\n');
+    }
+    listing
+}
+
+// what the indexes in an op refer to
+fn operands(chunk: &AsmChunk, op: &Op) -> String {
+    match op {
+        Op::Constant(index) => constant(chunk, *index),
+        Op::Get(index) | Op::Take(index) | Op::Assign(index) => match chunk.vars.get(*index) {
+            Some((var_type, name)) => format!("{}: {}", name, var_type),
+            None => "?".to_string(),
+        },
+        Op::Call(name, args) => format!("{} ({} args)", name_of(chunk, *name), args),
+        Op::CallLinked(link, _) => match chunk.links.get(*link) {
+            Some((call, _)) => operands(chunk, call),
+            None => "?".to_string(),
+        },
+        Op::CallBuiltin(name, receiver_type, args) => format!(
+            "{}.{} ({} args)",
+            name_of(chunk, *receiver_type),
+            name_of(chunk, *name),
+            args
+        ),
+        Op::FieldGet(name) | Op::FieldSet(name) => name_of(chunk, *name),
+        Op::Closure(name, captured) => format!("{} ({} captured)", name_of(chunk, *name), captured),
+        Op::Goto(addr) | Op::GotoIf(addr) | Op::GotoIfNot(addr) => format!("-> {:04}", addr),
+        _ => String::new(),
+    }
+}
+
+fn constant(chunk: &AsmChunk, index: usize) -> String {
+    match chunk.constants.get(index) {
+        Some(Value::String(s)) => format!("{:?}", s),
+        Some(value) => value.to_string(),
+        None => "?".to_string(),
+    }
+}
+
+fn name_of(chunk: &AsmChunk, index: usize) -> String {
+    chunk
+        .constants
+        .get(index)
+        .map_or("?".to_string(), |name| name.to_string())
+}
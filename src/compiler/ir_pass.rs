@@ -0,0 +1,830 @@
+use crate::SymbolTable;
+use crate::builtins::globals::GLOBAL_FUNCTIONS;
+use crate::builtins::lookup;
+use crate::compiler::assembly_pass::Op;
+use crate::compiler::ast_pass::{Expression, Function, Parameter, Statement};
+use crate::compiler::tokens::TokenType::Unknown;
+use crate::compiler::tokens::{Span, Token, TokenType};
+use crate::errors::CompilerError::{
+    FunctionNotFound, IllegalArgumentsException, IllegalAssignmentTarget, IllegalTypeToIterate,
+    IncompatibleTypes, UndeclaredVariable, UnexpectedType,
+};
+use crate::errors::{CompilerError, CompilerErrorAtLine};
+use crate::symbol_builder::{
+    Symbol, calculate_element_type, calculate_type, infer_type, literal_operand_type,
+};
+use crate::value::Value;
+use std::collections::HashMap;
+
+/// a function, or the top level code of a source file, with its variables resolved to slots
+#[derive(Debug, Clone)]
+pub struct IrFunction {
+    pub name: String,
+    pub parameters: Vec<Parameter>,
+    /// type and name of every variable: parameters, lets, loop variables and captured variables
+    pub locals: Vec<(TokenType, String)>,
+    /// slots of the enclosing variables a lambda captures, in the order of the captured values
+    pub captures: Vec<usize>,
+    pub body: Vec<IrStatement>,
+}
+
+#[derive(Debug, Clone)]
+pub struct IrStatement {
+    pub line: usize,
+    pub kind: IrStatementKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum IrStatementKind {
+    Let {
+        slot: usize,
+        value: IrExpression,
+    },
+    Print(IrExpression),
+    Expression(IrExpression),
+    Function(IrFunction),
+    Object {
+        name: String,
+        fields: Vec<Parameter>,
+    },
+    If {
+        condition: IrExpression,
+        then_branch: Vec<IrStatement>,
+        else_branch: Option<Vec<IrStatement>>,
+    },
+    RangeLoop {
+        slot: usize,
+        // hidden variable for the upper bound, evaluated once
+        end_slot: usize,
+        inclusive: bool,
+        range: IrExpression,
+        body: Vec<IrStatement>,
+    },
+    ListLoop {
+        slot: usize,
+        // hidden variables for the list and the current position in it
+        list_slot: usize,
+        index_slot: usize,
+        list: IrExpression,
+        body: Vec<IrStatement>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct IrExpression {
+    pub kind: IrExpressionKind,
+    pub ir_type: TokenType,
+    pub line: usize,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum IrExpressionKind {
+    Constant(Value),
+    Get(usize),
+    /// moves the value out of a variable that is assigned again right after, see optimize_pass
+    Take(usize),
+    Assign(usize, Box<IrExpression>),
+    /// stores the value in a list element, map entry or object field, and then the updated
+    /// collection in its own container, up to the variable. The keys are indexes and map keys
+    /// of the target that are computed once, into their slot, before the value
+    Store {
+        keys: Vec<(usize, IrExpression)>,
+        target: Box<IrExpression>,
+        value: Box<IrExpression>,
+    },
+    /// the qualified name of a named function used as a value
+    FunctionRef(String),
+    Unary(Op, Box<IrExpression>),
+    Binary(Op, Box<IrExpression>, Box<IrExpression>),
+    Cast(Box<IrExpression>, TokenType),
+    List(Vec<IrExpression>),
+    Map(Vec<(IrExpression, IrExpression)>),
+    Range(Box<IrExpression>, Box<IrExpression>),
+    ListGet(Box<IrExpression>, Box<IrExpression>),
+    MapGet(Box<IrExpression>, Box<IrExpression>),
+    FieldGet(Box<IrExpression>, String),
+    Slice {
+        operand: Box<IrExpression>,
+        lower: Option<Box<IrExpression>>,
+        upper: Option<Box<IrExpression>>,
+        inclusive: bool,
+    },
+    /// calls a function, an object constructor or a global function by name,
+    /// with the arguments in the order of the parameters
+    Call(String, Vec<IrExpression>),
+    /// calls a function value
+    CallValue(Box<IrExpression>, Vec<IrExpression>),
+    /// calls the builtin method of the (erased) receiver type
+    MethodCall {
+        receiver: Box<IrExpression>,
+        receiver_type: String,
+        method: String,
+        arguments: Vec<IrExpression>,
+    },
+    /// creates a function value from a lambda, with the values of the variables it captures
+    Closure {
+        namespace: String,
+        function: Box<IrFunction>,
+        captured: Vec<IrExpression>,
+    },
+    Interpolation(Vec<IrExpression>),
+    Stop,
+}
+
+/// lowers the ast of a source file into a typed ir, resolving variables and calls
+pub fn lower(
+    name: &str,
+    ast: &[Statement],
+    symbols: &SymbolTable,
+) -> Result<IrFunction, CompilerErrorAtLine> {
+    IrPass::new(name).lower(name, ast, symbols)
+}
+
+pub struct IrPass {
+    namespace: String,
+    current_line: usize,
+    current_span: Span,
+    locals: Vec<(TokenType, String)>,
+    vars: HashMap<String, usize>,
+    /// variables of the enclosing scopes that a lambda can capture
+    enclosing: HashMap<String, TokenType>,
+    captures: Vec<usize>,
+}
+
+impl IrPass {
+    pub fn new(namespace: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            current_line: 0,
+            current_span: Span::default(),
+            locals: vec![],
+            vars: HashMap::new(),
+            enclosing: HashMap::new(),
+            captures: vec![],
+        }
+    }
+
+    /// lowers top level code. The variables are kept, so that the REPL can lower line by line
+    pub fn lower(
+        &mut self,
+        name: &str,
+        ast: &[Statement],
+        symbols: &SymbolTable,
+    ) -> Result<IrFunction, CompilerErrorAtLine> {
+        let body = self.statements(ast, symbols)?;
+        Ok(IrFunction {
+            name: name.to_string(),
+            parameters: vec![],
+            locals: self.locals.clone(),
+            captures: self.captures.clone(),
+            body,
+        })
+    }
+
+    fn lower_function(
+        mut self,
+        function: &Function,
+        symbols: &SymbolTable,
+    ) -> Result<IrFunction, CompilerErrorAtLine> {
+        // the parameters are only in the symbol table while the body is lowered
+        let mut symbols = symbols.clone();
+        for parameter in &function.parameters {
+            self.declare(&parameter.var_type, &parameter.name.lexeme);
+            symbols.insert(
+                parameter.name.lexeme.clone(),
+                Symbol::Variable {
+                    name: parameter.name.lexeme.clone(),
+                    var_type: parameter.var_type.clone(),
+                },
+            );
+        }
+        let body = self.statements(&function.body, &symbols)?;
+        Ok(IrFunction {
+            name: function.name.lexeme.clone(),
+            parameters: function.parameters.to_vec(),
+            locals: self.locals,
+            captures: self.captures,
+            body,
+        })
+    }
+
+    fn statements(
+        &mut self,
+        ast: &[Statement],
+        symbols: &SymbolTable,
+    ) -> Result<Vec<IrStatement>, CompilerErrorAtLine> {
+        ast.iter().map(|s| self.statement(s, symbols)).collect()
+    }
+
+    fn statement(
+        &mut self,
+        statement: &Statement,
+        symbols: &SymbolTable,
+    ) -> Result<IrStatement, CompilerErrorAtLine> {
+        let line = statement.line();
+        self.current_line = line;
+        self.current_span = statement.span();
+        let kind = match statement {
+            Statement::VarStmt {
+                name, initializer, ..
+            } => {
+                let name = name.lexeme.as_str();
+                let Some(Symbol::Variable { var_type, .. }) = symbols.get(name) else {
+                    return Err(self.raise(UndeclaredVariable(name.to_string())));
+                };
+                let inferred_type = self.type_of(initializer, symbols)?;
+                let calculated_type =
+                    calculate_type(var_type, &inferred_type).map_err(|e| self.raise(e))?;
+                if var_type != &Unknown && var_type != &calculated_type {
+                    return Err(self.raise(IncompatibleTypes(var_type.clone(), calculated_type)));
+                }
+                // the initializer can still read an earlier variable with the same name
+                let value = converted(self.expression(initializer, symbols)?, var_type);
+                let slot = self.declare(var_type, name);
+                IrStatementKind::Let { slot, value }
+            }
+            Statement::PrintStmt { value } => IrStatementKind::Print(self.expression(value, symbols)?),
+            Statement::ExpressionStmt { expression } => {
+                IrStatementKind::Expression(self.expression(expression, symbols)?)
+            }
+            Statement::FunctionStmt { function } => IrStatementKind::Function(
+                IrPass::new(&self.namespace).lower_function(function, symbols)?,
+            ),
+            Statement::ObjectStmt { name, fields } => IrStatementKind::Object {
+                name: name.lexeme.clone(),
+                fields: fields.to_vec(),
+            },
+            Statement::GuardStatement { .. } => {
+                unimplemented!("guard statement")
+            }
+            Statement::IfStatement {
+                condition,
+                then_branch,
+                else_branch,
+            } => IrStatementKind::If {
+                condition: self.expression(condition, symbols)?,
+                then_branch: self.statements(then_branch, symbols)?,
+                else_branch: else_branch
+                    .as_ref()
+                    .map(|branch| self.statements(branch, symbols))
+                    .transpose()?,
+            },
+            Statement::ForStatement {
+                loop_var,
+                range,
+                body,
+            } => {
+                if let Expression::Range { inclusive, .. } = range {
+                    let range = self.expression(range, symbols)?;
+                    let end_slot =
+                        self.declare(&range.ir_type, &format!("{}#end", loop_var.lexeme));
+                    let slot = self.declare_loop_var(loop_var, symbols);
+                    let body = self.statements(body, symbols)?;
+                    IrStatementKind::RangeLoop {
+                        slot,
+                        end_slot,
+                        inclusive: *inclusive,
+                        range,
+                        body,
+                    }
+                } else {
+                    let list_type = self.type_of(range, symbols)?;
+                    if !matches!(list_type, TokenType::ListType(_)) {
+                        return Err(self.raise(IllegalTypeToIterate(list_type)));
+                    }
+                    let list_slot = self.declare(&list_type, &format!("{}#list", loop_var.lexeme));
+                    let index_slot =
+                        self.declare(&TokenType::U64, &format!("{}#index", loop_var.lexeme));
+                    let slot = self.declare_loop_var(loop_var, symbols);
+                    let list = self.expression(range, symbols)?;
+                    let body = self.statements(body, symbols)?;
+                    IrStatementKind::ListLoop {
+                        slot,
+                        list_slot,
+                        index_slot,
+                        list,
+                        body,
+                    }
+                }
+            }
+        };
+        Ok(IrStatement { line, kind })
+    }
+
+    fn expression(
+        &mut self,
+        expression: &Expression,
+        symbols: &SymbolTable,
+    ) -> Result<IrExpression, CompilerErrorAtLine> {
+        let kind = match expression {
+            Expression::FunctionCall {
+                name, arguments, ..
+            } => match symbols.get(name) {
+                // calling a function value
+                Some(Symbol::Variable {
+                    var_type: TokenType::FunctionType(parameter_types, _),
+                    ..
+                }) => {
+                    if parameter_types.len() != arguments.len() {
+                        return Err(self.raise(IllegalArgumentsException(
+                            name.to_string(),
+                            parameter_types.len(),
+                            arguments.len(),
+                        )));
+                    }
+                    for (parameter_type, argument) in parameter_types.iter().zip(arguments) {
+                        self.check_function_argument(parameter_type, argument, symbols)?;
+                    }
+                    let callee = self.variable_expression(name);
+                    let function = self.expression(&callee, symbols)?;
+                    let arguments = arguments
+                        .iter()
+                        .map(|argument| self.expression(argument, symbols))
+                        .collect::<Result<_, _>>()?;
+                    IrExpressionKind::CallValue(Box::new(function), arguments)
+                }
+                Some(Symbol::Function { parameters, .. }) => IrExpressionKind::Call(
+                    name.clone(),
+                    self.arguments_in_order(arguments, parameters, symbols)?,
+                ),
+                // constructor function
+                Some(Symbol::Object { fields, .. }) => IrExpressionKind::Call(
+                    name.clone(),
+                    self.arguments_in_order(arguments, fields, symbols)?,
+                ),
+                // maybe global function
+                _ => {
+                    let Some(fun) = GLOBAL_FUNCTIONS.get(name) else {
+                        return Err(self.raise(FunctionNotFound(name.to_string())));
+                    };
+                    if fun.arity() != arguments.len() {
+                        return Err(self.raise(IllegalArgumentsException(
+                            name.to_string(),
+                            fun.arity(),
+                            arguments.len(),
+                        )));
+                    }
+                    IrExpressionKind::Call(
+                        name.clone(),
+                        self.arguments_in_order(arguments, &fun.parameters, symbols)?,
+                    )
+                }
+            },
+            Expression::ValueCall {
+                function,
+                arguments,
+                ..
+            } => {
+                let function_type = self.type_of(function, symbols)?;
+                let TokenType::FunctionType(parameter_types, _) = &function_type else {
+                    return Err(self.raise(UnexpectedType(function_type)));
+                };
+                if parameter_types.len() != arguments.len() {
+                    return Err(self.raise(IllegalArgumentsException(
+                        function_type.to_string(),
+                        parameter_types.len(),
+                        arguments.len(),
+                    )));
+                }
+                for (parameter_type, argument) in parameter_types.iter().zip(arguments) {
+                    self.check_function_argument(parameter_type, argument, symbols)?;
+                }
+                let function = self.expression(function, symbols)?;
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.expression(argument, symbols))
+                    .collect::<Result<_, _>>()?;
+                IrExpressionKind::CallValue(Box::new(function), arguments)
+            }
+            Expression::MethodCall {
+                receiver,
+                method_name,
+                arguments,
+                ..
+            } => {
+                let receiver = self.expression(receiver, symbols)?;
+                if let TokenType::ListType(element_type) = &receiver.ir_type
+                    && method_name == "push"
+                    && let Some(element) = arguments.first()
+                {
+                    // the builtin accepts any value, so check it against the element type here
+                    let argument_type = self.type_of(element, symbols)?;
+                    calculate_element_type(element_type, &argument_type).map_err(|_| {
+                        self.raise(IncompatibleTypes(*element_type.clone(), argument_type))
+                    })?;
+                }
+                // the function of a list method is checked against the elements before the
+                // builtin signature, which only knows that it takes any function
+                if let TokenType::ListType(_) = &receiver.ir_type {
+                    self.type_of(expression, symbols)?;
+                }
+                let receiver_type = receiver.ir_type.erased().to_string();
+                let signature = lookup(&receiver_type, method_name).map_err(|e| self.raise(e))?;
+                if signature.arity() != arguments.len() {
+                    return Err(self.raise(IllegalArgumentsException(
+                        format!("{}.{}", receiver_type, method_name),
+                        signature.parameters.len(),
+                        arguments.len(),
+                    )));
+                }
+                IrExpressionKind::MethodCall {
+                    receiver: Box::new(receiver),
+                    receiver_type,
+                    method: method_name.clone(),
+                    arguments: self.arguments_in_order(arguments, &signature.parameters, symbols)?,
+                }
+            }
+            Expression::Variable { name, .. } => {
+                if let Some(slot) = self.vars.get(name) {
+                    IrExpressionKind::Get(*slot)
+                } else if let Some(var_type) = self.enclosing.get(name) {
+                    // captured by a lambda
+                    let slot = self.declare(&var_type.clone(), name);
+                    self.captures.push(slot);
+                    IrExpressionKind::Get(slot)
+                } else if let Some(Symbol::Function { .. }) = symbols.get(name) {
+                    IrExpressionKind::FunctionRef(format!("{}/{}", self.namespace, name))
+                } else {
+                    return Err(self.raise(UndeclaredVariable(name.to_string())));
+                }
+            }
+            Expression::Assignment {
+                variable_name,
+                value,
+                ..
+            } => {
+                let value = self.expression(value, symbols)?;
+                let Some(slot) = self.vars.get(variable_name) else {
+                    return Err(self.raise(UndeclaredVariable(variable_name.to_string())));
+                };
+                let value = converted(value, &self.locals[*slot].0);
+                IrExpressionKind::Assign(*slot, Box::new(value))
+            }
+            Expression::Cast {
+                value, target_type, ..
+            } => IrExpressionKind::Cast(
+                Box::new(self.expression(value, symbols)?),
+                target_type.clone(),
+            ),
+            Expression::ElementAssignment {
+                target,
+                value,
+                compound,
+                ..
+            } => {
+                let mut target = self.expression(target, symbols)?;
+                self.check_store_target(&target)?;
+                let mut keys = vec![];
+                let value = match value.as_ref() {
+                    // xs[f()] += 1 reads and writes the element with one call of f
+                    Expression::Binary {
+                        operator, right, ..
+                    } if *compound => {
+                        self.compute_keys_once(&mut target, &mut keys);
+                        let right = self.expression(right, symbols)?;
+                        IrExpression {
+                            kind: binary(binary_op(operator), target.clone(), right),
+                            ir_type: self.type_of(value, symbols)?,
+                            line: value.line(),
+                            span: value.span(),
+                        }
+                    }
+                    _ => self.expression(value, symbols)?,
+                };
+                IrExpressionKind::Store {
+                    keys,
+                    target: Box::new(target),
+                    value: Box::new(value),
+                }
+            }
+            Expression::Literal { value, .. } => IrExpressionKind::Constant(value.clone()),
+            Expression::List { values, .. } => IrExpressionKind::List(
+                values
+                    .iter()
+                    .map(|value| self.expression(value, symbols))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expression::Map { entries, .. } => IrExpressionKind::Map(
+                entries
+                    .iter()
+                    .map(|(key, value)| {
+                        Ok((self.expression(key, symbols)?, self.expression(value, symbols)?))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expression::Grouping { expression, .. } => return self.expression(expression, symbols),
+            Expression::Unary {
+                operator, right, ..
+            } => {
+                let op = match operator.token_type {
+                    TokenType::Minus => Op::Negate,
+                    TokenType::Bang => Op::Not,
+                    _ => unimplemented!("unary other than ! and -"),
+                };
+                IrExpressionKind::Unary(op, Box::new(self.expression(right, symbols)?))
+            }
+            Expression::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let left = self.expression(left, symbols)?;
+                let right = self.expression(right, symbols)?;
+                binary(binary_op(operator), left, right)
+            }
+            Expression::Stop { .. } => IrExpressionKind::Stop,
+            Expression::NamedParameter { value, .. } => return self.expression(value, symbols),
+            Expression::ListGet { list, index, .. } => IrExpressionKind::ListGet(
+                Box::new(self.expression(list, symbols)?),
+                Box::new(self.expression(index, symbols)?),
+            ),
+            Expression::Slice {
+                operand,
+                lower,
+                upper,
+                inclusive,
+                ..
+            } => IrExpressionKind::Slice {
+                operand: Box::new(self.expression(operand, symbols)?),
+                lower: self.optional(lower, symbols)?,
+                upper: self.optional(upper, symbols)?,
+                inclusive: *inclusive,
+            },
+            Expression::MapGet { map, key, .. } => IrExpressionKind::MapGet(
+                Box::new(self.expression(map, symbols)?),
+                Box::new(self.expression(key, symbols)?),
+            ),
+            Expression::FieldGet {
+                receiver, field, ..
+            } => IrExpressionKind::FieldGet(
+                Box::new(self.expression(receiver, symbols)?),
+                field.clone(),
+            ),
+            Expression::Interpolation { parts, .. } => IrExpressionKind::Interpolation(
+                parts
+                    .iter()
+                    .map(|part| self.expression(part, symbols))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expression::Lambda { function, .. } => self.lambda(function, symbols)?,
+            Expression::Range { lower, upper, .. } => IrExpressionKind::Range(
+                Box::new(self.expression(lower, symbols)?),
+                Box::new(self.expression(upper, symbols)?),
+            ),
+        };
+        Ok(IrExpression {
+            kind,
+            ir_type: self.type_of(expression, symbols)?,
+            line: expression.line(),
+            span: expression.span(),
+        })
+    }
+
+    fn lambda(
+        &mut self,
+        function: &Function,
+        symbols: &SymbolTable,
+    ) -> Result<IrExpressionKind, CompilerErrorAtLine> {
+        let mut pass = IrPass::new(&self.namespace);
+        pass.enclosing = self.enclosing.clone();
+        for (var_type, name) in &self.locals {
+            pass.enclosing.insert(name.clone(), var_type.clone());
+        }
+        let function = pass.lower_function(function, symbols)?;
+
+        // captured values are copied into the function value when it is created
+        let captured = function
+            .captures
+            .iter()
+            .map(|slot| {
+                let variable = self.variable_expression(&function.locals[*slot].1);
+                self.expression(&variable, symbols)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(IrExpressionKind::Closure {
+            namespace: self.namespace.clone(),
+            function: Box::new(function),
+            captured,
+        })
+    }
+
+    fn optional(
+        &mut self,
+        expression: &Option<Box<Expression>>,
+        symbols: &SymbolTable,
+    ) -> Result<Option<Box<IrExpression>>, CompilerErrorAtLine> {
+        expression
+            .as_ref()
+            .map(|e| self.expression(e, symbols).map(Box::new))
+            .transpose()
+    }
+
+    // only variables of this function, and their elements, can be assigned to
+    // replaces the computed indexes and keys of the target by variables, that get their value first
+    fn compute_keys_once(
+        &mut self,
+        target: &mut IrExpression,
+        keys: &mut Vec<(usize, IrExpression)>,
+    ) {
+        match &mut target.kind {
+            IrExpressionKind::ListGet(container, key) | IrExpressionKind::MapGet(container, key) => {
+                self.compute_keys_once(container, keys);
+                if !matches!(key.kind, IrExpressionKind::Constant(_) | IrExpressionKind::Get(_)) {
+                    let slot = self.declare(&key.ir_type, &format!("$key{}", self.locals.len()));
+                    let get = IrExpression {
+                        kind: IrExpressionKind::Get(slot),
+                        ir_type: key.ir_type.clone(),
+                        line: key.line,
+                        span: key.span,
+                    };
+                    keys.push((slot, std::mem::replace(key.as_mut(), get)));
+                }
+            }
+            IrExpressionKind::FieldGet(container, _) => self.compute_keys_once(container, keys),
+            _ => {}
+        }
+    }
+
+    fn check_store_target(&self, target: &IrExpression) -> Result<(), CompilerErrorAtLine> {
+        match &target.kind {
+            IrExpressionKind::Get(_) => Ok(()),
+            IrExpressionKind::ListGet(container, _)
+            | IrExpressionKind::MapGet(container, _)
+            | IrExpressionKind::FieldGet(container, _) => self.check_store_target(container),
+            _ => Err(self.raise(IllegalAssignmentTarget)),
+        }
+    }
+
+    // any unnamed parameters must be passed in order
+    // named parameters do not have to be passed in order, but they do need to be evaluated in the order of the called function/constructor
+    fn arguments_in_order(
+        &mut self,
+        arguments: &[Expression],
+        parameters: &[Parameter],
+        symbols: &SymbolTable,
+    ) -> Result<Vec<IrExpression>, CompilerErrorAtLine> {
+        let mut lowered = vec![];
+        for (index, argument) in arguments.iter().enumerate() {
+            for parameter in parameters {
+                if let Expression::NamedParameter { name, value, .. } = argument {
+                    if name.lexeme == parameter.name.lexeme {
+                        let value_type = self.type_of(value, symbols)?;
+                        // literals get their default type, like in let x = 40
+                        let literal_type = calculate_type(&Unknown, &value_type).ok();
+                        if let TokenType::FunctionType(..) = parameter.var_type {
+                            self.check_function_argument(&parameter.var_type, value, symbols)?;
+                            lowered.push(self.expression(argument, symbols)?);
+                            break;
+                        } else if parameter.var_type != value_type
+                            && Some(&parameter.var_type) != literal_type.as_ref()
+                        {
+                            return Err(self
+                                .raise(IncompatibleTypes(parameter.var_type.clone(), value_type)));
+                        } else {
+                            lowered.push(self.expression(argument, symbols)?);
+                            break;
+                        }
+                    }
+                } else {
+                    if let Some(parameter) = parameters.get(index) {
+                        self.check_function_argument(&parameter.var_type, argument, symbols)?;
+                    }
+                    lowered.push(self.expression(argument, symbols)?);
+                    break;
+                }
+            }
+        }
+        Ok(lowered)
+    }
+
+    // a function passed as an argument must have the declared function type
+    fn check_function_argument(
+        &self,
+        parameter_type: &TokenType,
+        argument: &Expression,
+        symbols: &SymbolTable,
+    ) -> Result<(), CompilerErrorAtLine> {
+        if let TokenType::FunctionType(..) = parameter_type {
+            let argument_type = self.type_of(argument, symbols)?;
+            calculate_type(parameter_type, &argument_type).map_err(|e| self.raise(e))?;
+        }
+        Ok(())
+    }
+
+    fn declare(&mut self, var_type: &TokenType, name: &str) -> usize {
+        self.locals.push((var_type.clone(), name.to_string()));
+        let slot = self.locals.len() - 1;
+        self.vars.insert(name.to_string(), slot);
+        slot
+    }
+
+    fn declare_loop_var(&mut self, loop_var: &Token, symbols: &SymbolTable) -> usize {
+        let name = loop_var.lexeme.as_str();
+        let var_type = if let Some(Symbol::Variable { var_type, .. }) = symbols.get(name) {
+            var_type
+        } else {
+            &loop_var.token_type
+        };
+        self.declare(var_type, name)
+    }
+
+    fn variable_expression(&self, name: &str) -> Expression {
+        Expression::Variable {
+            line: self.current_line,
+            span: self.current_span,
+            name: name.to_string(),
+            var_type: Unknown,
+        }
+    }
+
+    fn type_of(
+        &self,
+        expr: &Expression,
+        symbols: &SymbolTable,
+    ) -> Result<TokenType, CompilerErrorAtLine> {
+        infer_type(expr, symbols).map_err(|e| self.raise(e))
+    }
+
+    fn raise(&self, error: CompilerError) -> CompilerErrorAtLine {
+        CompilerErrorAtLine::raise(error, self.current_line).at(self.current_span)
+    }
+}
+
+fn binary(op: Op, mut left: IrExpression, mut right: IrExpression) -> IrExpressionKind {
+    if let Some(numeric_type) = literal_operand_type(&left.ir_type, &right.ir_type) {
+        left = literal_as(left, &numeric_type);
+        right = literal_as(right, &numeric_type);
+    }
+    IrExpressionKind::Binary(op, Box::new(left), Box::new(right))
+}
+
+// a number literal converted to the type of the other operand. Literals are i64 or f64 values,
+// that stay as they are when that is the type
+// a number stored in a variable of another number type is converted like `as`,
+// so that it fails instead of wrapping when it does not fit
+fn converted(expression: IrExpression, var_type: &TokenType) -> IrExpression {
+    let is_number = |t: &TokenType| {
+        matches!(
+            t,
+            TokenType::I32
+                | TokenType::I64
+                | TokenType::U32
+                | TokenType::U64
+                | TokenType::F32
+                | TokenType::F64
+        )
+    };
+    if &expression.ir_type == var_type || !is_number(&expression.ir_type) || !is_number(var_type) {
+        return expression;
+    }
+    IrExpression {
+        ir_type: var_type.clone(),
+        line: expression.line,
+        span: expression.span,
+        kind: IrExpressionKind::Cast(Box::new(expression), var_type.clone()),
+    }
+}
+
+fn literal_as(expression: IrExpression, numeric_type: &TokenType) -> IrExpression {
+    let converts = match expression.ir_type {
+        TokenType::Integer | TokenType::SignedInteger => numeric_type != &TokenType::I64,
+        TokenType::FloatingPoint => numeric_type != &TokenType::F64,
+        _ => false,
+    };
+    if !converts {
+        return expression;
+    }
+    IrExpression {
+        ir_type: numeric_type.clone(),
+        line: expression.line,
+        span: expression.span,
+        kind: IrExpressionKind::Cast(Box::new(expression), numeric_type.clone()),
+    }
+}
+
+fn binary_op(operator: &Token) -> Op {
+    match operator.token_type {
+        TokenType::BitAnd => Op::BitAnd,
+        TokenType::BitXor => Op::BitXor,
+        TokenType::EqualEqual => Op::Equal,
+        TokenType::BangEqual => Op::NotEqual,
+        TokenType::Greater => Op::Greater,
+        TokenType::GreaterEqual => Op::GreaterEqual,
+        TokenType::GreaterGreater => Op::Shr,
+        TokenType::Less => Op::Less,
+        TokenType::LessEqual => Op::LessEqual,
+        TokenType::LessLess => Op::Shl,
+        TokenType::LogicalAnd => Op::And,
+        TokenType::LogicalOr => Op::Or,
+        TokenType::Minus => Op::Subtract,
+        TokenType::Pipe => Op::BitOr,
+        TokenType::Plus => Op::Add,
+        TokenType::Slash => Op::Divide,
+        TokenType::Star => Op::Multiply,
+        TokenType::Percent => Op::Remainder,
+        TokenType::StarStar => Op::Power,
+        _ => unimplemented!("binary other than plus, minus, star, slash"),
+    }
+}
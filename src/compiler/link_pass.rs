@@ -0,0 +1,113 @@
+use crate::AsmRegistry;
+use crate::builtins::globals::GLOBAL_FUNCTIONS;
+use crate::builtins::{Signature, lookup};
+use crate::compiler::assembly_pass::{AsmChunk, Op};
+use crate::compiler::ast_pass::Parameter;
+use std::collections::HashMap;
+
+/// what a call refers to, once it is linked
+#[derive(Clone)]
+pub(crate) enum Link {
+    /// the index of the chunk in the registry
+    Function(usize),
+    Global(&'static Signature),
+    /// a method of a builtin type, called on the value below the arguments
+    Method(&'static Signature),
+    /// an object constructor, with the name and fields of the object
+    Constructor(String, Vec<Parameter>),
+}
+
+/// turns the calls by name into calls by link, so that the vm does not look up functions at runtime.
+/// Calls that cannot be resolved stay as they are, and fail when they run.
+/// Linking again after adding chunks, like the REPL does, keeps earlier links valid,
+/// because the registry keeps its order.
+pub fn link(registry: &mut AsmRegistry) {
+    let resolved: Vec<_> = registry
+        .iter()
+        .map(|(name, chunk)| resolve_calls(registry, name, chunk))
+        .collect();
+    for (chunk, calls) in registry.values_mut().zip(resolved) {
+        let mut link_indexes: HashMap<String, usize> = HashMap::new();
+        for (ip, name, link) in calls {
+            let call = chunk.code[ip].clone();
+            let index = *link_indexes.entry(name).or_insert_with(|| {
+                chunk.links.push((call.clone(), link));
+                chunk.links.len() - 1
+            });
+            chunk.code[ip] = match call {
+                Op::Call(_, num_args) | Op::CallBuiltin(_, _, num_args) => {
+                    Op::CallLinked(index, num_args)
+                }
+                _ => unreachable!("only calls are linked"),
+            };
+        }
+    }
+}
+
+/// puts back the calls by name, for a chunk that is stored without its links
+pub(crate) fn unlink(chunk: &mut AsmChunk) {
+    for op in chunk.code.iter_mut() {
+        if let Op::CallLinked(index, num_args) = *op {
+            // calls share a link by name, so the number of arguments comes from the call itself
+            *op = match chunk.links[index].0.clone() {
+                Op::Call(name, _) => Op::Call(name, num_args),
+                Op::CallBuiltin(name, receiver_type, _) => {
+                    Op::CallBuiltin(name, receiver_type, num_args)
+                }
+                _ => unreachable!("only calls are linked"),
+            };
+        }
+    }
+    chunk.links.clear();
+}
+
+// the ip, the name and the link of every call that can be resolved
+fn resolve_calls(
+    registry: &AsmRegistry,
+    chunk_name: &str,
+    chunk: &AsmChunk,
+) -> Vec<(usize, String, Link)> {
+    let mut calls = vec![];
+    for (ip, op) in chunk.code.iter().enumerate() {
+        match op {
+            Op::Call(name, _) => {
+                let name = chunk.constants[*name].to_string();
+                if let Some(link) = resolve_function(registry, chunk_name, &name) {
+                    calls.push((ip, name, link));
+                }
+            }
+            Op::CallBuiltin(name, receiver_type, _) => {
+                let name = chunk.constants[*name].to_string();
+                let receiver_type = chunk.constants[*receiver_type].to_string();
+                if let Ok(method) = lookup(&receiver_type, &name) {
+                    calls.push((ip, format!("{}.{}", receiver_type, name), Link::Method(method)));
+                }
+            }
+            _ => {}
+        }
+    }
+    calls
+}
+
+// global functions come first, then the functions and objects of the scope of the caller,
+// and of the scopes around it
+fn resolve_function(registry: &AsmRegistry, chunk_name: &str, name: &str) -> Option<Link> {
+    if let Some(function) = GLOBAL_FUNCTIONS.get(name) {
+        return Some(Link::Global(function));
+    }
+    let mut scope = chunk_name;
+    loop {
+        if let Some(index) = registry.get_index_of(&format!("{}/{}", scope, name)) {
+            return Some(Link::Function(index));
+        }
+        let constructor = registry.get(scope).and_then(|chunk| chunk.object_defs.get(name));
+        if let Some(fields) = constructor {
+            return Some(Link::Constructor(name.to_string(), fields.clone()));
+        }
+        match scope.rsplit_once('/') {
+            Some((outer, _)) => scope = outer,
+            None => break,
+        }
+    }
+    registry.get_index_of(name).map(Link::Function)
+}
@@ -1,43 +1,157 @@
 use std::collections::HashMap;
 use std::fs;
 use walkdir::WalkDir;
-use crate::{compiler, symbol_builder, AsmRegistry, TIPI_EXT};
-use crate::compiler::assembly_pass::AsmChunk;
-use crate::errors::TipiLangError;
+use crate::{symbol_builder, AsmRegistry, TIPI_EXT};
+use crate::compiler::ir_pass::IrFunction;
+use crate::compiler::rust_pass::{RustCrate, RustModule};
+use crate::compiler::warning_pass::{ObjectUsage, WarningConfig};
+use crate::errors::{CompilerErrorAtLine, Diagnostic, TipiLangError};
 use crate::errors::TipiLangError::Platform;
 
 mod compiler_tests;
 pub mod scan_pass;
 pub mod ast_pass;
 pub mod tokens;
+pub mod ir_pass;
 pub mod assembly_pass;
+pub mod disassembler;
+pub mod optimize_pass;
+pub mod link_pass;
+pub mod bytecode;
+pub mod rust_pass;
+pub mod warning_pass;
 
-pub fn compile_sourcedir(source_dir: &str) -> Result<HashMap<String, AsmChunk>, TipiLangError> {
+/// compiles all source files, reporting the errors of every file at once.
+/// Warnings are printed, unless tipi.toml allows them or denies them, which makes them errors.
+pub fn compile_sourcedir(source_dir: &str) -> Result<AsmRegistry, TipiLangError> {
+    let (asm_registry, diagnostics) = compile_project(source_dir)?;
+    let (warnings, errors): (Vec<_>, Vec<_>) =
+        diagnostics.into_iter().partition(|d| d.error.error.is_warning());
+    for warning in warnings {
+        println!("{}", warning);
+    }
+
+    if errors.is_empty() {
+        Ok(asm_registry)
+    } else {
+        Err(TipiLangError::Diagnostics(errors))
+    }
+}
+
+fn compile_project(source_dir: &str) -> Result<(AsmRegistry, Vec<Diagnostic>), TipiLangError> {
+    let config = WarningConfig::load(source_dir)?;
     let mut asm_registry = AsmRegistry::new();
+    let mut objects = ObjectUsage::default();
+    let mut sources = HashMap::new();
+    let mut diagnostics = vec![];
 
     for entry in WalkDir::new(source_dir).into_iter().filter_map(|e| e.ok()) {
         let path = entry.path().to_str().unwrap();
         if path.ends_with(TIPI_EXT) {
             print!("-- Compiling {} -- ", path);
             let source = fs::read_to_string(path).map_err(map_underlying())?;
-            let tokens = scan_pass::scan(&source)?;
-            let mut symbol_table = HashMap::new();
-            match ast_pass::compile(Some(path), tokens, &mut symbol_table) {
-                Ok(statements) => {
-                    let path = path.strip_prefix(source_dir).unwrap().replace(TIPI_EXT, "");
-
-                    symbol_builder::build(&path, &statements, &mut symbol_table);
-                    assembly_pass::compile(Some(&path), &statements, &symbol_table, &mut asm_registry)?;
-                }
-                Err(e) => {
-                    println!("{}", e);
-                    break;
+            let errors = compile_file(path, source_dir, &source, &mut asm_registry, &mut objects);
+            diagnostics.extend(
+                errors
+                    .into_iter()
+                    .filter_map(|e| config.apply(e))
+                    .map(|e| Diagnostic::new(path, &source, e)),
+            );
+            sources.insert(path.to_string(), source);
+        }
+    }
+    optimize_pass::peephole(&mut asm_registry);
+    link_pass::link(&mut asm_registry);
+    for (path, warning) in objects.unused() {
+        if let Some(warning) = config.apply(warning) {
+            diagnostics.push(Diagnostic::new(&path, &sources[&path], warning));
+        }
+    }
+    Ok((asm_registry, diagnostics))
+}
+
+// the errors and warnings in a source file
+fn compile_file(
+    path: &str,
+    source_dir: &str,
+    source: &str,
+    asm_registry: &mut AsmRegistry,
+    objects: &mut ObjectUsage,
+) -> Vec<CompilerErrorAtLine> {
+    let tokens = match scan_pass::scan(source) {
+        Ok(tokens) => tokens,
+        Err(e) => return vec![e],
+    };
+    let mut symbol_table = HashMap::new();
+    match ast_pass::compile(Some(path), tokens, &mut symbol_table) {
+        Ok(statements) => {
+            let mut problems = warning_pass::check(path, &statements, objects);
+            let path = path.strip_prefix(source_dir).unwrap().replace(TIPI_EXT, "");
+
+            symbol_builder::build(&path, &statements, &mut symbol_table);
+            match ir_pass::lower(&path, &statements, &symbol_table) {
+                Ok(mut ir) => {
+                    optimize_pass::fold(&mut ir);
+                    assembly_pass::compile(&ir, asm_registry);
                 }
+                Err(e) => problems.push(e),
             }
+            problems
         }
+        Err(errors) => errors,
     }
+}
 
-    Ok(asm_registry)
+/// translates all source files into a rust crate that serves the same routes, see rust_pass
+pub fn transpile_sourcedir(source_dir: &str) -> Result<RustCrate, TipiLangError> {
+    transpile_modules(source_dir).map(rust_pass::assemble)
+}
+
+// the rust code of every source file
+pub(crate) fn transpile_modules(source_dir: &str) -> Result<Vec<RustModule>, TipiLangError> {
+    let mut lowered = vec![];
+    let mut diagnostics = vec![];
+    for entry in WalkDir::new(source_dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path().to_str().unwrap();
+        if path.ends_with(TIPI_EXT) {
+            let source = fs::read_to_string(path).map_err(map_underlying())?;
+            match lower_file(path, source_dir, &source) {
+                Ok(ir) => lowered.push((path.to_string(), source, ir)),
+                Err(errors) => diagnostics
+                    .extend(errors.into_iter().map(|e| Diagnostic::new(path, &source, e))),
+            }
+        }
+    }
+    let irs: Vec<_> = lowered.iter().map(|(.., ir)| ir.clone()).collect();
+    let objects = rust_pass::objects(&irs);
+    let mut modules = vec![];
+    for (path, source, ir) in &lowered {
+        match rust_pass::transpile(ir, &objects) {
+            Ok(module) => modules.push(module),
+            Err(e) => diagnostics.push(Diagnostic::new(path, source, e)),
+        }
+    }
+    if diagnostics.is_empty() {
+        Ok(modules)
+    } else {
+        Err(TipiLangError::Diagnostics(diagnostics))
+    }
+}
+
+// the typed ir of a source file, like compile_file but without the warnings
+fn lower_file(
+    path: &str,
+    source_dir: &str,
+    source: &str,
+) -> Result<IrFunction, Vec<CompilerErrorAtLine>> {
+    let tokens = scan_pass::scan(source).map_err(|e| vec![e])?;
+    let mut symbol_table = HashMap::new();
+    let statements = ast_pass::compile(Some(path), tokens, &mut symbol_table)?;
+    let path = path.strip_prefix(source_dir).unwrap().replace(TIPI_EXT, "");
+    symbol_builder::build(&path, &statements, &mut symbol_table);
+    let mut ir = ir_pass::lower(&path, &statements, &symbol_table).map_err(|e| vec![e])?;
+    optimize_pass::fold(&mut ir);
+    Ok(ir)
 }
 
 pub fn map_underlying() -> fn(std::io::Error) -> TipiLangError {
@@ -45,24 +159,65 @@ pub fn map_underlying() -> fn(std::io::Error) -> TipiLangError {
 }
 
 
-pub fn compile(src: &str) -> Result<HashMap<String, AsmChunk>, TipiLangError> {
+pub fn compile(src: &str) -> Result<AsmRegistry, TipiLangError> {
+    compile_source(src, true)
+}
+
+fn compile_source(src: &str, optimize: bool) -> Result<AsmRegistry, TipiLangError> {
     let tokens = scan_pass::scan(src)?;
-    let mut asm_registry = HashMap::new();
+    let mut asm_registry = AsmRegistry::new();
     let mut symbol_table = HashMap::new();
     let ast = ast_pass::compile(None, tokens, &mut symbol_table)?;
     symbol_builder::build("", &ast, &mut symbol_table);
-    assembly_pass::compile(None, &ast, &symbol_table, &mut asm_registry)?;
+    let mut ir = ir_pass::lower("main", &ast, &symbol_table)?;
+    if optimize {
+        optimize_pass::fold(&mut ir);
+    }
+    assembly_pass::compile(&ir, &mut asm_registry);
+    if optimize {
+        optimize_pass::peephole(&mut asm_registry);
+    }
+    link_pass::link(&mut asm_registry);
     Ok(asm_registry)
 }
 
 #[cfg(test)]
-pub(crate) fn run(src: &str) -> Result<crate::value::Value, TipiLangError> {
+pub(crate) fn compile_unoptimized(src: &str) -> Result<AsmRegistry, TipiLangError> {
+    compile_source(src, false)
+}
+
+/// the warnings in a source directory, including the denied ones
+#[cfg(test)]
+pub(crate) fn warnings(source_dir: &str) -> Result<Vec<Diagnostic>, TipiLangError> {
+    use crate::errors::CompilerError::{Denied, Warning};
+    let (_, diagnostics) = compile_project(source_dir)?;
+    Ok(diagnostics
+        .into_iter()
+        .filter(|d| matches!(d.error.error, Warning(_) | Denied(_)))
+        .collect())
+}
+
+#[cfg(test)]
+pub(crate) fn ir(src: &str) -> Result<ir_pass::IrFunction, TipiLangError> {
     let tokens = scan_pass::scan(src)?;
     let mut symbol_table = HashMap::new();
     let ast = ast_pass::compile(None, tokens, &mut symbol_table)?;
     symbol_builder::build("", &ast, &mut symbol_table);
-    let mut asm_registry = HashMap::new();
-    assembly_pass::compile(None, &ast, &symbol_table, &mut asm_registry)?;
+    Ok(ir_pass::lower("main", &ast, &symbol_table)?)
+}
+
+#[cfg(test)]
+pub(crate) fn run(src: &str) -> Result<crate::value::Value, TipiLangError> {
+    execute(compile(src)?)
+}
+
+#[cfg(test)]
+pub(crate) fn run_unoptimized(src: &str) -> Result<crate::value::Value, TipiLangError> {
+    execute(compile_unoptimized(src)?)
+}
+
+#[cfg(test)]
+pub(crate) fn execute(asm_registry: AsmRegistry) -> Result<crate::value::Value, TipiLangError> {
     let registry = arc_swap::ArcSwap::from(std::sync::Arc::new(asm_registry));
     crate::vm::interpret(registry.load(), "main").map_err(TipiLangError::from)
 }
@@ -0,0 +1,368 @@
+use crate::AsmRegistry;
+use crate::compiler::assembly_pass::{AsmChunk, Op};
+use crate::compiler::ir_pass::IrExpressionKind::{Binary, Cast, Constant, Unary};
+use crate::compiler::ir_pass::{
+    IrExpression, IrExpressionKind, IrFunction, IrStatement, IrStatementKind,
+};
+use crate::value::Value;
+
+/// folds constant expressions and removes the branches of ifs that never run.
+/// Also moves a list, map or string out of a variable that gets the changed value right after,
+/// like in `xs = xs.push(x)`, so that the value is not shared and not copied when it changes
+pub fn fold(function: &mut IrFunction) {
+    function.body = fold_statements(std::mem::take(&mut function.body));
+}
+
+/// removes redundant ops and jumps, and duplicate constants, in all chunks
+pub fn peephole(registry: &mut AsmRegistry) {
+    for chunk in registry.values_mut() {
+        optimize_chunk(chunk);
+    }
+}
+
+fn fold_statements(statements: Vec<IrStatement>) -> Vec<IrStatement> {
+    let mut folded = vec![];
+    for mut statement in statements {
+        match statement.kind {
+            IrStatementKind::If {
+                mut condition,
+                then_branch,
+                else_branch,
+            } => {
+                fold_expression(&mut condition);
+                match condition.kind {
+                    Constant(Value::Bool(true)) => folded.extend(fold_statements(then_branch)),
+                    Constant(Value::Bool(false)) => {
+                        folded.extend(fold_statements(else_branch.unwrap_or_default()))
+                    }
+                    _ => folded.push(IrStatement {
+                        kind: IrStatementKind::If {
+                            condition,
+                            then_branch: fold_statements(then_branch),
+                            else_branch: else_branch.map(fold_statements),
+                        },
+                        ..statement
+                    }),
+                }
+                continue;
+            }
+            IrStatementKind::Let { ref mut value, .. }
+            | IrStatementKind::Print(ref mut value)
+            | IrStatementKind::Expression(ref mut value) => fold_expression(value),
+            IrStatementKind::Function(ref mut function) => fold(function),
+            IrStatementKind::Object { .. } => {}
+            IrStatementKind::RangeLoop {
+                ref mut range,
+                ref mut body,
+                ..
+            } => {
+                fold_expression(range);
+                *body = fold_statements(std::mem::take(body));
+            }
+            IrStatementKind::ListLoop {
+                ref mut list,
+                ref mut body,
+                ..
+            } => {
+                fold_expression(list);
+                *body = fold_statements(std::mem::take(body));
+            }
+        }
+        folded.push(statement);
+    }
+    folded
+}
+
+fn fold_expression(expression: &mut IrExpression) {
+    for child in children(&mut expression.kind) {
+        fold_expression(child);
+    }
+    if let IrExpressionKind::Closure { function, .. } = &mut expression.kind {
+        fold(function);
+    }
+    // operations that fail, like an overflow, are left to report their error at runtime
+    let value = match &expression.kind {
+        Unary(op, operand) => match &operand.kind {
+            Constant(value) => unary(op, value),
+            _ => None,
+        },
+        Binary(op, left, right) => match (&left.kind, &right.kind) {
+            (Constant(left), Constant(right)) => binary(op, left, right),
+            _ => None,
+        },
+        Cast(operand, target_type) => match &operand.kind {
+            Constant(value) => value.convert(target_type),
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(value) = value {
+        expression.kind = Constant(value);
+    }
+    move_reassigned(expression);
+}
+
+fn move_reassigned(expression: &mut IrExpression) {
+    match &mut expression.kind {
+        // the arguments are evaluated after the receiver is taken, so they cannot read it
+        IrExpressionKind::Assign(slot, value) => {
+            if let IrExpressionKind::MethodCall {
+                receiver,
+                arguments,
+                ..
+            } = &mut value.kind
+                && matches!(receiver.kind, IrExpressionKind::Get(s) if s == *slot)
+                && !arguments.iter_mut().any(|a| reads(a, *slot))
+            {
+                receiver.kind = IrExpressionKind::Take(*slot);
+            }
+        }
+        // the new value is evaluated before, the index or key after the container is taken
+        IrExpressionKind::Store { target, .. } => {
+            let (container, index) = match &mut target.kind {
+                IrExpressionKind::ListGet(container, index)
+                | IrExpressionKind::MapGet(container, index) => (container, Some(index)),
+                IrExpressionKind::FieldGet(container, _) => (container, None),
+                _ => return,
+            };
+            if let IrExpressionKind::Get(slot) = container.kind
+                && !index.is_some_and(|index| reads(index, slot))
+            {
+                container.kind = IrExpressionKind::Take(slot);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn reads(expression: &mut IrExpression, slot: usize) -> bool {
+    matches!(expression.kind, IrExpressionKind::Get(s) if s == slot)
+        || children(&mut expression.kind).into_iter().any(|child| reads(child, slot))
+}
+
+fn children(kind: &mut IrExpressionKind) -> Vec<&mut IrExpression> {
+    match kind {
+        Constant(_)
+        | IrExpressionKind::Get(_)
+        | IrExpressionKind::Take(_)
+        | IrExpressionKind::FunctionRef(_)
+        | IrExpressionKind::Stop => vec![],
+        IrExpressionKind::Assign(_, value) | Unary(_, value) | Cast(value, _) => vec![value],
+        IrExpressionKind::FieldGet(receiver, _) => vec![receiver],
+        IrExpressionKind::Store {
+            keys,
+            target,
+            value,
+        } => keys.iter_mut().map(|(_, key)| key).chain([&mut **target, &mut **value]).collect(),
+        Binary(_, left, right)
+        | IrExpressionKind::Range(left, right)
+        | IrExpressionKind::ListGet(left, right)
+        | IrExpressionKind::MapGet(left, right) => vec![left, right],
+        IrExpressionKind::List(values) | IrExpressionKind::Interpolation(values) => {
+            values.iter_mut().collect()
+        }
+        IrExpressionKind::Map(entries) => entries
+            .iter_mut()
+            .flat_map(|(key, value)| [key, value])
+            .collect(),
+        IrExpressionKind::Slice {
+            operand,
+            lower,
+            upper,
+            ..
+        } => {
+            let mut children = vec![operand.as_mut()];
+            children.extend(lower.as_deref_mut());
+            children.extend(upper.as_deref_mut());
+            children
+        }
+        IrExpressionKind::Call(_, arguments) => arguments.iter_mut().collect(),
+        IrExpressionKind::CallValue(function, arguments) => {
+            let mut children = vec![function.as_mut()];
+            children.extend(arguments.iter_mut());
+            children
+        }
+        IrExpressionKind::MethodCall {
+            receiver,
+            arguments,
+            ..
+        } => {
+            let mut children = vec![receiver.as_mut()];
+            children.extend(arguments.iter_mut());
+            children
+        }
+        IrExpressionKind::Closure { captured, .. } => captured.iter_mut().collect(),
+    }
+}
+
+// the same operations as the vm
+fn unary(op: &Op, value: &Value) -> Option<Value> {
+    match op {
+        Op::Negate => -value,
+        Op::Not => !value,
+        _ => return None,
+    }
+    .ok()
+}
+
+fn binary(op: &Op, a: &Value, b: &Value) -> Option<Value> {
+    match op {
+        Op::Add => a + b,
+        Op::Subtract => a - b,
+        Op::Multiply => a * b,
+        Op::Divide => a / b,
+        Op::Remainder => a % b,
+        Op::Power => a.pow(b),
+        Op::BitAnd => a & b,
+        Op::BitOr => a | b,
+        Op::BitXor => a ^ b,
+        Op::Shl => a << b,
+        Op::Shr => a >> b,
+        Op::Equal => Ok(Value::Bool(a == b)),
+        Op::NotEqual => Ok(Value::Bool(a != b)),
+        Op::Greater => Ok(Value::Bool(a > b)),
+        Op::GreaterEqual => Ok(Value::Bool(a >= b)),
+        Op::Less => Ok(Value::Bool(a < b)),
+        Op::LessEqual => Ok(Value::Bool(a <= b)),
+        Op::And | Op::Or => match (a, b) {
+            (Value::Bool(a), Value::Bool(b)) if *op == Op::And => Ok(Value::Bool(*a && *b)),
+            (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(*a || *b)),
+            _ => return None,
+        },
+        _ => return None,
+    }
+    .ok()
+}
+
+fn optimize_chunk(chunk: &mut AsmChunk) {
+    let mut remove = vec![false; chunk.code.len()];
+    for ip in 0..chunk.code.len() {
+        // an if keeps a copy of its condition, that is only popped when the condition is true
+        if chunk.code[ip] == Op::Dup
+            && matches!(chunk.code.get(ip + 1), Some(Op::GotoIfNot(_)))
+            && chunk.code.get(ip + 2) == Some(&Op::Pop)
+        {
+            remove[ip] = true;
+            remove[ip + 2] = true;
+        }
+        // a jump to a goto can jump to where the goto goes
+        if let Some(target) = jump_target(&chunk.code[ip]) {
+            let final_target = follow_gotos(&chunk.code, target);
+            set_jump_target(&mut chunk.code[ip], final_target);
+        }
+    }
+    // a goto to the next op, like at the end of an if without else
+    for ip in 0..chunk.code.len() {
+        if let Op::Goto(target) = chunk.code[ip]
+            && target > ip
+            && (ip + 1..target).all(|i| remove[i])
+        {
+            remove[ip] = true;
+        }
+    }
+    remove_ops(chunk, &remove);
+    deduplicate_constants(chunk);
+}
+
+fn follow_gotos(code: &[Op], mut target: usize) -> usize {
+    // bounded, in case of a loop of gotos
+    for _ in 0..code.len() {
+        match code.get(target) {
+            Some(Op::Goto(next)) if *next != target => target = *next,
+            _ => break,
+        }
+    }
+    target
+}
+
+fn jump_target(op: &Op) -> Option<usize> {
+    match op {
+        Op::Goto(target) | Op::GotoIf(target) | Op::GotoIfNot(target) => Some(*target),
+        _ => None,
+    }
+}
+
+fn set_jump_target(op: &mut Op, new_target: usize) {
+    if let Op::Goto(target) | Op::GotoIf(target) | Op::GotoIfNot(target) = op {
+        *target = new_target;
+    }
+}
+
+// jumps to a removed op go to the op after it
+fn remove_ops(chunk: &mut AsmChunk, remove: &[bool]) {
+    let mut new_index = Vec::with_capacity(remove.len() + 1);
+    let mut kept = 0;
+    for removed in remove {
+        new_index.push(kept);
+        if !removed {
+            kept += 1;
+        }
+    }
+    new_index.push(kept);
+
+    let mut ip = 0;
+    chunk.code.retain(|_| {
+        ip += 1;
+        !remove[ip - 1]
+    });
+    let mut ip = 0;
+    chunk.lines.retain(|_| {
+        ip += 1;
+        !remove[ip - 1]
+    });
+    for op in chunk.code.iter_mut() {
+        if let Some(target) = jump_target(op) {
+            set_jump_target(op, new_index[target]);
+        }
+    }
+}
+
+// the same value with the same representation, unlike ==, for which 0.0 equals -0.0,
+// 2.0m equals 2.00m and datetimes in different offsets can be equal
+fn identical(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::F32(a), Value::F32(b)) => a.to_bits() == b.to_bits(),
+        (Value::F64(a), Value::F64(b)) => a.to_bits() == b.to_bits(),
+        (Value::Decimal(a), Value::Decimal(b)) => a.serialize() == b.serialize(),
+        (Value::DateTime(a), Value::DateTime(b)) => a == b && a.offset() == b.offset(),
+        (Value::U32(_), Value::U32(_))
+        | (Value::I32(_), Value::I32(_))
+        | (Value::U64(_), Value::U64(_))
+        | (Value::I64(_), Value::I64(_))
+        | (Value::String(_), Value::String(_))
+        | (Value::Char(_), Value::Char(_))
+        | (Value::Bool(_), Value::Bool(_))
+        | (Value::Date(_), Value::Date(_))
+        | (Value::Duration(_), Value::Duration(_))
+        | (Value::Uuid(_), Value::Uuid(_)) => a == b,
+        // collections, objects and functions are not merged
+        _ => false,
+    }
+}
+
+fn deduplicate_constants(chunk: &mut AsmChunk) {
+    let mut constants: Vec<Value> = vec![];
+    let mut new_index = vec![];
+    for constant in chunk.constants.drain(..) {
+        let existing = constants.iter().position(|c| identical(c, &constant));
+        new_index.push(existing.unwrap_or_else(|| {
+            constants.push(constant);
+            constants.len() - 1
+        }));
+    }
+    chunk.constants = constants;
+    for op in chunk.code.iter_mut() {
+        match op {
+            Op::Constant(index)
+            | Op::Call(index, _)
+            | Op::FieldGet(index)
+            | Op::FieldSet(index)
+            | Op::Closure(index, _) => *index = new_index[*index],
+            Op::CallBuiltin(name, receiver_type, _) => {
+                *name = new_index[*name];
+                *receiver_type = new_index[*receiver_type];
+            }
+            _ => {}
+        }
+    }
+}
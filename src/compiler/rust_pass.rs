@@ -0,0 +1,1172 @@
+use crate::compiler::assembly_pass::Op;
+use crate::compiler::ast_pass::Parameter;
+use crate::compiler::ir_pass::{
+    IrExpression, IrExpressionKind, IrFunction, IrStatement, IrStatementKind,
+};
+use crate::compiler::map_underlying;
+use crate::compiler::tokens::TokenType;
+use crate::errors::CompilerError::Unsupported;
+use crate::errors::{CompilerErrorAtLine, TipiLangError};
+use crate::value::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// the http methods that a function in a web.tp file handles
+const METHODS: [&str; 5] = ["get", "post", "put", "delete", "patch"];
+
+/// the generated Rust source of a service
+pub struct RustCrate {
+    pub main_rs: String,
+}
+
+impl RustCrate {
+    /// writes the crate, named after its directory
+    pub fn write(&self, dir: &str) -> Result<(), TipiLangError> {
+        let name = Path::new(dir)
+            .file_name()
+            .map_or("tipi-service".to_string(), |name| {
+                name.to_string_lossy().to_string()
+            });
+        fs::create_dir_all(Path::new(dir).join("src")).map_err(map_underlying())?;
+        fs::write(Path::new(dir).join("Cargo.toml"), cargo_toml(&name)).map_err(map_underlying())?;
+        fs::write(Path::new(dir).join("src/main.rs"), &self.main_rs).map_err(map_underlying())
+    }
+}
+
+/// the rust code for one source file
+#[derive(Default)]
+pub(crate) struct RustModule {
+    structs: Vec<String>,
+    functions: Vec<String>,
+    handlers: Vec<String>,
+    /// the component path, the method and the name of the handler
+    routes: Vec<(String, String, String)>,
+}
+
+/// the objects of all source files, by name
+pub(crate) fn objects(modules: &[IrFunction]) -> HashMap<String, Vec<Parameter>> {
+    let mut objects = HashMap::new();
+    for module in modules {
+        for statement in &module.body {
+            if let IrStatementKind::Object { name, fields } = &statement.kind {
+                objects.insert(name.clone(), fields.clone());
+            }
+        }
+    }
+    objects
+}
+
+/// translates the typed ir of a source file, like hello/web, into rust: a struct for every object,
+/// a function for every function and an axum route for the http methods of a web.tp file.
+/// Anything the rust backend does not know yet is reported, instead of guessed.
+pub(crate) fn transpile(
+    ir: &IrFunction,
+    objects: &HashMap<String, Vec<Parameter>>,
+) -> Result<RustModule, CompilerErrorAtLine> {
+    let mut module = RustModule::default();
+    let functions = ir
+        .body
+        .iter()
+        .filter_map(|statement| match &statement.kind {
+            IrStatementKind::Function(function) => Some(function.name.clone()),
+            _ => None,
+        })
+        .collect();
+    let emitter = Emitter {
+        prefix: identifier(&ir.name),
+        functions,
+        objects,
+    };
+    let component = ir
+        .name
+        .strip_suffix("web")
+        .filter(|path| path.is_empty() || path.ends_with('/'));
+    for statement in &ir.body {
+        match &statement.kind {
+            IrStatementKind::Object { name, fields } => {
+                module
+                    .structs
+                    .push(emitter.object(name, fields, statement.line)?)
+            }
+            IrStatementKind::Function(function) => {
+                module
+                    .functions
+                    .push(emitter.function(function, statement.line)?);
+                if let Some(component) = component
+                    && METHODS.contains(&function.name.as_str())
+                {
+                    let component = format!("/{}", component.trim_matches('/'));
+                    let (name, handler) = emitter.handler(function, &component, statement.line)?;
+                    module.handlers.push(handler);
+                    module.routes.push((component, function.name.clone(), name));
+                }
+            }
+            _ => return Err(unsupported("top level code", statement.line)),
+        }
+    }
+    Ok(module)
+}
+
+/// puts the modules together in a main.rs that serves the routes like the vm does
+pub(crate) fn assemble(modules: Vec<RustModule>) -> RustCrate {
+    let mut main_rs = format!("{}{}{}{}", ALLOW, IMPORTS, RUNTIME, WEB);
+    let mut routes: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+    for module in modules {
+        let code = module.structs.iter().chain(&module.functions).chain(&module.handlers);
+        for code in code {
+            main_rs.push('\n');
+            main_rs.push_str(code);
+        }
+        for (component, method, handler) in module.routes {
+            routes.entry(component).or_default().push((method, handler));
+        }
+    }
+    main_rs.push_str("\n#[tokio::main]\nasync fn main() {\n    let app = Router::new()");
+    for (component, handlers) in routes {
+        let handlers: Vec<_> = handlers
+            .iter()
+            .map(|(method, handler)| format!("{}({})", method, handler))
+            .collect();
+        let handlers = handlers.join(".");
+        // the last path segment can be a parameter, like /api/customer/{id}
+        let with_parameter = format!("{}/{{param}}", component.trim_end_matches('/'));
+        main_rs.push_str(&format!("\n        .route({:?}, {})", component, handlers));
+        main_rs.push_str(&format!(
+            "\n        .route({:?}, {})",
+            with_parameter, handlers
+        ));
+    }
+    main_rs.push_str(";\n");
+    main_rs.push_str(MAIN_END);
+    RustCrate { main_rs }
+}
+
+/// the functions of the modules with the runtime they need, but without the structs and the routes,
+/// and so without dependencies: a program of its own with the given main. Tests run it to compare
+/// the rust code with the vm
+#[cfg(test)]
+pub(crate) fn program(modules: &[RustModule], main: &str) -> String {
+    let mut program = format!("{}use std::collections::HashMap;\n{}", ALLOW, RUNTIME);
+    for function in modules.iter().flat_map(|module| &module.functions) {
+        program.push('\n');
+        program.push_str(function);
+    }
+    program.push('\n');
+    program.push_str(main);
+    program
+}
+
+struct Emitter<'a> {
+    /// the source file, as a prefix for the function names
+    prefix: String,
+    functions: Vec<String>,
+    objects: &'a HashMap<String, Vec<Parameter>>,
+}
+
+// the variables of the function that is emitted
+struct Scope<'a> {
+    locals: &'a [(TokenType, String)],
+}
+
+impl Emitter<'_> {
+    fn object(
+        &self,
+        name: &str,
+        fields: &[Parameter],
+        line: usize,
+    ) -> Result<String, CompilerErrorAtLine> {
+        let mut code = format!(
+            "#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]\nstruct {} {{\n",
+            name
+        );
+        for field in fields {
+            code.push_str(&format!(
+                "    {}: {},\n",
+                identifier(&field.name.lexeme),
+                rust_type(&field.var_type, line)?
+            ));
+        }
+        code.push_str("}\n");
+        Ok(code)
+    }
+
+    fn function(&self, function: &IrFunction, line: usize) -> Result<String, CompilerErrorAtLine> {
+        let scope = Scope {
+            locals: &function.locals,
+        };
+        let parameters = function
+            .parameters
+            .iter()
+            .map(|p| {
+                Ok(format!(
+                    "mut {}: {}",
+                    identifier(&p.name.lexeme),
+                    rust_type(&p.var_type, line)?
+                ))
+            })
+            .collect::<Result<Vec<_>, CompilerErrorAtLine>>()?;
+        // the value of the last expression is returned, or the error the vm would raise
+        let return_type = match function.body.last().map(|statement| &statement.kind) {
+            Some(IrStatementKind::Expression(e)) if !is_statement(e) => e.ir_type.clone(),
+            _ => TokenType::Void,
+        };
+        let mut code = format!(
+            "fn {}({}) -> Result<{}, Error> {{\n",
+            self.function_name(&function.name),
+            parameters.join(", "),
+            rust_type(&return_type, line)?
+        );
+        self.statements(
+            &scope,
+            &function.body,
+            return_type != TokenType::Void,
+            1,
+            &mut code,
+        )?;
+        if return_type == TokenType::Void {
+            code.push_str("    Ok(())\n");
+        }
+        code.push_str("}\n");
+        Ok(code)
+    }
+
+    // an axum handler that takes the arguments from the request, like interpret_async
+    fn handler(
+        &self,
+        function: &IrFunction,
+        component: &str,
+        line: usize,
+    ) -> Result<(String, String), CompilerErrorAtLine> {
+        let name = format!("{}_route", self.function_name(&function.name));
+        let mut code = format!(
+            "async fn {}(\n    uri: Uri,\n    Query(query): Query<HashMap<String, String>>,\n    \
+             headers: HeaderMap,\n) -> Result<Json<serde_json::Value>, StatusCode> {{\n",
+            name
+        );
+        code.push_str(&format!(
+            "    let mut path_param = path_param(&uri, {:?});\n",
+            component
+        ));
+        let mut arguments = vec![];
+        for parameter in &function.parameters {
+            let argument = match parameter.name.lexeme.as_str() {
+                "path" => "uri.to_string()".to_string(),
+                "query" => "query.clone()".to_string(),
+                "headers" => "header_map(&headers)".to_string(),
+                name => {
+                    if !is_parameter_type(&parameter.var_type) {
+                        return Err(unsupported(
+                            &format!("a {} parameter", parameter.var_type),
+                            line,
+                        ));
+                    }
+                    format!(
+                        "parameter::<{}>(&query, {:?}, &mut path_param)?",
+                        rust_type(&parameter.var_type, line)?,
+                        name
+                    )
+                }
+            };
+            arguments.push(argument);
+        }
+        code.push_str(&format!(
+            "    let result = {}({}).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;\n",
+            self.function_name(&function.name),
+            arguments.join(", ")
+        ));
+        code.push_str(
+            "    serde_json::to_value(result)\n        .map(Json)\n        \
+             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)\n}\n",
+        );
+        Ok((name, code))
+    }
+
+    fn function_name(&self, name: &str) -> String {
+        if self.prefix.is_empty() {
+            identifier(name)
+        } else {
+            format!("{}_{}", self.prefix, identifier(name))
+        }
+    }
+
+    fn statements(
+        &self,
+        scope: &Scope,
+        statements: &[IrStatement],
+        returns: bool,
+        depth: usize,
+        code: &mut String,
+    ) -> Result<(), CompilerErrorAtLine> {
+        for (i, statement) in statements.iter().enumerate() {
+            let tail = returns && i == statements.len() - 1;
+            self.statement(scope, statement, tail, depth, code)?;
+        }
+        Ok(())
+    }
+
+    fn statement(
+        &self,
+        scope: &Scope,
+        statement: &IrStatement,
+        tail: bool,
+        depth: usize,
+        code: &mut String,
+    ) -> Result<(), CompilerErrorAtLine> {
+        let indent = "    ".repeat(depth);
+        match &statement.kind {
+            IrStatementKind::Let { slot, value } => {
+                let (var_type, name) = &scope.locals[*slot];
+                code.push_str(&format!(
+                    "{}let mut {}: {} = {};\n",
+                    indent,
+                    identifier(name),
+                    rust_type(var_type, statement.line)?,
+                    self.expression(scope, value)?
+                ));
+            }
+            IrStatementKind::Print(value) => {
+                let format = if is_display(&value.ir_type) {
+                    "{}"
+                } else {
+                    "{:?}"
+                };
+                code.push_str(&format!(
+                    "{}println!(\"{}\", {});\n",
+                    indent,
+                    format,
+                    self.expression(scope, value)?
+                ));
+            }
+            IrStatementKind::Expression(e) if tail => {
+                code.push_str(&format!("{}Ok({})\n", indent, self.expression(scope, e)?));
+            }
+            IrStatementKind::Expression(e) => {
+                code.push_str(&format!("{}{};\n", indent, self.effect(scope, e)?));
+            }
+            IrStatementKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                code.push_str(&format!(
+                    "{}if {} {{\n",
+                    indent,
+                    self.expression(scope, condition)?
+                ));
+                self.statements(scope, then_branch, false, depth + 1, code)?;
+                if let Some(else_branch) = else_branch {
+                    code.push_str(&format!("{}}} else {{\n", indent));
+                    self.statements(scope, else_branch, false, depth + 1, code)?;
+                }
+                code.push_str(&format!("{}}}\n", indent));
+            }
+            IrStatementKind::RangeLoop {
+                slot,
+                inclusive,
+                range,
+                body,
+                ..
+            } => {
+                let IrExpressionKind::Range(lower, upper) = &range.kind else {
+                    return Err(unsupported("a loop over this range", statement.line));
+                };
+                code.push_str(&format!(
+                    "{}for mut {} in {}{}{} {{\n",
+                    indent,
+                    identifier(&scope.locals[*slot].1),
+                    self.expression(scope, lower)?,
+                    if *inclusive { "..=" } else { ".." },
+                    self.expression(scope, upper)?
+                ));
+                self.statements(scope, body, false, depth + 1, code)?;
+                code.push_str(&format!("{}}}\n", indent));
+            }
+            IrStatementKind::ListLoop {
+                slot, list, body, ..
+            } => {
+                code.push_str(&format!(
+                    "{}for mut {} in {} {{\n",
+                    indent,
+                    identifier(&scope.locals[*slot].1),
+                    self.expression(scope, list)?
+                ));
+                self.statements(scope, body, false, depth + 1, code)?;
+                code.push_str(&format!("{}}}\n", indent));
+            }
+            IrStatementKind::Function(_) => {
+                return Err(unsupported("a nested function", statement.line));
+            }
+            IrStatementKind::Object { .. } => {
+                return Err(unsupported("a nested object", statement.line));
+            }
+        }
+        Ok(())
+    }
+
+    // an expression of which only the effect is used, so that assignments are statements
+    fn effect(
+        &self,
+        scope: &Scope,
+        expression: &IrExpression,
+    ) -> Result<String, CompilerErrorAtLine> {
+        match &expression.kind {
+            IrExpressionKind::Assign(slot, value) => Ok(format!(
+                "{} = {}",
+                identifier(&scope.locals[*slot].1),
+                self.expression(scope, value)?
+            )),
+            IrExpressionKind::Store {
+                keys,
+                target,
+                value,
+            } => {
+                let value = self.expression(scope, value)?;
+                let store = match &target.kind {
+                    IrExpressionKind::MapGet(map, key) => format!(
+                        "{}.insert({}, {})",
+                        self.place(scope, map)?,
+                        self.expression(scope, key)?,
+                        value
+                    ),
+                    _ => format!("{} = {}", self.place(scope, target)?, value),
+                };
+                if keys.is_empty() {
+                    return Ok(store);
+                }
+                // the computed keys first, as in the vm
+                let mut block = "{ ".to_string();
+                for (slot, key) in keys {
+                    let name = identifier(&scope.locals[*slot].1);
+                    block.push_str(&format!("let {} = {}; ", name, self.expression(scope, key)?));
+                }
+                Ok(format!("{}{} }}", block, store))
+            }
+            _ => self.expression(scope, expression),
+        }
+    }
+
+    // the variable, list element or object field that is assigned to
+    fn place(&self, scope: &Scope, target: &IrExpression) -> Result<String, CompilerErrorAtLine> {
+        match &target.kind {
+            IrExpressionKind::Get(slot) | IrExpressionKind::Take(slot) => {
+                Ok(identifier(&scope.locals[*slot].1))
+            }
+            IrExpressionKind::FieldGet(object, field) => Ok(format!(
+                "{}.{}",
+                self.place(scope, object)?,
+                identifier(field)
+            )),
+            IrExpressionKind::ListGet(list, index) => Ok(format!(
+                "(*element_mut(&mut {}, {})?)",
+                self.place(scope, list)?,
+                self.expression(scope, index)?
+            )),
+            IrExpressionKind::MapGet(map, key) => Ok(format!(
+                "(*value_mut(&mut {}, &{})?)",
+                self.place(scope, map)?,
+                self.expression(scope, key)?
+            )),
+            _ => Err(unsupported_at("this assignment", target)),
+        }
+    }
+
+    fn expression(
+        &self,
+        scope: &Scope,
+        expression: &IrExpression,
+    ) -> Result<String, CompilerErrorAtLine> {
+        Ok(match &expression.kind {
+            IrExpressionKind::Constant(value) => literal(value)
+                .ok_or_else(|| unsupported_at(&format!("a {} constant", value), expression))?,
+            // values are copied, as in the vm
+            IrExpressionKind::Get(slot) | IrExpressionKind::Take(slot) => {
+                let name = identifier(&scope.locals[*slot].1);
+                if is_copy(&expression.ir_type) {
+                    name
+                } else {
+                    format!("{}.clone()", name)
+                }
+            }
+            IrExpressionKind::Assign(..) | IrExpressionKind::Store { .. } => {
+                return Err(unsupported_at("an assignment used as a value", expression));
+            }
+            IrExpressionKind::Unary(op, operand) => {
+                let operand = self.expression(scope, operand)?;
+                match op {
+                    Op::Negate if is_integer(&expression.ir_type) => format!("neg({})?", operand),
+                    Op::Negate => format!("(-{})", operand),
+                    Op::Not => format!("(!{})", operand),
+                    _ => return Err(unsupported_at(&format!("operator {:?}", op), expression)),
+                }
+            }
+            IrExpressionKind::Binary(op, left, right) => {
+                self.binary(scope, op, left, right, expression)?
+            }
+            // conversions that lose information raise an error, like in the vm
+            IrExpressionKind::Cast(operand, target) => {
+                let operand = self.expression(scope, operand)?;
+                match target {
+                    TokenType::StringType => format!("{}.to_string()", operand),
+                    TokenType::I32
+                    | TokenType::I64
+                    | TokenType::U32
+                    | TokenType::U64
+                    | TokenType::F32
+                    | TokenType::F64 => {
+                        format!("to_{}({})?", rust_type(target, expression.line)?, operand)
+                    }
+                    _ => return Err(unsupported_at(&format!("a cast to {}", target), expression)),
+                }
+            }
+            IrExpressionKind::List(elements) => format!("vec![{}]", self.list(scope, elements)?),
+            IrExpressionKind::Map(entries) => {
+                let entries = entries
+                    .iter()
+                    .map(|(k, v)| {
+                        Ok(format!(
+                            "({}, {})",
+                            self.expression(scope, k)?,
+                            self.expression(scope, v)?
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, CompilerErrorAtLine>>()?;
+                format!("HashMap::from([{}])", entries.join(", "))
+            }
+            IrExpressionKind::ListGet(text, index) if text.ir_type == TokenType::StringType => {
+                format!(
+                    "char_at(&{}, {})?",
+                    self.borrowed(scope, text)?,
+                    self.expression(scope, index)?
+                )
+            }
+            IrExpressionKind::ListGet(list, index) => format!(
+                "element(&{}, {})?",
+                self.borrowed(scope, list)?,
+                self.expression(scope, index)?
+            ),
+            IrExpressionKind::MapGet(map, key) => format!(
+                "value(&{}, &{})?",
+                self.borrowed(scope, map)?,
+                self.expression(scope, key)?
+            ),
+            IrExpressionKind::FieldGet(object, field) => {
+                format!("{}.{}", self.expression(scope, object)?, identifier(field))
+            }
+            IrExpressionKind::Call(name, arguments) => {
+                if self.functions.contains(name) {
+                    format!(
+                        "{}({})?",
+                        self.function_name(name),
+                        self.list(scope, arguments)?
+                    )
+                } else if let Some(fields) = self.objects.get(name) {
+                    let fields = fields
+                        .iter()
+                        .zip(arguments)
+                        .map(|(field, argument)| {
+                            Ok(format!(
+                                "{}: {}",
+                                identifier(&field.name.lexeme),
+                                self.expression(scope, argument)?
+                            ))
+                        })
+                        .collect::<Result<Vec<_>, CompilerErrorAtLine>>()?;
+                    format!("{} {{ {} }}", name, fields.join(", "))
+                } else {
+                    return Err(unsupported_at(&format!("function {}", name), expression));
+                }
+            }
+            IrExpressionKind::MethodCall {
+                receiver,
+                receiver_type,
+                method,
+                arguments,
+            } => {
+                let receiver = self.expression(scope, receiver)?;
+                let arguments = self.list(scope, arguments)?;
+                match (receiver_type.as_str(), method.as_str()) {
+                    ("string" | "list", "len") => format!("({}.len() as u64)", receiver),
+                    ("string", "to_uppercase" | "to_lowercase") => {
+                        format!("{}.{}()", receiver, method)
+                    }
+                    ("string", "starts_with" | "ends_with") => {
+                        format!("{}.{}({}.as_str())", receiver, method, arguments)
+                    }
+                    ("list", "push") => {
+                        format!(
+                            "{{ let mut list = {}; list.push({}); list }}",
+                            receiver, arguments
+                        )
+                    }
+                    _ => {
+                        return Err(unsupported_at(
+                            &format!("{}.{}", receiver_type, method),
+                            expression,
+                        ));
+                    }
+                }
+            }
+            IrExpressionKind::Interpolation(parts) => {
+                let format: String = parts
+                    .iter()
+                    .map(|part| {
+                        if is_display(&part.ir_type) {
+                            "{}"
+                        } else {
+                            "{:?}"
+                        }
+                    })
+                    .collect();
+                format!("format!({:?}, {})", format, self.list(scope, parts)?)
+            }
+            IrExpressionKind::FunctionRef(_)
+            | IrExpressionKind::CallValue(..)
+            | IrExpressionKind::Closure { .. } => {
+                return Err(unsupported_at("a function value", expression));
+            }
+            IrExpressionKind::Range(..) => return Err(unsupported_at("a range value", expression)),
+            IrExpressionKind::Slice { .. } => return Err(unsupported_at("a slice", expression)),
+            IrExpressionKind::Stop => return Err(unsupported_at("stop", expression)),
+        })
+    }
+
+    fn binary(
+        &self,
+        scope: &Scope,
+        op: &Op,
+        left: &IrExpression,
+        right: &IrExpression,
+        expression: &IrExpression,
+    ) -> Result<String, CompilerErrorAtLine> {
+        let (left_type, right_type) = (&left.ir_type, &right.ir_type);
+        let (mut left, mut right) = (
+            self.expression(scope, left)?,
+            self.expression(scope, right)?,
+        );
+        // integers are widened to decimals, like in the vm
+        let decimal = left_type == &TokenType::Decimal || right_type == &TokenType::Decimal;
+        if decimal {
+            if is_integer(left_type) {
+                left = format!("Decimal::from({})", left);
+            }
+            if is_integer(right_type) {
+                right = format!("Decimal::from({})", right);
+            }
+        }
+        let temporal = matches!(left_type, TokenType::DateTime | TokenType::Date)
+            && right_type == &TokenType::Duration;
+        Ok(match op {
+            Op::Add if expression.ir_type == TokenType::StringType => {
+                format!("format!(\"{{}}{{}}\", {}, {})", left, right)
+            }
+            // overflows and divisions by zero raise an error instead of a panic
+            Op::Add | Op::Subtract | Op::Multiply | Op::Divide | Op::Remainder if decimal => {
+                let method = checked_method(op).unwrap_or_default();
+                format!("{}.checked_{}({}).ok_or(Error::Overflow)?", left, method, right)
+            }
+            // the exponent of a decimal is a whole number, like in the vm
+            Op::Power if decimal => {
+                let exponent = format!(
+                    "Some({}).filter(|e| e.fract().is_zero()).and_then(|e| i64::try_from(e).ok())",
+                    right
+                );
+                format!(
+                    "{}.checked_powi({}.ok_or(Error::IllegalExponent)?).ok_or(Error::Overflow)?",
+                    left, exponent
+                )
+            }
+            Op::Add | Op::Subtract if temporal => {
+                let method = checked_method(op).unwrap_or_default();
+                format!("{}.checked_{}_signed({}).ok_or(Error::OutOfRange)?", left, method, right)
+            }
+            op if is_integer(left_type) && checked_method(op).is_some() => {
+                format!("{}({}, {})?", checked_method(op).unwrap_or_default(), left, right)
+            }
+            Op::Power if matches!(left_type, TokenType::F32 | TokenType::F64) => {
+                format!("{}.powf({})", left, right)
+            }
+            op => {
+                let operator = operator(op)
+                    .ok_or_else(|| unsupported_at(&format!("operator {:?}", op), expression))?;
+                format!("({} {} {})", left, operator, right)
+            }
+        })
+    }
+
+    // a list or map that is only read, without a copy of it
+    fn borrowed(
+        &self,
+        scope: &Scope,
+        expression: &IrExpression,
+    ) -> Result<String, CompilerErrorAtLine> {
+        match &expression.kind {
+            IrExpressionKind::Get(slot) | IrExpressionKind::Take(slot) => {
+                Ok(identifier(&scope.locals[*slot].1))
+            }
+            _ => self.expression(scope, expression),
+        }
+    }
+
+    fn list(
+        &self,
+        scope: &Scope,
+        expressions: &[IrExpression],
+    ) -> Result<String, CompilerErrorAtLine> {
+        Ok(expressions
+            .iter()
+            .map(|e| self.expression(scope, e))
+            .collect::<Result<Vec<_>, _>>()?
+            .join(", "))
+    }
+}
+
+fn rust_type(var_type: &TokenType, line: usize) -> Result<String, CompilerErrorAtLine> {
+    Ok(match var_type {
+        TokenType::I32 => "i32".to_string(),
+        TokenType::I64 => "i64".to_string(),
+        TokenType::U32 => "u32".to_string(),
+        TokenType::U64 => "u64".to_string(),
+        TokenType::F32 => "f32".to_string(),
+        TokenType::F64 => "f64".to_string(),
+        TokenType::Bool => "bool".to_string(),
+        TokenType::Char => "char".to_string(),
+        TokenType::StringType => "String".to_string(),
+        TokenType::Date => "NaiveDate".to_string(),
+        TokenType::DateTime => "DateTime<FixedOffset>".to_string(),
+        TokenType::Duration => "TimeDelta".to_string(),
+        TokenType::Decimal => "Decimal".to_string(),
+        TokenType::Uuid => "Uuid".to_string(),
+        TokenType::Void => "()".to_string(),
+        TokenType::ListType(element) => format!("Vec<{}>", rust_type(element, line)?),
+        TokenType::MapType(key, value) => {
+            format!(
+                "HashMap<{}, {}>",
+                rust_type(key, line)?,
+                rust_type(value, line)?
+            )
+        }
+        TokenType::ObjectType(name) => name.clone(),
+        _ => return Err(unsupported(&format!("type {}", var_type), line)),
+    })
+}
+
+fn literal(value: &Value) -> Option<String> {
+    let literal = match value {
+        Value::I32(v) => format!("{}i32", v),
+        Value::I64(v) => format!("{}i64", v),
+        Value::U32(v) => format!("{}u32", v),
+        Value::U64(v) => format!("{}u64", v),
+        Value::F32(v) => format!("{:?}f32", v),
+        Value::F64(v) => format!("{:?}f64", v),
+        Value::String(v) => format!("String::from({:?})", &**v),
+        Value::Char(v) => format!("{:?}", v),
+        Value::Bool(v) => v.to_string(),
+        Value::Decimal(v) => {
+            format!("Decimal::from_i128_with_scale({}, {})", v.mantissa(), v.scale())
+        }
+        Value::Void => "()".to_string(),
+        _ => return None,
+    };
+    // folded negative constants
+    Some(if literal.starts_with('-') {
+        format!("({})", literal)
+    } else {
+        literal
+    })
+}
+
+// the name of the checked operation in the runtime, and of the checked method of rust
+fn checked_method(op: &Op) -> Option<&'static str> {
+    Some(match op {
+        Op::Add => "add",
+        Op::Subtract => "sub",
+        Op::Multiply => "mul",
+        Op::Divide => "div",
+        Op::Remainder => "rem",
+        Op::Power => "pow",
+        Op::Shl => "shl",
+        Op::Shr => "shr",
+        _ => return None,
+    })
+}
+
+fn operator(op: &Op) -> Option<&'static str> {
+    Some(match op {
+        Op::Add => "+",
+        Op::Subtract => "-",
+        Op::Multiply => "*",
+        Op::Divide => "/",
+        Op::Remainder => "%",
+        Op::And => "&&",
+        Op::Or => "||",
+        Op::Equal => "==",
+        Op::NotEqual => "!=",
+        Op::Greater => ">",
+        Op::GreaterEqual => ">=",
+        Op::Less => "<",
+        Op::LessEqual => "<=",
+        Op::BitAnd => "&",
+        Op::BitOr => "|",
+        Op::BitXor => "^",
+        Op::Shl => "<<",
+        Op::Shr => ">>",
+        _ => return None,
+    })
+}
+
+fn is_integer(var_type: &TokenType) -> bool {
+    matches!(
+        var_type,
+        TokenType::I32 | TokenType::I64 | TokenType::U32 | TokenType::U64
+    )
+}
+
+// the types that rust copies, the others are cloned
+fn is_copy(var_type: &TokenType) -> bool {
+    !matches!(
+        var_type,
+        TokenType::StringType
+            | TokenType::ListType(_)
+            | TokenType::MapType(_, _)
+            | TokenType::ObjectType(_)
+    )
+}
+
+// the types that print the same in rust as in tipi
+fn is_display(var_type: &TokenType) -> bool {
+    !matches!(
+        var_type,
+        TokenType::ListType(_) | TokenType::MapType(_, _) | TokenType::ObjectType(_)
+    )
+}
+
+// the types that interpret_async parses from the query or the path
+fn is_parameter_type(var_type: &TokenType) -> bool {
+    matches!(
+        var_type,
+        TokenType::StringType
+            | TokenType::Uuid
+            | TokenType::U32
+            | TokenType::U64
+            | TokenType::I32
+            | TokenType::I64
+            | TokenType::F32
+            | TokenType::F64
+            | TokenType::Decimal
+            | TokenType::Bool
+    )
+}
+
+// assignments have no value in rust
+fn is_statement(expression: &IrExpression) -> bool {
+    matches!(
+        expression.kind,
+        IrExpressionKind::Assign(..) | IrExpressionKind::Store { .. }
+    )
+}
+
+// a rust identifier for a name or a path like hello/web
+fn identifier(name: &str) -> String {
+    let identifier: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let identifier = identifier.trim_matches('_').to_string();
+    match identifier.as_str() {
+        "type" | "match" | "move" | "ref" | "impl" | "mod" | "use" | "struct" | "enum"
+        | "trait" | "where" | "loop" | "while" | "return" | "self" | "super" | "crate"
+        | "async" | "await" | "dyn" | "static" | "const" | "unsafe" | "extern" | "pub" | "mut" => {
+            format!("r#{}", identifier)
+        }
+        _ => identifier,
+    }
+}
+
+fn unsupported(what: &str, line: usize) -> CompilerErrorAtLine {
+    CompilerErrorAtLine::raise(Unsupported(what.to_string()), line)
+}
+
+fn unsupported_at(what: &str, expression: &IrExpression) -> CompilerErrorAtLine {
+    CompilerErrorAtLine {
+        error: Unsupported(what.to_string()),
+        line: expression.line,
+        span: Some(expression.span),
+    }
+}
+
+fn cargo_toml(name: &str) -> String {
+    format!(
+        r#"[package]
+name = "{}"
+version = "0.1.0"
+edition = "2024"
+
+[dependencies]
+axum = "0.8.6"
+tokio = {{ version = "1.47", features = ["full"] }}
+serde = {{ version = "1.0.228", features = ["derive"] }}
+serde_json = "1.0.145"
+chrono = {{ version = "0.4.42", features = ["serde"] }}
+rust_decimal = {{ version = "1.39.0", features = ["maths"] }}
+uuid = {{ version = "1.18.1", features = ["serde"] }}
+"#,
+        name
+    )
+}
+
+const ALLOW: &str = r#"// generated by tipi-lang transpile, do not edit
+#![allow(unused_imports, unused_mut, unused_variables, unused_parens, dead_code)]
+
+"#;
+
+const IMPORTS: &str = r#"use axum::extract::Query;
+use axum::http::{HeaderMap, StatusCode, Uri};
+use axum::routing::{delete, get, patch, post, put};
+use axum::{Json, Router};
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeDelta};
+use rust_decimal::{Decimal, MathematicalOps};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use uuid::Uuid;
+"#;
+
+// the checks of the vm: errors where plain rust would panic, wrap or lose information
+const RUNTIME: &str = r#"
+#[derive(Debug)]
+enum Error {
+    Overflow,
+    DivisionByZero,
+    IllegalExponent,
+    LossyConversion,
+    IndexOutOfBounds,
+    KeyNotFound,
+    OutOfRange,
+}
+
+trait Integer: Copy + Default + PartialEq + TryInto<u32> {
+    fn add(self, rhs: Self) -> Option<Self>;
+    fn sub(self, rhs: Self) -> Option<Self>;
+    fn mul(self, rhs: Self) -> Option<Self>;
+    fn div(self, rhs: Self) -> Option<Self>;
+    fn rem(self, rhs: Self) -> Option<Self>;
+    fn neg(self) -> Option<Self>;
+    fn pow(self, exponent: u32) -> Option<Self>;
+    fn shl(self, bits: u32) -> Option<Self>;
+    fn shr(self, bits: u32) -> Option<Self>;
+}
+
+macro_rules! integer {
+    ($($t:ty),*) => {$(
+        impl Integer for $t {
+            fn add(self, rhs: Self) -> Option<Self> { self.checked_add(rhs) }
+            fn sub(self, rhs: Self) -> Option<Self> { self.checked_sub(rhs) }
+            fn mul(self, rhs: Self) -> Option<Self> { self.checked_mul(rhs) }
+            fn div(self, rhs: Self) -> Option<Self> { self.checked_div(rhs) }
+            fn rem(self, rhs: Self) -> Option<Self> { self.checked_rem(rhs) }
+            fn neg(self) -> Option<Self> { self.checked_neg() }
+            fn pow(self, exponent: u32) -> Option<Self> { self.checked_pow(exponent) }
+            // bits that are shifted out overflow
+            fn shl(self, bits: u32) -> Option<Self> {
+                self.checked_shl(bits).filter(|shifted| shifted >> bits == self)
+            }
+            fn shr(self, bits: u32) -> Option<Self> { self.checked_shr(bits) }
+        }
+    )*};
+}
+integer!(i32, i64, u32, u64);
+
+fn add<T: Integer>(a: T, b: T) -> Result<T, Error> { a.add(b).ok_or(Error::Overflow) }
+fn sub<T: Integer>(a: T, b: T) -> Result<T, Error> { a.sub(b).ok_or(Error::Overflow) }
+fn mul<T: Integer>(a: T, b: T) -> Result<T, Error> { a.mul(b).ok_or(Error::Overflow) }
+fn neg<T: Integer>(a: T) -> Result<T, Error> { a.neg().ok_or(Error::Overflow) }
+
+fn div<T: Integer>(a: T, b: T) -> Result<T, Error> {
+    if b == T::default() {
+        return Err(Error::DivisionByZero);
+    }
+    a.div(b).ok_or(Error::Overflow)
+}
+
+fn rem<T: Integer>(a: T, b: T) -> Result<T, Error> {
+    if b == T::default() {
+        return Err(Error::DivisionByZero);
+    }
+    a.rem(b).ok_or(Error::Overflow)
+}
+
+fn pow<T: Integer>(a: T, exponent: T) -> Result<T, Error> {
+    let exponent = exponent.try_into().map_err(|_| Error::IllegalExponent)?;
+    a.pow(exponent).ok_or(Error::Overflow)
+}
+
+fn shl<T: Integer>(a: T, bits: T) -> Result<T, Error> {
+    a.shl(bits.try_into().map_err(|_| Error::Overflow)?).ok_or(Error::Overflow)
+}
+
+fn shr<T: Integer>(a: T, bits: T) -> Result<T, Error> {
+    a.shr(bits.try_into().map_err(|_| Error::Overflow)?).ok_or(Error::Overflow)
+}
+
+// a value that can be cast, as an integer when it has no fraction and as a float
+trait Number: Copy {
+    fn integer(self) -> Option<i128>;
+    fn float(self) -> Option<f64>;
+}
+
+macro_rules! integer_number {
+    ($($t:ty),*) => {$(
+        impl Number for $t {
+            fn integer(self) -> Option<i128> { Some(self as i128) }
+            // a float is exact up to 2^53
+            fn float(self) -> Option<f64> {
+                Some(self as f64).filter(|float| *float as i128 == self as i128)
+            }
+        }
+    )*};
+}
+integer_number!(i32, i64, u32, u64);
+
+macro_rules! float_number {
+    ($($t:ty),*) => {$(
+        impl Number for $t {
+            fn integer(self) -> Option<i128> {
+                (self.is_finite() && self.fract() == 0.0).then(|| self as i128)
+            }
+            fn float(self) -> Option<f64> { Some(self as f64) }
+        }
+    )*};
+}
+float_number!(f32, f64);
+
+impl Number for bool {
+    fn integer(self) -> Option<i128> { Some(self as i128) }
+    fn float(self) -> Option<f64> { Some(self as u8 as f64) }
+}
+
+impl Number for char {
+    fn integer(self) -> Option<i128> { Some(self as u32 as i128) }
+    fn float(self) -> Option<f64> { Some(self as u32 as f64) }
+}
+
+fn to_i32<N: Number>(n: N) -> Result<i32, Error> {
+    n.integer().and_then(|i| i.try_into().ok()).ok_or(Error::LossyConversion)
+}
+
+fn to_i64<N: Number>(n: N) -> Result<i64, Error> {
+    n.integer().and_then(|i| i.try_into().ok()).ok_or(Error::LossyConversion)
+}
+
+fn to_u32<N: Number>(n: N) -> Result<u32, Error> {
+    n.integer().and_then(|i| i.try_into().ok()).ok_or(Error::LossyConversion)
+}
+
+fn to_u64<N: Number>(n: N) -> Result<u64, Error> {
+    n.integer().and_then(|i| i.try_into().ok()).ok_or(Error::LossyConversion)
+}
+
+fn to_f64<N: Number>(n: N) -> Result<f64, Error> {
+    n.float().ok_or(Error::LossyConversion)
+}
+
+// an f32 has less precision, but not less range
+fn to_f32<N: Number>(n: N) -> Result<f32, Error> {
+    let float = n.float().filter(|f| !f.is_finite() || f.abs() <= f32::MAX as f64);
+    float.map(|f| f as f32).ok_or(Error::LossyConversion)
+}
+
+// the position of an index in a list or string, negative ones count from the end
+trait Index: Copy {
+    fn position(self, len: usize) -> Result<usize, Error>;
+}
+
+impl Index for i64 {
+    fn position(self, len: usize) -> Result<usize, Error> {
+        let position = if self < 0 {
+            usize::try_from(self.unsigned_abs()).ok().and_then(|from_end| len.checked_sub(from_end))
+        } else {
+            usize::try_from(self).ok()
+        };
+        position.filter(|p| *p < len).ok_or(Error::IndexOutOfBounds)
+    }
+}
+
+impl Index for i32 {
+    fn position(self, len: usize) -> Result<usize, Error> { i64::from(self).position(len) }
+}
+
+impl Index for u64 {
+    fn position(self, len: usize) -> Result<usize, Error> {
+        usize::try_from(self).ok().filter(|p| *p < len).ok_or(Error::IndexOutOfBounds)
+    }
+}
+
+impl Index for u32 {
+    fn position(self, len: usize) -> Result<usize, Error> { u64::from(self).position(len) }
+}
+
+fn element<T: Clone, I: Index>(list: &[T], index: I) -> Result<T, Error> {
+    Ok(list[index.position(list.len())?].clone())
+}
+
+fn element_mut<T, I: Index>(list: &mut [T], index: I) -> Result<&mut T, Error> {
+    let position = index.position(list.len())?;
+    Ok(&mut list[position])
+}
+
+fn char_at<I: Index>(text: &str, index: I) -> Result<char, Error> {
+    let position = index.position(text.chars().count())?;
+    text.chars().nth(position).ok_or(Error::IndexOutOfBounds)
+}
+
+fn value<K: Eq + std::hash::Hash, V: Clone>(map: &HashMap<K, V>, key: &K) -> Result<V, Error> {
+    map.get(key).cloned().ok_or(Error::KeyNotFound)
+}
+
+fn value_mut<'a, K: Eq + std::hash::Hash, V>(
+    map: &'a mut HashMap<K, V>,
+    key: &K,
+) -> Result<&'a mut V, Error> {
+    map.get_mut(key).ok_or(Error::KeyNotFound)
+}
+"#;
+
+const WEB: &str = r#"
+// the last path segment, for a route like /api/customer/{id}
+fn path_param(uri: &Uri, component: &str) -> Option<String> {
+    let segment = uri.path().strip_prefix(component)?.trim_matches('/');
+    (!segment.is_empty()).then(|| segment.to_string())
+}
+
+// a parameter from the query, or else from the last path segment, like the vm does
+fn parameter<T: FromStr>(
+    query: &HashMap<String, String>,
+    name: &str,
+    path_param: &mut Option<String>,
+) -> Result<T, StatusCode> {
+    let text = query.get(name).cloned().or_else(|| path_param.take());
+    text.ok_or(StatusCode::BAD_REQUEST)?.parse().map_err(|_| StatusCode::BAD_REQUEST)
+}
+
+fn header_map(headers: &HeaderMap) -> HashMap<String, String> {
+    headers
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or_default().to_string()))
+        .collect()
+}
+"#;
+
+const MAIN_END: &str = r#"    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
+        .await
+        .unwrap();
+    println!("-- Listening on {} --", listener.local_addr().unwrap());
+    axum::serve(listener, app).await.unwrap();
+}
+"#;
@@ -1,15 +1,18 @@
-use crate::errors::CompilerError::{IllegalCharLength, UnexpectedIdentifier, Unterminated};
+use crate::errors::CompilerError::{
+    Expected, IllegalCharLength, IllegalEscape, UnexpectedIdentifier, Unterminated,
+};
 use crate::errors::{CompilerError, CompilerErrorAtLine};
 use crate::compiler::tokens::TokenType::{BitXor, FloatingPoint, Integer, Question, U32, U64};
 use crate::keywords;
 use crate::compiler::tokens::{
-    Token,
+    Span, Token,
     TokenType::{self},
 };
 
 pub fn scan(source: &str) -> Result<Vec<Token>, CompilerErrorAtLine> {
     let scanner = Scanner {
         chars: source.chars().collect(),
+        offsets: byte_offsets(source),
         current: 0,
         start: 0,
         line: 1,
@@ -25,6 +28,7 @@ impl Scanner {
             self.start = self.current;
             self.scan_token()?;
         }
+        self.start = self.current;
         self.add_token(TokenType::Eol);
         self.add_token(TokenType::Eof);
         Ok(self.tokens)
@@ -47,20 +51,54 @@ impl Scanner {
                 '[' => self.add_token(TokenType::LeftBracket),
                 ']' => self.add_token(TokenType::RightBracket),
                 ',' => self.add_token(TokenType::Comma),
+                '.' if self.match_next('.') => {
+                    let t = if self.match_next('=') {
+                        TokenType::RangeInclusive
+                    } else {
+                        TokenType::Range
+                    };
+                    self.add_token(t);
+                }
                 '.' => self.add_token(TokenType::Dot),
                 '-' => {
                     let t = if self.match_next('>') {
                         TokenType::SingleRightArrow
+                    } else if self.match_next('=') {
+                        TokenType::MinusEqual
                     } else {
                         TokenType::Minus
                     };
                     self.add_token(t);
                 }
                 '#' => self.add_token(TokenType::Hash),
-                '+' => self.add_token(TokenType::Plus),
+                '+' => {
+                    let t = if self.match_next('=') {
+                        TokenType::PlusEqual
+                    } else {
+                        TokenType::Plus
+                    };
+                    self.add_token(t);
+                }
+                '%' => {
+                    let t = if self.match_next('=') {
+                        TokenType::PercentEqual
+                    } else {
+                        TokenType::Percent
+                    };
+                    self.add_token(t);
+                }
                 ':' => self.add_token(TokenType::Colon),
                 ';' => println!("Warning: Ignoring semicolon at line {}", self.line),
-                '*' => self.add_token(TokenType::Star),
+                '*' => {
+                    let t = if self.match_next('*') {
+                        TokenType::StarStar
+                    } else if self.match_next('=') {
+                        TokenType::StarEqual
+                    } else {
+                        TokenType::Star
+                    };
+                    self.add_token(t);
+                }
                 '!' => {
                     let t = if self.match_next('=') {
                         TokenType::BangEqual
@@ -103,6 +141,8 @@ impl Scanner {
                         while self.peek() != '\n' && !self.is_at_end() {
                             self.advance();
                         }
+                    } else if self.match_next('=') {
+                        self.add_token(TokenType::SlashEqual);
                     } else {
                         self.add_token(TokenType::Slash);
                     }
@@ -110,7 +150,14 @@ impl Scanner {
                 '\'' => self.char()?,
                 '"' => self.string()?,
                 'd' if self.match_next('"') => {
-                    self.datetime()?;
+                    self.prefixed_literal(TokenType::DateTime)?;
+                }
+                'u' if self.match_next('"') => {
+                    self.prefixed_literal(TokenType::Uuid)?;
+                }
+                'f' if self.match_next('"') => self.interpolated_string()?,
+                'r' if self.match_next('"') => {
+                    self.prefixed_literal(TokenType::StringType)?;
                 }
                 '\r' | '\t' | ' ' => {}
                 '\n' => {
@@ -189,45 +236,42 @@ impl Scanner {
             self.advance();
         }
         if self.peek() == '.' && self.peek_next() == '.' {
-            self.range_expression()
+            // the lower bound of a range, the range itself is scanned next
+            let value: String = self.chars[self.start..self.current].iter().collect();
+            self.add_token_with_value(Integer, value);
         } else {
             while is_digit_or_scientific(self.peek()) {
                 self.advance();
             }
             let value: String = self.chars[self.start..self.current].iter().collect();
-            self.add_token_with_value(if has_dot { FloatingPoint } else { Integer }, value);
-        }
-    }
-
-    fn range_expression(&mut self) {
-        let lower: String = self.chars[self.start..self.current].iter().collect();
-        self.match_next('.');
-        self.match_next('.');
-        self.add_token_with_value(Integer, lower);
-        self.add_token(TokenType::Range);
-        self.start = self.current;
-        while self.peek().is_ascii_digit() {
-            self.advance();
+            // 19.99m is a decimal literal
+            if self.peek() == 'm' && !is_alphanumeric(self.peek_next()) {
+                self.advance();
+                self.add_token_with_value(TokenType::Decimal, value);
+            } else {
+                self.add_token_with_value(if has_dot { FloatingPoint } else { Integer }, value);
+            }
         }
-        let upper: String = self.chars[self.start..self.current].iter().collect();
-        self.add_token_with_value(Integer, upper);
     }
 
     fn char(&mut self) -> Result<(), CompilerErrorAtLine> {
+        let mut text = String::new();
         while self.peek() != '\'' && !self.is_at_end() {
-            self.advance();
+            if self.match_next('\\') {
+                self.escaped(&mut text);
+            } else {
+                text.push(self.advance());
+            }
         }
 
         if self.is_at_end() {
-            return Err(CompilerErrorAtLine::raise(Unterminated("char"), self.line));
+            return Err(self.raise(Unterminated("char")));
         }
 
         self.advance();
 
-        let value: String = self.chars[self.start + 1..self.current - 1]
-            .iter()
-            .collect();
-        if value.len() != 1 {
+        let value = unescape(&text).map_err(|e| self.raise(e))?;
+        if value.chars().count() != 1 {
             return Err(self.raise(IllegalCharLength(value)));
         }
         self.add_token_with_value(TokenType::Char, value);
@@ -235,42 +279,192 @@ impl Scanner {
     }
 
     fn raise(&self, error: CompilerError) -> CompilerErrorAtLine {
-        CompilerErrorAtLine::raise(error, self.line)
+        CompilerErrorAtLine::raise(error, self.line).at(self.span())
     }
 
-    fn datetime(&mut self) -> Result<(), CompilerErrorAtLine> {
+    // the location of the token being scanned, from its start up to the current char
+    fn span(&self) -> Span {
+        let newlines = self.chars[self.start..self.current]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count();
+        Span {
+            start: self.offsets[self.start] as u32,
+            end: self.offsets[self.current] as u32,
+            line: (self.line - newlines) as u32,
+            column: self.column(self.start),
+        }
+    }
+
+    fn column(&self, index: usize) -> u32 {
+        let line_start = self.chars[..index]
+            .iter()
+            .rposition(|c| *c == '\n')
+            .map_or(0, |i| i + 1);
+        (index - line_start + 1) as u32
+    }
+
+    // literals like d"..." and u"..." that are parsed into their value by the compiler
+    fn prefixed_literal(&mut self, token_type: TokenType) -> Result<(), CompilerErrorAtLine> {
         while self.peek() != '"' && !self.is_at_end() {
-            self.advance();
+            if self.advance() == '\n' {
+                self.line += 1;
+            }
+        }
+        if self.is_at_end() {
+            return Err(self.raise(Unterminated("literal")));
         }
         self.advance();
         let value: String = self.chars[self.start + 2..self.current - 1]
             .iter()
             .collect();
-        self.add_token_with_value(TokenType::DateTime, value);
+        self.add_token_with_value(token_type, value);
         Ok(())
     }
 
-    fn string(&mut self) -> Result<(), CompilerErrorAtLine> {
-        while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+    // f"hello {name}": the text parts become strings, the embedded expressions are scanned
+    // in place, between braces. {{ and }} are literal braces
+    fn interpolated_string(&mut self) -> Result<(), CompilerErrorAtLine> {
+        self.add_token(TokenType::InterpolationStart);
+        let mut text = String::new();
+        loop {
+            if self.is_at_end() {
+                return Err(self.raise(Unterminated("string")));
+            }
+            match self.advance() {
+                '"' => break,
+                '\\' => self.escaped(&mut text),
+                '{' if self.match_next('{') => text.push('{'),
+                '}' if self.match_next('}') => text.push('}'),
+                '{' => {
+                    if !text.is_empty() {
+                        let part = unescape(&std::mem::take(&mut text)).map_err(|e| self.raise(e))?;
+                        self.add_token_with_value(TokenType::StringType, part);
+                    }
+                    self.add_token(TokenType::LeftBrace);
+                    self.embedded_expression()?;
+                    self.add_token(TokenType::RightBrace);
+                }
+                '}' => return Err(self.raise(Expected("'}}' for a literal brace"))),
+                c => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    text.push(c);
+                }
             }
-            self.advance();
         }
+        if !text.is_empty() {
+            let part = unescape(&text).map_err(|e| self.raise(e))?;
+            self.add_token_with_value(TokenType::StringType, part);
+        }
+        self.add_token(TokenType::InterpolationEnd);
+        Ok(())
+    }
 
-        if self.is_at_end() {
-            return Err(self.raise(Unterminated("string")));
+    // scans the tokens up to the closing brace, with a scanner of its own
+    fn embedded_expression(&mut self) -> Result<(), CompilerErrorAtLine> {
+        let start = self.current;
+        let mut depth = 0;
+        let mut in_string = false;
+        loop {
+            if self.is_at_end() || (self.peek() == '\n' && !in_string) {
+                return Err(self.raise(Unterminated("interpolation")));
+            }
+            match self.advance() {
+                '"' => in_string = !in_string,
+                '{' if !in_string => depth += 1,
+                '}' if !in_string && depth == 0 => break,
+                '}' if !in_string => depth -= 1,
+                _ => {}
+            }
+        }
+        let chars = self.chars[start..self.current - 1].to_vec();
+        let mut scanner = Scanner {
+            offsets: byte_offsets(&chars.iter().collect::<String>()),
+            chars,
+            current: 0,
+            start: 0,
+            line: self.line,
+            tokens: vec![],
+            new_line: false,
+        };
+        while !scanner.is_at_end() {
+            scanner.start = scanner.current;
+            scanner.scan_token()?;
         }
+        // the embedded scanner only knows its own chars, on a single line
+        let column = self.column(start);
+        for mut token in scanner.tokens {
+            token.span.start += self.offsets[start] as u32;
+            token.span.end += self.offsets[start] as u32;
+            token.span.column += column - 1;
+            self.tokens.push(token);
+        }
+        Ok(())
+    }
 
-        self.advance();
+    fn string(&mut self) -> Result<(), CompilerErrorAtLine> {
+        if self.peek() == '"' && self.peek_next() == '"' {
+            self.advance();
+            self.advance();
+            return self.multiline_string();
+        }
+        let mut text = String::new();
+        loop {
+            if self.is_at_end() {
+                return Err(self.raise(Unterminated("string")));
+            }
+            match self.advance() {
+                '"' => break,
+                '\\' => self.escaped(&mut text),
+                c => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    text.push(c);
+                }
+            }
+        }
+        let value = unescape(&text).map_err(|e| self.raise(e))?;
+        self.add_token_with_value(TokenType::StringType, value);
+        Ok(())
+    }
 
-        let value: String = self.chars[self.start + 1..self.current - 1]
-            .iter()
-            .collect();
+    // """...""" with the common indentation stripped
+    fn multiline_string(&mut self) -> Result<(), CompilerErrorAtLine> {
+        let mut text = String::new();
+        loop {
+            if self.is_at_end() {
+                return Err(self.raise(Unterminated("multi-line string")));
+            }
+            if self.peek() == '"' && self.peek_next() == '"' && self.peek_at(2) == '"' {
+                self.current += 3;
+                break;
+            }
+            match self.advance() {
+                '\\' => self.escaped(&mut text),
+                c => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    text.push(c);
+                }
+            }
+        }
+        let value = unescape(&strip_indent(&text)).map_err(|e| self.raise(e))?;
         self.add_token_with_value(TokenType::StringType, value);
         Ok(())
     }
 
+    // keeps an escape sequence as is, so that an escaped quote does not end the string
+    fn escaped(&mut self, text: &mut String) {
+        text.push('\\');
+        if !self.is_at_end() {
+            text.push(self.advance());
+        }
+    }
+
     fn peek(&self) -> char {
         if self.current >= self.chars.len() {
             '\0'
@@ -280,11 +474,11 @@ impl Scanner {
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.chars.len() {
-            '\0'
-        } else {
-            self.chars[self.current + 1]
-        }
+        self.peek_at(1)
+    }
+
+    fn peek_at(&self, offset: usize) -> char {
+        self.chars.get(self.current + offset).copied().unwrap_or('\0')
     }
 
     fn match_next(&mut self, expected: char) -> bool {
@@ -298,11 +492,11 @@ impl Scanner {
 
     fn add_token(&mut self, tokentype: TokenType) {
         self.tokens
-            .push(Token::new(tokentype, "".to_string(), self.line));
+            .push(Token::new(tokentype, "".to_string(), self.line, self.span()));
     }
 
     fn add_token_with_value(&mut self, tokentype: TokenType, value: String) {
-        self.tokens.push(Token::new(tokentype, value, self.line));
+        self.tokens.push(Token::new(tokentype, value, self.line, self.span()));
     }
 
     fn advance(&mut self) -> char {
@@ -316,6 +510,8 @@ impl Scanner {
 
 struct Scanner {
     chars: Vec<char>,
+    // the byte offset of every char, and of the end of the source
+    offsets: Vec<usize>,
     current: usize,
     start: usize,
     tokens: Vec<Token>,
@@ -323,6 +519,80 @@ struct Scanner {
     new_line: bool,
 }
 
+fn byte_offsets(source: &str) -> Vec<usize> {
+    source
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain(std::iter::once(source.len()))
+        .collect()
+}
+
+// \n, \t, \r, \0, \\, \", \' and \u{...}
+fn unescape(text: &str) -> Result<String, CompilerError> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        let escaped = match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('\'') => '\'',
+            Some('u') => {
+                let rest = chars.as_str();
+                let code_point = rest
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.split_once('}'))
+                    .map(|(hex, _)| hex)
+                    .filter(|hex| (1..=6).contains(&hex.len()))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| illegal_escape(rest.chars().take_while(|c| *c != '}')))?;
+                // skip past the closing brace
+                chars = rest.split_once('}').map(|(_, rest)| rest).unwrap_or("").chars();
+                code_point
+            }
+            Some(other) => return Err(IllegalEscape(format!("\\{}", other))),
+            None => return Err(IllegalEscape("\\".to_string())),
+        };
+        unescaped.push(escaped);
+    }
+    Ok(unescaped)
+}
+
+fn illegal_escape(sequence: impl Iterator<Item = char>) -> CompilerError {
+    IllegalEscape(format!("\\u{}", sequence.collect::<String>()))
+}
+
+// removes the line break after the opening quotes, the line with the closing quotes
+// and the indentation that all lines have in common
+fn strip_indent(text: &str) -> String {
+    let mut lines: Vec<&str> = text.split('\n').collect();
+    if lines.len() > 1 && lines[0].trim().is_empty() {
+        lines.remove(0);
+    }
+    if lines.len() > 1 && lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or(line.trim_start()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 fn is_digit_or_scientific(c: char) -> bool {
     c.is_ascii_digit() || c == 'e' || c == 'E'
 }
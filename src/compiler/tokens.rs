@@ -1,25 +1,52 @@
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     pub line: usize,
+    pub span: Span,
 }
 
 impl Token {
-    pub(crate) fn new(tokentype: TokenType, lexeme: String, line: usize) -> Self {
+    pub(crate) fn new(tokentype: TokenType, lexeme: String, line: usize, span: Span) -> Self {
         Self {
             token_type: tokentype,
             lexeme,
             line,
+            span,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Hash)]
+/// a location in the source: the byte range, and the line and column (in chars) it starts at
+/// u32 keeps compiler errors small, sources are nowhere near 4GB
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Span {
+    pub start: u32,
+    pub end: u32,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Span {
+    /// the span from the start of this one to the end of the other
+    pub fn to(self, other: Span) -> Span {
+        if other.end <= self.start {
+            return self;
+        }
+        Span {
+            end: other.end,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Hash, Serialize, Deserialize)]
 pub enum TokenType {
     Any,
+    As,
     Bang,
     BangEqual,
     BitAnd,
@@ -29,7 +56,10 @@ pub enum TokenType {
     Char,
     Colon,
     Comma,
+    Date,
     DateTime,
+    Decimal,
+    Duration,
     Dot,
     Else,
     Eof,
@@ -41,6 +71,7 @@ pub enum TokenType {
     F64,
     False,
     Fn,
+    FunctionType(Vec<TokenType>, Box<TokenType>),
     For,
     Greater,
     GreaterEqual,
@@ -54,6 +85,8 @@ pub enum TokenType {
     In,
     Indent,
     Integer,
+    InterpolationStart,
+    InterpolationEnd,
     SignedInteger,
     UnsignedInteger,
     LeftBrace,
@@ -63,18 +96,23 @@ pub enum TokenType {
     LessEqual,
     LessLess,
     Let,
-    ListType,
-    MapType,
+    ListType(Box<TokenType>),
+    MapType(Box<TokenType>, Box<TokenType>),
     LogicalAnd,
     LogicalOr,
     Minus,
+    MinusEqual,
     Not,
     FloatingPoint,
     Object,
+    Percent,
+    PercentEqual,
     Plus,
+    PlusEqual,
     Print,
     Question,
     Range,
+    RangeInclusive,
     Return,
     RightParen,
     RightBrace,
@@ -82,12 +120,16 @@ pub enum TokenType {
     Semicolon,
     SingleRightArrow,
     Slash,
+    SlashEqual,
     Star,
+    StarEqual,
+    StarStar,
     StringType,
     True,
     U32,
     U64,
     Unknown,
+    Uuid,
     Void,
     While,
     ObjectType(String),
@@ -97,8 +139,12 @@ impl fmt::Display for TokenType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             TokenType::Any => write!(f, "any"),
+            TokenType::As => write!(f, "as"),
             TokenType::StringType => write!(f, "string"),
+            TokenType::Date => write!(f, "date"),
             TokenType::DateTime => write!(f, "datetime"),
+            TokenType::Decimal => write!(f, "decimal"),
+            TokenType::Duration => write!(f, "duration"),
             TokenType::Char => write!(f, "char"),
             TokenType::I32 => write!(f, "i32"),
             TokenType::I64 => write!(f, "i64"),
@@ -115,8 +161,30 @@ impl fmt::Display for TokenType {
             TokenType::Colon => write!(f, ":"),
             TokenType::Comma => write!(f, ","),
             TokenType::FloatingPoint => write!(f, "float"),
-            TokenType::MapType => write!(f, "map"),
-            TokenType::ListType => write!(f, "list"),
+            TokenType::MapType(key_type, value_type) => {
+                if **key_type == TokenType::Any && **value_type == TokenType::Any {
+                    write!(f, "map")
+                } else {
+                    write!(f, "map<{}, {}>", key_type, value_type)
+                }
+            }
+            TokenType::ListType(element_type) => {
+                if **element_type == TokenType::Any {
+                    write!(f, "list")
+                } else {
+                    write!(f, "list<{}>", element_type)
+                }
+            }
+            TokenType::FunctionType(parameter_types, return_type) => {
+                write!(f, "fn(")?;
+                for (i, parameter_type) in parameter_types.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", parameter_type)?;
+                }
+                write!(f, ") -> {}", return_type)
+            }
             TokenType::Dot => write!(f, "."),
             TokenType::Else => write!(f, "else"),
             TokenType::Eof => write!(f, "EOF"),
@@ -137,6 +205,8 @@ impl fmt::Display for TokenType {
             TokenType::In => write!(f, "in"),
             TokenType::Indent => write!(f, "indent"),
             TokenType::Integer => write!(f, "integer"),
+            TokenType::InterpolationStart => write!(f, "f\""),
+            TokenType::InterpolationEnd => write!(f, "\""),
             TokenType::LeftBrace => write!(f, "{{"),
             TokenType::LeftBracket => write!(f, "["),
             TokenType::LeftParen => write!(f, "("),
@@ -151,9 +221,17 @@ impl fmt::Display for TokenType {
             TokenType::Object => write!(f, "object"),
             TokenType::ObjectType(_) => write!(f, "object"),
             TokenType::Plus => write!(f, "+"),
+            TokenType::PlusEqual => write!(f, "+="),
+            TokenType::MinusEqual => write!(f, "-="),
+            TokenType::StarEqual => write!(f, "*="),
+            TokenType::SlashEqual => write!(f, "/="),
+            TokenType::Percent => write!(f, "%"),
+            TokenType::PercentEqual => write!(f, "%="),
+            TokenType::StarStar => write!(f, "**"),
             TokenType::Print => write!(f, "print"),
             TokenType::Question => write!(f, "?"),
             TokenType::Range => write!(f, ".."),
+            TokenType::RangeInclusive => write!(f, "..="),
             TokenType::Return => write!(f, "return"),
             TokenType::RightParen => write!(f, ")"),
             TokenType::RightBrace => write!(f, "}}"),
@@ -164,6 +242,7 @@ impl fmt::Display for TokenType {
             TokenType::Star => write!(f, "*"),
             TokenType::True => write!(f, "true"),
             TokenType::Unknown => write!(f, "?"),
+            TokenType::Uuid => write!(f, "uuid"),
             TokenType::Void => write!(f, "()"),
             TokenType::While => write!(f, "while"),
             TokenType::SignedInteger => write!(f, "i32/64"),
@@ -175,6 +254,23 @@ impl fmt::Display for TokenType {
 impl Eq for TokenType {}
 
 impl TokenType {
+    // the types of numbers, including those of literals
+    pub(crate) fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            TokenType::I32
+                | TokenType::I64
+                | TokenType::U32
+                | TokenType::U64
+                | TokenType::F32
+                | TokenType::F64
+                | TokenType::Decimal
+                | TokenType::Integer
+                | TokenType::SignedInteger
+                | TokenType::FloatingPoint
+        )
+    }
+
     pub(crate) fn is_type(&self) -> bool {
         matches!(
             self,
@@ -184,12 +280,35 @@ impl TokenType {
                 | TokenType::U64
                 | TokenType::F32
                 | TokenType::F64
+                | TokenType::Decimal
                 | TokenType::StringType
+                | TokenType::Date
                 | TokenType::DateTime
+                | TokenType::Duration
+                | TokenType::Uuid
                 | TokenType::Object
-                | TokenType::ListType
-                | TokenType::MapType
+                | TokenType::ListType(_)
+                | TokenType::MapType(_, _)
                 | TokenType::Char
         )
     }
+
+    /// list without an element type annotation, that can hold any value
+    pub(crate) fn untyped_list() -> TokenType {
+        TokenType::ListType(Box::new(TokenType::Any))
+    }
+
+    /// map without key and value type annotations
+    pub(crate) fn untyped_map() -> TokenType {
+        TokenType::MapType(Box::new(TokenType::Any), Box::new(TokenType::Any))
+    }
+
+    /// the type without generic type arguments, as used for looking up builtin methods
+    pub(crate) fn erased(&self) -> TokenType {
+        match self {
+            TokenType::ListType(_) => TokenType::untyped_list(),
+            TokenType::MapType(_, _) => TokenType::untyped_map(),
+            _ => self.clone(),
+        }
+    }
 }
@@ -0,0 +1,429 @@
+use crate::compiler::ast_pass::{Expression, Function, Parameter, Statement};
+use crate::compiler::tokens::TokenType::{FunctionType, ListType, MapType, ObjectType};
+use crate::compiler::tokens::{Span, Token, TokenType};
+use crate::errors::CompilerWarning::{
+    ConstantCondition, Shadowing, UnroutableHandler, UnusedObject, UnusedParameter,
+    UnusedVariable,
+};
+use crate::errors::{CompilerError, CompilerErrorAtLine, CompilerWarning, TipiLangError};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+pub const CONFIG_FILE: &str = "tipi.toml";
+
+const LINTS: [&str; 6] = [
+    "unused_variable",
+    "unused_parameter",
+    "shadowing",
+    "unroutable_handler",
+    "unused_object",
+    "constant_condition",
+];
+
+// the methods that requests are routed by, see handle_any in main
+const HTTP_METHODS: [&str; 9] = [
+    "get", "post", "put", "delete", "patch", "head", "options", "trace", "connect",
+];
+
+/// what to do with a warning, per lint in the [warnings] table of tipi.toml:
+/// ```toml
+/// [warnings]
+/// unused_parameter = "allow"
+/// constant_condition = "deny"
+/// ```
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct WarningConfig {
+    #[serde(default)]
+    warnings: HashMap<String, Level>,
+}
+
+impl WarningConfig {
+    /// reads tipi.toml in the source directory, when there is one
+    pub fn load(source_dir: &str) -> Result<Self, TipiLangError> {
+        let path = Path::new(source_dir).join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(&path).map_err(|e| TipiLangError::Platform(e.to_string()))?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, TipiLangError> {
+        let config: Self = toml::from_str(text)
+            .map_err(|e| TipiLangError::Platform(format!("{}: {}", CONFIG_FILE, e)))?;
+        if let Some(lint) = config.warnings.keys().find(|lint| !LINTS.contains(&lint.as_str())) {
+            return Err(TipiLangError::Platform(format!(
+                "{}: unknown warning '{}'",
+                CONFIG_FILE, lint
+            )));
+        }
+        Ok(config)
+    }
+
+    /// allowed warnings are dropped, denied warnings become errors
+    pub fn apply(&self, warning: CompilerErrorAtLine) -> Option<CompilerErrorAtLine> {
+        let CompilerError::Warning(w) = &warning.error else {
+            return Some(warning);
+        };
+        match self.warnings.get(w.lint()).unwrap_or(&Level::Warn) {
+            Level::Allow => None,
+            Level::Warn => Some(warning),
+            Level::Deny => Some(CompilerErrorAtLine {
+                error: CompilerError::Denied(w.clone()),
+                ..warning
+            }),
+        }
+    }
+}
+
+/// the objects of all source files, because an object can be used in another file than its own
+#[derive(Default)]
+pub struct ObjectUsage {
+    declared: Vec<(String, Token)>,
+    used: HashSet<String>,
+}
+
+impl ObjectUsage {
+    /// warnings for the objects that are used nowhere, with the path of their source file
+    pub fn unused(&self) -> Vec<(String, CompilerErrorAtLine)> {
+        self.declared
+            .iter()
+            .filter(|(_, name)| !self.used.contains(&name.lexeme))
+            .map(|(path, name)| {
+                let warning = warn(UnusedObject(name.lexeme.clone()), name.line, name.span);
+                (path.clone(), warning)
+            })
+            .collect()
+    }
+
+    // object types can be nested, like list<Customer>
+    fn mention(&mut self, var_type: &TokenType) {
+        match var_type {
+            ObjectType(name) => {
+                self.used.insert(name.clone());
+            }
+            ListType(element) => self.mention(element),
+            MapType(key, value) => {
+                self.mention(key);
+                self.mention(value);
+            }
+            FunctionType(parameters, return_type) => {
+                parameters.iter().for_each(|p| self.mention(p));
+                self.mention(return_type);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// the warnings for a source file, except for unused objects, see [ObjectUsage]
+pub fn check(
+    path: &str,
+    statements: &[Statement],
+    objects: &mut ObjectUsage,
+) -> Vec<CompilerErrorAtLine> {
+    let mut checker = Checker {
+        path,
+        scopes: vec![Scope::default()],
+        functions: vec![],
+        calls: HashSet::new(),
+        warnings: vec![],
+        objects,
+    };
+    checker.statements(statements);
+    checker.end_scope();
+
+    // functions in web.tp are request handlers, unless other functions there use them
+    if Path::new(path).file_stem().is_some_and(|stem| stem == "web") {
+        for function in std::mem::take(&mut checker.functions) {
+            let name = &function.lexeme;
+            if !HTTP_METHODS.contains(&name.as_str()) && !checker.calls.contains(name) {
+                let warning = warn(UnroutableHandler(name.clone()), function.line, function.span);
+                checker.warnings.push(warning);
+            }
+        }
+    }
+    checker.warnings.sort_by_key(|w| w.span.map(|s| s.start));
+    checker.warnings
+}
+
+fn warn(warning: CompilerWarning, line: usize, span: Span) -> CompilerErrorAtLine {
+    CompilerErrorAtLine::raise(CompilerError::Warning(warning), line).at(span)
+}
+
+struct Declaration {
+    name: Token,
+    parameter: bool,
+    used: bool,
+}
+
+// variables are visible in the whole function they are declared in
+#[derive(Default)]
+struct Scope {
+    declarations: Vec<Declaration>,
+}
+
+struct Checker<'a> {
+    path: &'a str,
+    scopes: Vec<Scope>,
+    // the top level functions
+    functions: Vec<Token>,
+    calls: HashSet<String>,
+    warnings: Vec<CompilerErrorAtLine>,
+    objects: &'a mut ObjectUsage,
+}
+
+impl Checker<'_> {
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::ExpressionStmt { expression } => self.expression(expression),
+            Statement::PrintStmt { value } => self.expression(value),
+            Statement::VarStmt {
+                name,
+                var_type,
+                initializer,
+            } => {
+                // the initializer can still read an earlier variable with the same name
+                self.expression(initializer);
+                self.objects.mention(var_type);
+                self.declare(name, false);
+            }
+            Statement::FunctionStmt { function } => {
+                if self.scopes.len() == 1 {
+                    self.functions.push(function.name.clone());
+                }
+                self.function(function);
+            }
+            Statement::ObjectStmt { name, fields } => {
+                self.objects.declared.push((self.path.to_string(), name.clone()));
+                fields.iter().for_each(|f| self.objects.mention(&f.var_type));
+            }
+            Statement::GuardStatement { if_expr, then_expr } => {
+                self.expression(if_expr);
+                self.expression(then_expr);
+            }
+            Statement::IfStatement {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if is_constant(condition) {
+                    self.warnings.push(warn(
+                        ConstantCondition,
+                        condition.line(),
+                        condition.span(),
+                    ));
+                }
+                self.expression(condition);
+                self.statements(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statements(else_branch);
+                }
+            }
+            Statement::ForStatement {
+                loop_var,
+                range,
+                body,
+            } => {
+                self.expression(range);
+                // the loop variable belongs to the loop, the variables of the body do not
+                self.scopes.push(Scope::default());
+                self.declare(loop_var, false);
+                self.statements(body);
+                let mut scope = self.scopes.pop().unwrap();
+                let body = scope.declarations.split_off(1);
+                self.scopes.last_mut().unwrap().declarations.extend(body);
+                self.scopes.push(scope);
+                self.end_scope();
+            }
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        self.scopes.push(Scope::default());
+        for Parameter { name, var_type } in &function.parameters {
+            self.objects.mention(var_type);
+            self.scopes.last_mut().unwrap().declarations.push(Declaration {
+                name: name.clone(),
+                parameter: true,
+                used: false,
+            });
+        }
+        self.objects.mention(&function.return_type);
+        self.statements(&function.body);
+        self.end_scope();
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Binary { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Unary { right, .. } => self.expression(right),
+            Expression::Grouping { expression, .. } => self.expression(expression),
+            Expression::Literal { literaltype, .. } => self.objects.mention(literaltype),
+            Expression::Range { lower, upper, .. } => {
+                self.expression(lower);
+                self.expression(upper);
+            }
+            Expression::List {
+                values,
+                literaltype,
+                ..
+            } => {
+                self.objects.mention(literaltype);
+                values.iter().for_each(|v| self.expression(v));
+            }
+            Expression::Map {
+                entries,
+                literaltype,
+                ..
+            } => {
+                self.objects.mention(literaltype);
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expression::Variable { name, var_type, .. } => {
+                self.objects.mention(var_type);
+                self.read(name);
+            }
+            // only assigning to a variable is not using it
+            Expression::Assignment { value, .. } => self.expression(value),
+            Expression::FunctionCall {
+                name, arguments, ..
+            } => {
+                // a function, an object constructor, or a variable holding a function
+                self.calls.insert(name.clone());
+                self.objects.used.insert(name.clone());
+                self.read(name);
+                arguments.iter().for_each(|a| self.expression(a));
+            }
+            Expression::MethodCall {
+                receiver,
+                arguments,
+                ..
+            } => {
+                self.expression(receiver);
+                arguments.iter().for_each(|a| self.expression(a));
+            }
+            Expression::ValueCall {
+                function,
+                arguments,
+                ..
+            } => {
+                self.expression(function);
+                arguments.iter().for_each(|a| self.expression(a));
+            }
+            Expression::Stop { .. } => {}
+            Expression::NamedParameter { value, .. } => self.expression(value),
+            Expression::MapGet { map, key, .. } => {
+                self.expression(map);
+                self.expression(key);
+            }
+            Expression::ListGet { list, index, .. } => {
+                self.expression(list);
+                self.expression(index);
+            }
+            Expression::Cast {
+                value, target_type, ..
+            } => {
+                self.objects.mention(target_type);
+                self.expression(value);
+            }
+            Expression::ElementAssignment { target, value, .. } => {
+                self.expression(target);
+                self.expression(value);
+            }
+            Expression::Slice {
+                operand,
+                lower,
+                upper,
+                ..
+            } => {
+                self.expression(operand);
+                lower.iter().chain(upper.iter()).for_each(|b| self.expression(b));
+            }
+            Expression::FieldGet { receiver, .. } => self.expression(receiver),
+            Expression::Lambda { function, .. } => self.function(function),
+            Expression::Interpolation { parts, .. } => {
+                parts.iter().for_each(|p| self.expression(p));
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &Token, parameter: bool) {
+        let shadowed = self
+            .scopes
+            .iter()
+            .flat_map(|scope| scope.declarations.iter())
+            .any(|d| d.name.lexeme == name.lexeme);
+        if shadowed && !name.lexeme.starts_with('_') {
+            let warning = warn(Shadowing(name.lexeme.clone()), name.line, name.span);
+            self.warnings.push(warning);
+        }
+        self.scopes.last_mut().unwrap().declarations.push(Declaration {
+            name: name.clone(),
+            parameter,
+            used: false,
+        });
+    }
+
+    // marks the latest declaration with the name as used, looking outwards from the current scope
+    fn read(&mut self, name: &str) {
+        let declaration = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .flat_map(|scope| scope.declarations.iter_mut().rev())
+            .find(|d| d.name.lexeme == name);
+        if let Some(declaration) = declaration {
+            declaration.used = true;
+        }
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+        for declaration in scope.declarations {
+            let name = &declaration.name;
+            if declaration.used || name.lexeme.starts_with('_') {
+                continue;
+            }
+            let warning = if declaration.parameter {
+                UnusedParameter(name.lexeme.clone())
+            } else {
+                UnusedVariable(name.lexeme.clone())
+            };
+            self.warnings.push(warn(warning, name.line, name.span));
+        }
+    }
+}
+
+// conditions made of literals only, like 1 == 1
+fn is_constant(expression: &Expression) -> bool {
+    match expression {
+        Expression::Literal { .. } => true,
+        Expression::Grouping { expression, .. } => is_constant(expression),
+        Expression::Unary { right, .. } => is_constant(right),
+        Expression::Binary { left, right, .. } => is_constant(left) && is_constant(right),
+        _ => false,
+    }
+}
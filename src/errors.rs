@@ -1,4 +1,4 @@
-use crate::compiler::tokens::TokenType;
+use crate::compiler::tokens::{Span, TokenType};
 use std::fmt::Display;
 use thiserror::Error;
 
@@ -6,35 +6,146 @@ use thiserror::Error;
 pub enum TipiLangError {
     #[error("Compilation failed: {0}")]
     Compiler(#[from] CompilerErrorAtLine),
+    #[error("Compilation failed with {} error(s):\n{}", .0.len(), Diagnostic::render(.0))]
+    Diagnostics(Vec<Diagnostic>),
 
     #[error(transparent)]
     Runtime(#[from] RuntimeError),
     #[error("Platform error {0}")]
     Platform(String),
+    #[error("Bytecode error: {0}")]
+    Bytecode(String),
 }
 
 #[derive(Error, Debug, PartialEq)]
 pub struct CompilerErrorAtLine {
     pub error: CompilerError,
     pub line: usize,
+    pub span: Option<Span>,
 }
 
 impl CompilerErrorAtLine {
     pub(crate) fn raise(error: CompilerError, line: usize) -> Self {
-        Self { error, line }
+        Self {
+            error,
+            line,
+            span: None,
+        }
+    }
+
+    /// the part of the source the error points to
+    pub(crate) fn at(self, span: Span) -> Self {
+        Self {
+            span: Some(span),
+            ..self
+        }
+    }
+}
+
+impl From<Vec<CompilerErrorAtLine>> for TipiLangError {
+    fn from(mut errors: Vec<CompilerErrorAtLine>) -> Self {
+        if errors.len() == 1 {
+            TipiLangError::Compiler(errors.remove(0))
+        } else {
+            TipiLangError::Diagnostics(
+                errors.into_iter().map(|e| Diagnostic::new("", "", e)).collect(),
+            )
+        }
+    }
+}
+
+/// a compiler error in a source file, with the source line it points to
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub path: String,
+    pub error: CompilerErrorAtLine,
+    source_line: Option<String>,
+    // the number of chars to underline
+    width: usize,
+}
+
+impl Diagnostic {
+    pub fn new(path: &str, source: &str, error: CompilerErrorAtLine) -> Self {
+        let (source_line, width) = match error.span {
+            Some(span) if span.line > 0 => {
+                let (start, end) = (span.start as usize, span.end as usize);
+                let line = source.lines().nth(span.line as usize - 1);
+                let line_end = source[start.min(source.len())..]
+                    .find('\n')
+                    .map_or(source.len(), |i| start + i);
+                let width = source
+                    .get(start..end.min(line_end))
+                    .map_or(1, |s| s.chars().count().max(1));
+                (line.map(|l| l.trim_end().to_string()), width)
+            }
+            _ => (None, 0),
+        };
+        Self {
+            path: path.to_string(),
+            error,
+            source_line,
+            width,
+        }
+    }
+
+    fn render(diagnostics: &[Diagnostic]) -> String {
+        diagnostics
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// like rustc:
+// error: Cannot apply '*' to i64 and float
+//  --> source/a.tp:2:9
+//   |
+// 2 | let c = b * 2.5
+//   |         ^^^^^^^
+//   = help: convert one of the operands with 'as', like x as f64
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (Some(span), Some(source_line)) = (self.error.span, &self.source_line) else {
+            return if self.path.is_empty() {
+                write!(f, "{}", self.error)
+            } else {
+                write!(f, "{}: {}", self.path, self.error)
+            };
+        };
+        let gutter = " ".repeat(span.line.to_string().len());
+        let level = if self.error.error.is_warning() { "warning" } else { "error" };
+        writeln!(f, "{}: {}", level, self.error.error)?;
+        if self.path.is_empty() {
+            writeln!(f, "{}--> {}:{}", gutter, span.line, span.column)?;
+        } else {
+            writeln!(f, "{}--> {}:{}:{}", gutter, self.path, span.line, span.column)?;
+        }
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", span.line, source_line)?;
+        write!(
+            f,
+            "{} | {}{}",
+            gutter,
+            " ".repeat(span.column as usize - 1),
+            "^".repeat(self.width)
+        )?;
+        if let Some(help) = self.error.error.help() {
+            write!(f, "\n{} = help: {}", gutter, help)?;
+        }
+        Ok(())
     }
 }
 
 impl Display for CompilerErrorAtLine {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "error at line {}, {}", self.line, self.error)
+        let level = if self.error.is_warning() { "warning" } else { "error" };
+        write!(f, "{} at line {}, {}", level, self.line, self.error)
     }
 }
 
 #[derive(Error, Debug, PartialEq)]
 pub enum CompilerError {
-    #[error("Compilation failed")]
-    Failure,
     #[error("Too many parameters")]
     TooManyParameters,
     #[error("Expected {0}")]
@@ -47,8 +158,12 @@ pub enum CompilerError {
     UninitializedVariable,
     #[error("Expected {0}, found {1}")]
     IncompatibleTypes(TokenType, TokenType),
+    #[error("Cannot apply '{0}' to {1} and {2}")]
+    IncompatibleOperands(String, TokenType, TokenType),
     #[error("Error parsing number {0}")]
     ParseError(String),
+    #[error("Invalid {0} literal '{1}'")]
+    InvalidLiteral(TokenType, String),
     #[error("Undeclared variable: '{0}'")]
     UndeclaredVariable(String),
     #[error("Unexpected identifier")]
@@ -57,6 +172,12 @@ pub enum CompilerError {
     Unterminated(&'static str),
     #[error("Illegal char length for {0}")]
     IllegalCharLength(String),
+    #[error("Illegal escape sequence {0}")]
+    IllegalEscape(String),
+    #[error("Cannot convert {0} to {1}")]
+    IllegalConversion(TokenType, TokenType),
+    #[error("Only variables, list elements, map entries and object fields can be assigned to")]
+    IllegalAssignmentTarget,
     #[error("Unexpected type {0}")]
     UnexpectedType(TokenType),
     #[error("'{0}' is a keyword. You cannot use it as an identifier")]
@@ -69,15 +190,101 @@ pub enum CompilerError {
     IllegalIndexArgument(TokenType),
     #[error("Illegal argument: '{0}' cannot be indexed")]
     IllegalTypeToIndex(String),
+    #[error("Illegal argument: '{0}' cannot be iterated over")]
+    IllegalTypeToIterate(TokenType),
     #[error("The number of of arguments for {0} is not correct. Should be {1}, got {2}")]
     IllegalArgumentsException(String, usize, usize),
     #[error("Function name {0} is a global function and cannot be used here.")]
     ReservedFunctionName(String),
+    #[error("{0} cannot be transpiled to Rust yet")]
+    Unsupported(String),
+    #[error("{0}")]
+    Warning(CompilerWarning),
+    #[error("{0}")]
+    Denied(CompilerWarning),
+}
+
+impl CompilerError {
+    pub fn is_warning(&self) -> bool {
+        matches!(self, CompilerError::Warning(_))
+    }
+
+    /// a hint on how to fix the error, shown below the source
+    pub fn help(&self) -> Option<String> {
+        let help = match self {
+            // 'as' only converts numbers
+            CompilerError::IncompatibleOperands(_, left, right)
+                if left.is_numeric() && right.is_numeric() =>
+            {
+                Some("convert one of the operands with 'as', like x as f64")
+            }
+            CompilerError::IllegalConversion(..) => {
+                Some("'as' converts between numbers, and from char and bool to integers")
+            }
+            CompilerError::UninitializedVariable => Some("give the variable a value: let x = 0"),
+            CompilerError::IllegalIndexArgument(_) => Some("use an integer index"),
+            CompilerError::ReservedFunctionName(_) => Some("choose another name"),
+            CompilerError::KeywordNotAllowedAsIdentifier(_) => Some("choose another name"),
+            CompilerError::IllegalEscape(_) => {
+                Some("valid escapes are \\n \\t \\r \\0 \\\\ \\\" \\' and \\u{...}")
+            }
+            CompilerError::Warning(warning) => warning.help(),
+            CompilerError::Denied(warning) => {
+                return Some(format!("'{}' is set to deny in tipi.toml", warning.lint()));
+            }
+            _ => None,
+        };
+        help.map(str::to_string)
+    }
+}
+
+/// things that compile, but are probably not what was meant
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum CompilerWarning {
+    #[error("unused variable '{0}'")]
+    UnusedVariable(String),
+    #[error("unused parameter '{0}'")]
+    UnusedParameter(String),
+    #[error("'{0}' shadows an earlier declaration")]
+    Shadowing(String),
+    #[error("'{0}' is not an http method, so requests are never routed to it")]
+    UnroutableHandler(String),
+    #[error("object '{0}' is never used")]
+    UnusedObject(String),
+    #[error("the condition is constant, so one of the branches never runs")]
+    ConstantCondition,
+}
+
+impl CompilerWarning {
+    /// the name used to allow or deny the warning in tipi.toml
+    pub fn lint(&self) -> &'static str {
+        match self {
+            CompilerWarning::UnusedVariable(_) => "unused_variable",
+            CompilerWarning::UnusedParameter(_) => "unused_parameter",
+            CompilerWarning::Shadowing(_) => "shadowing",
+            CompilerWarning::UnroutableHandler(_) => "unroutable_handler",
+            CompilerWarning::UnusedObject(_) => "unused_object",
+            CompilerWarning::ConstantCondition => "constant_condition",
+        }
+    }
+
+    fn help(&self) -> Option<&'static str> {
+        match self {
+            CompilerWarning::UnusedVariable(_) | CompilerWarning::UnusedParameter(_) => {
+                Some("remove it, or start the name with an underscore")
+            }
+            CompilerWarning::Shadowing(_) => Some("choose another name"),
+            CompilerWarning::UnroutableHandler(_) => {
+                Some("name it after an http method, like get or post, or call it from one")
+            }
+            _ => None,
+        }
+    }
 }
 
 #[derive(Error, Debug, PartialEq)]
 pub enum RuntimeError {
-    #[error("Error while executing")]
+    #[error("Error while executing: {0}")]
     ValueError(#[from] ValueError),
     #[error("Error occurred")]
     Something,
@@ -89,10 +296,27 @@ pub enum RuntimeError {
     IllegalArgumentsException(String, usize, usize),
     #[error("{0}")]
     IllegalArgumentException(String),
+    /// a parameter of a request that is missing or cannot be parsed, a bad request
+    #[error("{0}")]
+    IllegalParameter(String),
     #[error("Expected {0}")]
     ExpectedType(String),
     #[error("Index out of bounds: {0} > {1}")]
     IndexOutOfBounds(usize, usize),
+    #[error("Key not found: {0}")]
+    KeyNotFound(String),
+    #[error("Cannot parse '{0}' as {1}")]
+    CannotParse(String, &'static str),
+    #[error("Overflow at line {0}")]
+    Overflow(usize),
+    #[error("Division by zero at line {0}")]
+    DivisionByZero(usize),
+    #[error("Cannot convert {0} to {1} without loss at line {2}")]
+    LossyConversion(String, TokenType, usize),
+    #[error("No element matches")]
+    NoMatch,
+    #[error("Cannot compare {0} and {1}")]
+    Incomparable(String, String),
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -103,4 +327,8 @@ pub enum ValueError {
     Some(&'static str),
     #[error("Illegal cast")]
     IllegalCast,
+    #[error("Overflow")]
+    Overflow,
+    #[error("Division by zero")]
+    DivisionByZero,
 }
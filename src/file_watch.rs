@@ -1,5 +1,4 @@
 use notify::{RecursiveMode, Watcher};
-use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
 use std::sync::mpsc::channel;
@@ -7,12 +6,12 @@ use std::thread;
 use std::time::{Duration, SystemTime};
 use arc_swap::ArcSwap;
 use log::info;
-use crate::compiler::assembly_pass::AsmChunk;
+use crate::AsmRegistry;
 use crate::compiler::compile_sourcedir;
 
 const ONE_SEC: Duration = Duration::from_secs(1);
 
-pub fn start_watch_daemon(source: &str, registry: Arc<ArcSwap<HashMap<String, AsmChunk>>>) {
+pub fn start_watch_daemon(source: &str, registry: Arc<ArcSwap<AsmRegistry>>) {
     let source = source.to_string();
     let s = source.to_string();
     let (tx, rx) = channel();
@@ -44,9 +43,11 @@ pub fn start_watch_daemon(source: &str, registry: Arc<ArcSwap<HashMap<String, As
                 }
             }
             info!("Change detected"); // TODO implement refresh source
-            let new_registry = Arc::new(compile_sourcedir(&s).unwrap());
-
-            registry.store(new_registry.clone());
+            // on errors the previous registry stays in place until the next change
+            match compile_sourcedir(&s) {
+                Ok(new_registry) => registry.store(Arc::new(new_registry)),
+                Err(e) => println!("{}", e),
+            }
             file_changed = false;
         }
     });
@@ -3,9 +3,13 @@ use crate::compiler::tokens::TokenType;
 pub(crate) fn get_keyword(lexeme: &str) -> Option<TokenType> {
     match lexeme {
         "and" => Some(TokenType::LogicalAnd),
+        "as" => Some(TokenType::As),
         "bool" => Some(TokenType::Bool),
         "char" => Some(TokenType::Char),
+        "date" => Some(TokenType::Date),
         "datetime" => Some(TokenType::DateTime),
+        "decimal" => Some(TokenType::Decimal),
+        "duration" => Some(TokenType::Duration),
         "else" => Some(TokenType::Else),
         "false" => Some(TokenType::False),
         "f32" => Some(TokenType::F32),
@@ -17,8 +21,8 @@ pub(crate) fn get_keyword(lexeme: &str) -> Option<TokenType> {
         "i32" => Some(TokenType::I32),
         "i64" => Some(TokenType::I64),
         "let" => Some(TokenType::Let),
-        "list" => Some(TokenType::ListType),
-        "map" => Some(TokenType::MapType),
+        "list" => Some(TokenType::untyped_list()),
+        "map" => Some(TokenType::untyped_map()),
         "or" => Some(TokenType::LogicalOr),
         "object" => Some(TokenType::Object),
         "print" => Some(TokenType::Print),
@@ -26,6 +30,7 @@ pub(crate) fn get_keyword(lexeme: &str) -> Option<TokenType> {
         "true" => Some(TokenType::True),
         "u32" => Some(TokenType::U32),
         "u64" => Some(TokenType::U64),
+        "uuid" => Some(TokenType::Uuid),
         "while" => Some(TokenType::While),
 
         _ => None,
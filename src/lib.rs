@@ -2,9 +2,11 @@ use crate::compiler::assembly_pass::AsmChunk;
 use crate::compiler::ast_pass::{Expression, Statement};
 use crate::errors::CompilerErrorAtLine;
 use crate::symbol_builder::Symbol;
+use indexmap::IndexMap;
 use std::collections::HashMap;
 
 mod builtins;
+pub mod bundle;
 pub mod compiler;
 pub mod errors;
 pub mod file_watch;
@@ -17,7 +19,9 @@ pub mod vm;
 pub(crate) type SymbolTable = HashMap<String, Symbol>;
 pub(crate) type Expr = Result<Expression, CompilerErrorAtLine>;
 pub(crate) type Stmt = Result<Statement, CompilerErrorAtLine>;
-pub(crate) type AsmRegistry = HashMap<String, AsmChunk>;
+/// the compiled chunks by name. The order is kept, so that linked calls can refer to a chunk by index
+pub type AsmRegistry = IndexMap<String, AsmChunk>;
 
 pub const TIPI_EXT: &str = ".tp";
-pub const DATE_FORMAT_TIMEZONE: &str = "%Y-%m-%d %H:%M:%S%.3f %z";
\ No newline at end of file
+pub const DATE_FORMAT_TIMEZONE: &str = "%Y-%m-%d %H:%M:%S%.3f %z";
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
\ No newline at end of file
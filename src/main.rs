@@ -1,16 +1,21 @@
 use axum::extract::{Request, State};
 use axum::http::StatusCode;
-use axum::routing::any;
+use axum::http::header::CONTENT_TYPE;
+use axum::routing::{any, get};
 use axum::{Json, Router};
-use clap::Parser;
-use tipi_lang::errors::TipiLangError;
+use clap::{Parser, Subcommand};
+use tipi_lang::errors::{RuntimeError, TipiLangError};
 use tipi_lang::vm::interpret_async;
 use std::collections::HashMap;
 use std::sync::Arc;
 use arc_swap::ArcSwap;
+use bytes::Bytes;
 use log::info;
-use tipi_lang::compiler::assembly_pass::AsmChunk;
-use tipi_lang::compiler::{compile_sourcedir, map_underlying};
+use tipi_lang::AsmRegistry;
+use tipi_lang::bundle;
+use tipi_lang::compiler::bytecode;
+use tipi_lang::compiler::disassembler::disassemble;
+use tipi_lang::compiler::{compile_sourcedir, map_underlying, transpile_sourcedir};
 
 /// A simple CLI tool to greet users
 #[derive(Parser, Debug)]
@@ -23,6 +28,37 @@ struct Args {
 
     #[arg(short, long)]
     watch: bool,
+
+    /// print the bytecode of every compiled function
+    #[arg(short, long)]
+    disassemble: bool,
+
+    #[command(subcommand)]
+    mode: Option<Mode>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Mode {
+    /// compile the sources into a bytecode file
+    Build {
+        #[arg(short, long, default_value = "app.tpc")]
+        output: String,
+    },
+    /// serve a bytecode file, without the sources
+    Run { file: String },
+    /// compile the sources into a copy of this executable, that serves them on its own
+    Bundle {
+        #[arg(short, long, default_value = "app")]
+        output: String,
+        /// a directory with static files, served by their path in it
+        #[arg(short, long)]
+        assets: Option<String>,
+    },
+    /// translate the sources into a rust crate with axum routes (experimental)
+    Transpile {
+        #[arg(short, long, default_value = "tipi-service")]
+        output: String,
+    },
 }
 
 #[tokio::main]
@@ -31,21 +67,73 @@ async fn main() -> Result<(), TipiLangError> {
     tracing_subscriber::fmt::init();
     let args = Args::parse();
     let source = args.source.unwrap_or("./source".to_string());
-    let registry = compile_sourcedir(&source)?;
+    // a bundled executable only serves what it was bundled with
+    let bundle = bundle::embedded()?;
+    let embedded = bundle.is_some();
+    let mut assets = HashMap::new();
+    let registry = match (bundle, &args.mode) {
+        (Some(bundle), _) => {
+            assets = bundle.assets;
+            bundle.registry
+        }
+        (None, Some(Mode::Build { output })) => {
+            bytecode::save(&or_exit(compile_sourcedir(&source)), output)?;
+            println!("-- Wrote {} --", output);
+            return Ok(());
+        }
+        (None, Some(Mode::Bundle { output, assets })) => {
+            let assets = match assets {
+                Some(dir) => bundle::read_assets(dir)?,
+                None => HashMap::new(),
+            };
+            bundle::write_executable(&or_exit(compile_sourcedir(&source)), &assets, output)?;
+            println!("-- Wrote {} --", output);
+            return Ok(());
+        }
+        (None, Some(Mode::Transpile { output })) => {
+            or_exit(transpile_sourcedir(&source)).write(output)?;
+            println!("-- Wrote {} --", output);
+            return Ok(());
+        }
+        (None, Some(Mode::Run { file })) => bytecode::load(file)?,
+        (None, None) => match compile_sourcedir(&source) {
+            Ok(registry) => registry,
+            Err(e @ TipiLangError::Diagnostics(_)) => {
+                println!("{}", e);
+                AsmRegistry::new()
+            }
+            Err(e) => return Err(e),
+        },
+    };
     let empty = registry.is_empty();
+    if args.disassemble {
+        let mut names: Vec<_> = registry.keys().collect();
+        names.sort();
+        for name in names {
+            println!("{}", disassemble(name, &registry[name]));
+        }
+    }
 
     let swap = Arc::new(ArcSwap::from(Arc::new(registry)));
     if !empty {
-        if args.watch {
+        // a bytecode file has no sources to watch
+        if args.watch && args.mode.is_none() && !embedded {
             tipi_lang::file_watch::start_watch_daemon(&source, swap.clone());
         }
         println!("-- Compilation successful --");
         let state =AppState {
             registry: swap.clone(),
         };
-        let app = Router::new()
+        let mut app = Router::new()
             .route("/", any(handle_any).with_state(state.clone()))
             .route("/{*path}", any(handle_any).with_state(state.clone()));
+        // static files of a bundle take precedence over the wildcard route
+        for (path, contents) in assets {
+            bundle::check_asset_path(&path)?;
+            let headers = [(CONTENT_TYPE, content_type(&path))];
+            let contents = Bytes::from(contents);
+            app = app.route(&path, get(move || async move { (headers, contents) }));
+        }
 
         let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
             .await
@@ -70,15 +158,26 @@ async fn main() -> Result<(), TipiLangError> {
     Ok(())
 }
 
+// errors are shown like run mode shows diagnostics, instead of as a debug dump, and fail the command
+fn or_exit<T>(result: Result<T, TipiLangError>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
 #[derive(Clone)]
 struct AppState {
-    registry: Arc<ArcSwap<HashMap<String, AsmChunk>>>,
+    registry: Arc<ArcSwap<AsmRegistry>>,
 }
 
 async fn handle_any(
     State(state): State<AppState>,
     req: Request,
-) -> Result<Json<String>, StatusCode> {
+) -> Result<Json<serde_json::Value>, StatusCode> {
     let method = req.method().to_string().to_ascii_lowercase();
     let uri = req.uri();
 
@@ -91,7 +190,7 @@ async fn handle_any(
                 .collect()
         })
         .unwrap_or_default();
-    let component = format!("{}/web", &uri.path());
+    let (component, path_param) = resolve_component(&state, uri.path(), &method);
     let function_qname = format!("{}/{}", component, method);
 
     let mut headers = HashMap::new();
@@ -104,12 +203,16 @@ async fn handle_any(
         state.registry.load(),
         &function_qname,
         path,
+        path_param.as_deref(),
         query_params,
         headers,
     )
     .await
     {
-        Ok(value) => Ok(Json(value.to_string())),
+        Ok(value) => Ok(Json(
+            serde_json::to_value(value).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        )),
+        Err(RuntimeError::IllegalParameter(_)) => Err(StatusCode::BAD_REQUEST),
         Err(_) => {
             // url checks out but function for method not found
             if state.registry.load().get(&format!("{}.main", component)).is_some() {
@@ -120,3 +223,34 @@ async fn handle_any(
         }
     }
 }
+
+// the media type of a static file, by its extension
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("ico") => "image/x-icon",
+        Some("txt") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// finds the web component for the path, either for the path itself,
+/// or for its parent, with the last path segment as parameter, like /api/customer/{id}
+fn resolve_component(state: &AppState, path: &str, method: &str) -> (String, Option<String>) {
+    let component = format!("{}/web", path);
+    if state.registry.load().contains_key(&format!("{}/{}", component, method)) {
+        return (component, None);
+    }
+    match path.trim_end_matches('/').rsplit_once('/') {
+        Some((parent, segment)) if !segment.is_empty() => {
+            (format!("{}/web", parent), Some(segment.to_string()))
+        }
+        _ => (component, None),
+    }
+}
@@ -1,7 +1,9 @@
-use crate::compiler::assembly_pass::AsmChunk;
+use crate::AsmRegistry;
+use crate::compiler::disassembler::disassemble;
+use crate::compiler::ir_pass::IrPass;
 use crate::compiler::scan_pass::scan;
-use crate::compiler::{assembly_pass, ast_pass, map_underlying};
-use crate::errors::TipiLangError;
+use crate::compiler::{assembly_pass, ast_pass, link_pass, map_underlying, optimize_pass};
+use crate::errors::{Diagnostic, TipiLangError};
 use crate::symbol_builder;
 use crate::vm::Vm;
 use arc_swap::ArcSwap;
@@ -11,12 +13,12 @@ use std::io::Write;
 use std::ops::Deref;
 use std::sync::Arc;
 
-pub fn start(registry: Arc<ArcSwap<HashMap<String, AsmChunk>>>) -> Result<(), TipiLangError> {
+pub fn start(registry: Arc<ArcSwap<AsmRegistry>>) -> Result<(), TipiLangError> {
     println!("REPL started -- Type ctrl-c to exit (both the repl and the server)");
     println!(":h for help");
     let mut symbol_table = HashMap::new();
     let mut vm = Vm::new(&registry.load());
-    let mut asm_pass = assembly_pass::AsmPass::new("main");
+    let mut ir_pass = IrPass::new("");
     loop {
         print!(">");
         io::stdout().flush().map_err(map_underlying())?;
@@ -29,6 +31,9 @@ pub fn start(registry: Arc<ArcSwap<HashMap<String, AsmChunk>>>) -> Result<(), Ti
             ":h" => help(),
             ":le" => list_endpoints(registry.load().clone()),
             ":lf" => list_functions(registry.load().clone()),
+            _ if input.starts_with(":d") => {
+                disassemble_function(registry.load().clone(), input[2..].trim())
+            }
             _ => {
                 let registry_copy = registry.load().clone();
                 let mut registry_copy = registry_copy.deref().clone();
@@ -37,26 +42,34 @@ pub fn start(registry: Arc<ArcSwap<HashMap<String, AsmChunk>>>) -> Result<(), Ti
 
                 let ast = match ast_pass::compile(None, tokens, &mut symbol_table) {
                     Ok(ast) => ast,
-                    Err(e) => {
-                        println!("{}", e);
+                    Err(errors) => {
+                        for e in errors {
+                            println!("{}", Diagnostic::new("", input, e));
+                        }
                         continue;
                     }
                 };
                 symbol_builder::build("", &ast, &mut symbol_table);
 
-                match asm_pass.compile(&ast, &symbol_table, &mut registry_copy, "") {
-                    Ok(chunk) => {
-                        registry_copy.insert("main".to_string(), chunk);
+                match ir_pass.lower("main", &ast, &symbol_table) {
+                    Ok(mut ir) => {
+                        optimize_pass::fold(&mut ir);
+                        assembly_pass::compile(&ir, &mut registry_copy);
+                        optimize_pass::peephole(&mut registry_copy);
+                        link_pass::link(&mut registry_copy);
                         registry.store(Arc::new(registry_copy));
 
-                        let result = match vm.run("main", registry.load().get("main").unwrap()) {
+                        // the functions of this line are only in the new registry
+                        let current = registry.load_full();
+                        vm.registry = current.clone();
+                        let result = match vm.run(&current["main"]) {
                             Ok(value) => value.to_string(),
                             Err(e) => e.to_string(),
                         };
                         println!("{}", result);
                     }
                     Err(e) => {
-                        println!("{}", e);
+                        println!("{}", Diagnostic::new("", input, e));
                     }
                 }
             }
@@ -68,7 +81,7 @@ pub fn start(registry: Arc<ArcSwap<HashMap<String, AsmChunk>>>) -> Result<(), Ti
     }
 }
 
-fn list_endpoints(registry: Arc<HashMap<String, AsmChunk>>) {
+fn list_endpoints(registry: Arc<AsmRegistry>) {
     registry
         .iter()
         .filter(|(k, _)| k.contains("get"))
@@ -77,13 +90,22 @@ fn list_endpoints(registry: Arc<HashMap<String, AsmChunk>>) {
         });
 }
 
-fn list_functions(registry: Arc<HashMap<String, AsmChunk>>) {
+fn list_functions(registry: Arc<AsmRegistry>) {
     registry.iter().for_each(|(k, _)| {
         println!("{}", k); //number
     });
 }
 
+fn disassemble_function(registry: Arc<AsmRegistry>, name: &str) {
+    let name = if name.is_empty() { "main" } else { name };
+    match registry.get(name) {
+        Some(chunk) => print!("{}", disassemble(name, chunk)),
+        None => println!("{} not found, :lf lists the functions", name),
+    }
+}
+
 fn help() {
     println!(":le\t lists all registered endpoints");
     println!(":lf\t lists all registered functions");
+    println!(":d [name]\t disassembles a function, or the last input");
 }
@@ -1,17 +1,18 @@
 use crate::compiler::ast_pass::{Expression, Parameter, Statement};
+use crate::builtins::globals::GLOBAL_FUNCTIONS;
 use crate::builtins::lookup;
 use crate::errors::CompilerError;
-use crate::errors::CompilerError::IncompatibleTypes;
+use crate::errors::CompilerError::{IncompatibleOperands, IncompatibleTypes};
 use crate::compiler::tokens::TokenType::{
-    Bool, DateTime, F32, F64, FloatingPoint, Greater, GreaterEqual, I32, I64, Integer, Less,
-    LessEqual, ListType, MapType, Minus, ObjectType, Plus, SignedInteger, StringType, U32, U64,
-    Unknown, UnsignedInteger,
+    Any, BangEqual, Bool, Char, Date, DateTime, Decimal, Duration, EqualEqual, F32, F64, FloatingPoint, FunctionType, Greater,
+    GreaterEqual, I32, I64, Integer, Less, LessEqual, ListType, MapType, Minus, ObjectType, Plus,
+    SignedInteger, StringType, U32, U64, Unknown, UnsignedInteger, Uuid,
 };
 use crate::compiler::tokens::{Token, TokenType};
 use log::debug;
 use std::collections::HashMap;
-use std::ops::Deref;
 
+#[derive(Clone)]
 pub enum Symbol {
     Function {
         name: String,
@@ -87,46 +88,191 @@ pub fn calculate_type(
                 (F32, FloatingPoint) => F32,
                 (F64, I64) => F64,
                 (F64, FloatingPoint) => F64,
+                (Decimal, Integer) => Decimal,
                 (U64, I64) => U64,
+                (U64, Integer) => U64,
                 (U64, I32) => U64,
                 (I64, Integer) => I64,
-                (StringType, _) => StringType, // meh, this all needs rigorous testing. Update: this is in progress
-                _ => {
-                    return Err(IncompatibleTypes(
-                        declared_type.clone(),
-                        inferred_type.clone(),
-                    ));
+                (I64, SignedInteger) => I64,
+                (I32, SignedInteger) => I32,
+                // a function value fits when it takes the same parameters and returns the same type
+                // `any` accepts all values, and the return type of a lambda may still be unknown
+                (
+                    FunctionType(declared_parameters, declared_return),
+                    FunctionType(parameters, return_type),
+                ) if declared_parameters.len() == parameters.len()
+                    && declared_parameters
+                        .iter()
+                        .zip(parameters)
+                        .all(|(declared, parameter)| {
+                            declared == parameter || declared == &Any || parameter == &Any
+                        })
+                    && (declared_return == return_type
+                        || **declared_return == Any
+                        || **return_type == Unknown) =>
+                {
+                    declared_type.clone()
                 }
+                (ListType(declared_element), ListType(inferred_element)) => ListType(Box::new(
+                    calculate_element_type(declared_element, inferred_element)
+                        .map_err(|_| incompatible(declared_type, inferred_type))?,
+                )),
+                (MapType(declared_key, declared_value), MapType(inferred_key, inferred_value)) => {
+                    MapType(
+                        Box::new(
+                            calculate_element_type(declared_key, inferred_key)
+                                .map_err(|_| incompatible(declared_type, inferred_type))?,
+                        ),
+                        Box::new(
+                            calculate_element_type(declared_value, inferred_value)
+                                .map_err(|_| incompatible(declared_type, inferred_type))?,
+                        ),
+                    )
+                }
+                _ => return Err(incompatible(declared_type, inferred_type)),
             }
         } else {
             declared_type.clone()
         }
     } else {
         match inferred_type {
-            Integer | I64 => I64,
-            FloatingPoint => F64,
-            Bool => Bool,
-            DateTime => DateTime,
-            ListType => ListType,
-            MapType => MapType,
+            Integer | SignedInteger | I64 => I64,
+            FloatingPoint | F64 => F64,
+            I32 | U32 | U64 | F32 | Decimal | StringType | Char | Bool | DateTime | Date | Duration
+            | Uuid => {
+                inferred_type.clone()
+            }
+            ListType(_) | MapType(_, _) | FunctionType(_, _) => inferred_type.clone(),
             ObjectType(p) => ObjectType(p.clone()),
             _ => return Err(CompilerError::UnexpectedType(inferred_type.clone())),
         }
     })
 }
 
-pub fn infer_type(expr: &Expression, symbols: &HashMap<String, Symbol>) -> TokenType {
-    match expr {
+/// checks the element (or key/value) type of a collection against its declaration
+/// `any` accepts all values and an empty literal (unknown element type) fits any declaration
+pub fn calculate_element_type(
+    declared_type: &TokenType,
+    inferred_type: &TokenType,
+) -> Result<TokenType, CompilerError> {
+    match (declared_type, inferred_type) {
+        (Any, _) => Ok(Any),
+        (_, Unknown) => Ok(declared_type.clone()),
+        (StringType, StringType) => Ok(StringType),
+        (StringType, _) | (_, Any) => Err(incompatible(declared_type, inferred_type)),
+        _ => calculate_type(declared_type, inferred_type),
+    }
+}
+
+fn incompatible(declared_type: &TokenType, inferred_type: &TokenType) -> CompilerError {
+    IncompatibleTypes(declared_type.clone(), inferred_type.clone())
+}
+
+/// the common type of all elements in a collection literal
+/// mixed element types result in `any`, an empty literal in `unknown`
+pub fn infer_element_type<'a>(
+    elements: impl Iterator<Item = &'a Expression>,
+    symbols: &HashMap<String, Symbol>,
+) -> Result<TokenType, CompilerError> {
+    let mut element_type = Unknown;
+    for element in elements {
+        let inferred_type = infer_type(element, symbols)?;
+        let inferred_type = calculate_type(&Unknown, &inferred_type).unwrap_or(Any);
+        if element_type == Unknown {
+            element_type = inferred_type;
+        } else if element_type != inferred_type {
+            return Ok(Any);
+        }
+    }
+    Ok(element_type)
+}
+
+/// the result type of date and time arithmetic
+fn temporal_type(
+    left_type: &TokenType,
+    operator: &TokenType,
+    right_type: &TokenType,
+) -> Option<TokenType> {
+    match (left_type, operator, right_type) {
+        (DateTime, Plus | Minus, Duration) => Some(DateTime),
+        (Date, Plus | Minus, Duration) => Some(Date),
+        (DateTime, Minus, DateTime) | (Date, Minus, Date) => Some(Duration),
+        _ => None,
+    }
+}
+
+/// the type of a single element taken from a collection of the given type
+fn element_type(collection_element_type: &TokenType) -> TokenType {
+    match collection_element_type {
+        Any => Unknown,
+        _ => collection_element_type.clone(),
+    }
+}
+
+// integers mix with decimals without losing precision
+/// the type both operands get when one of them is a number literal, that has no type of its own
+/// yet, like the 1 in x + 1. None when the operands are not numbers, or the literal does not fit
+pub fn literal_operand_type(left: &TokenType, right: &TokenType) -> Option<TokenType> {
+    let is_literal = |t: &TokenType| matches!(t, Integer | SignedInteger | FloatingPoint);
+    match (left, right) {
+        (FloatingPoint, right) if is_literal(right) => Some(F64),
+        (left, FloatingPoint) if is_literal(left) => Some(F64),
+        (left, right) if is_literal(left) && is_literal(right) => Some(I64),
+        (left, right) if is_literal(left) => literal_in(left, right),
+        (left, right) if is_literal(right) => literal_in(right, left),
+        _ => None,
+    }
+}
+
+// the numeric type a literal takes on
+fn literal_in(literal: &TokenType, numeric_type: &TokenType) -> Option<TokenType> {
+    match (literal, numeric_type) {
+        (Integer, I32 | I64 | U32 | U64 | F32 | F64 | Decimal) => Some(numeric_type.clone()),
+        // -1 does not fit an unsigned type
+        (SignedInteger, I32 | I64 | F32 | F64 | Decimal) => Some(numeric_type.clone()),
+        (FloatingPoint, F32 | F64) => Some(numeric_type.clone()),
+        _ => None,
+    }
+}
+
+fn is_integer(token_type: &TokenType) -> bool {
+    matches!(token_type, Integer | I32 | I64 | U32 | U64 | SignedInteger | UnsignedInteger)
+}
+
+/// the conversions `as` allows, whether a value fits is checked at runtime
+pub fn is_convertible(from: &TokenType, to: &TokenType) -> bool {
+    let is_number = |t: &TokenType| is_integer(t) || matches!(t, F32 | F64 | FloatingPoint | Decimal);
+    match (from, to) {
+        (from, to) if from == to => true,
+        (Any | Unknown, _) => true,
+        (from, to) if is_number(from) && is_number(to) => true,
+        (Char | Bool, to) => is_integer(to),
+        (from, Char) => is_integer(from),
+        _ => false,
+    }
+}
+
+pub fn infer_type(
+    expr: &Expression,
+    symbols: &HashMap<String, Symbol>,
+) -> Result<TokenType, CompilerError> {
+    Ok(match expr {
         Expression::Binary {
             left,
             operator,
             right,
             ..
         } => {
-            let left_type = infer_type(left, symbols);
-            let right_type = infer_type(right, symbols);
-            if [Greater, Less, GreaterEqual, LessEqual].contains(&operator.token_type) {
+            let left_type = infer_type(left, symbols)?;
+            let right_type = infer_type(right, symbols)?;
+            if [Greater, Less, GreaterEqual, LessEqual, EqualEqual, BangEqual]
+                .contains(&operator.token_type)
+            {
                 Bool
+            } else if let Some(temporal_type) =
+                temporal_type(&left_type, &operator.token_type, &right_type)
+            {
+                temporal_type
             } else if left_type == right_type {
                 // map to determined numeric type if yet undetermined (32 or 64 bits)
                 match left_type {
@@ -134,88 +280,233 @@ pub fn infer_type(expr: &Expression, symbols: &HashMap<String, Symbol>) -> Token
                     Integer => I64,
                     _ => left_type,
                 }
+            } else if let Some(numeric_type) = literal_operand_type(&left_type, &right_type) {
+                numeric_type
             } else if let Plus = operator.token_type {
                 // includes string concatenation with numbers
                 // followed by type coercion to 64 bits for numeric types
                 debug!("coerce {} : {}", left_type, right_type);
                 match (left_type, right_type) {
+                    (ListType(element), right_type) => {
+                        if calculate_element_type(&element, &right_type).is_ok() {
+                            ListType(element)
+                        } else {
+                            TokenType::untyped_list()
+                        }
+                    }
                     (_, StringType) => StringType,
                     (StringType, _) => StringType,
-                    (FloatingPoint, _) => F64,
-                    (Integer, FloatingPoint) => F64,
-                    (Integer, _) => I64,
-                    (I64, Integer) => I64,
-                    (F64, _) => F64,
+                    (Decimal, right_type) if is_integer(&right_type) => Decimal,
+                    (left_type, Decimal) if is_integer(&left_type) => Decimal,
                     (U64, U32) => U64,
                     (I64, I32) => I64,
                     // could add a date and a duration. future work
-                    // could add a List and a value. also future work
                     // could add a Map and a tuple. Will I add tuple types? Future work!
-                    _ => panic!("Unexpected coercion"),
+                    // only known at runtime
+                    (Unknown | Any, _) | (_, Unknown | Any) => Unknown,
+                    (left_type, right_type) => {
+                        return Err(IncompatibleOperands(
+                            Plus.to_string(),
+                            left_type,
+                            right_type,
+                        ));
+                    }
                 }
-                // could have done some fall through here, but this will fail less gracefully,
-                // so if my thinking is wrong or incomplete it will panic
             } else {
                 // type coercion to 64 bits for numeric types
                 debug!("coerce {} : {}", left_type, right_type);
                 match (left_type, right_type) {
-                    (FloatingPoint, _) => F64,
-                    (Integer, FloatingPoint) => F64,
-                    (Integer, I64) => I64,
-                    (I64, FloatingPoint) => F64,
-                    (F64, _) => F64,
+                    (Decimal, right_type) if is_integer(&right_type) => Decimal,
+                    (left_type, Decimal) if is_integer(&left_type) => Decimal,
                     (U64, U32) => U64,
                     (I64, I32) => I64,
-                    (I64, Integer) => I64,
-                    _ => panic!("Unexpected coercion"),
+                    (Unknown | Any, _) | (_, Unknown | Any) => Unknown,
+                    (left_type, right_type) => {
+                        return Err(IncompatibleOperands(
+                            operator.token_type.to_string(),
+                            left_type,
+                            right_type,
+                        ));
+                    }
                 }
             }
         }
-        Expression::Grouping { expression, .. } => infer_type(expression, symbols),
+        Expression::Grouping { expression, .. } => infer_type(expression, symbols)?,
         Expression::Literal { literaltype, .. } => literaltype.clone(),
         Expression::List { literaltype, .. } => literaltype.clone(),
         Expression::Map { literaltype, .. } => literaltype.clone(),
         Expression::Unary {
             right, operator, ..
         } => {
-            let literal_type = infer_type(right, symbols);
-            if literal_type == Integer && operator.token_type == Minus {
-                SignedInteger
-            } else {
-                UnsignedInteger
+            // -1 is a signed literal, that does not fit an unsigned type, and -x has the type of x
+            match (&operator.token_type, infer_type(right, symbols)?) {
+                (TokenType::Bang, _) => Bool,
+                (Minus, Integer) => SignedInteger,
+                (_, operand_type) => operand_type,
             }
         }
-        Expression::Variable { var_type, .. } => var_type.clone(),
-        Expression::Assignment { value, .. } => infer_type(value, symbols),
+        Expression::Variable { name, var_type, .. } => match (var_type, symbols.get(name)) {
+            // a named function used as a value
+            (Unknown, Some(Symbol::Function { parameters, return_type, .. })) => {
+                function_type(parameters, return_type)
+            }
+            _ => var_type.clone(),
+        },
+        Expression::Assignment { value, .. } => infer_type(value, symbols)?,
+        Expression::ElementAssignment { value, .. } => infer_type(value, symbols)?,
+        Expression::Cast { target_type, .. } => target_type.clone(),
         Expression::FunctionCall { name, .. } => {
             let symbol = symbols.get(name);
             match symbol {
                 Some(Symbol::Function { return_type, .. }) => return_type.clone(),
                 Some(Symbol::Object { name, .. }) => ObjectType(name.clone()),
-                _ => Unknown,
+                Some(Symbol::Variable {
+                    var_type: FunctionType(_, return_type),
+                    ..
+                }) => *return_type.clone(),
+                _ => GLOBAL_FUNCTIONS
+                    .get(name)
+                    .map(|function| function.return_type.clone())
+                    .unwrap_or(Unknown),
             }
         }
         Expression::MethodCall {
             receiver,
             method_name,
+            arguments,
             ..
         } => {
-            if let Expression::Literal { value, .. } = receiver.deref() {
-                if let Ok(signature) = lookup(&value.to_string(), method_name) {
-                    signature.return_type.clone()
+            let receiver_type = infer_type(receiver, symbols)?;
+            if let ListType(element) = &receiver_type
+                && let Some(list_type) =
+                    list_method_type(element, method_name, arguments, symbols)?
+            {
+                list_type
+            } else if let Ok(signature) = lookup(&receiver_type.erased().to_string(), method_name) {
+                // methods like list.push return the (generic) receiver type
+                if signature.return_type == receiver_type.erased() {
+                    receiver_type
                 } else {
-                    unreachable!() //?
+                    signature.return_type.clone()
                 }
             } else {
-                infer_type(receiver, symbols)
+                Unknown
             }
         }
+        Expression::ValueCall { function, .. } => match infer_type(function, symbols)? {
+            FunctionType(_, return_type) => *return_type,
+            _ => Unknown,
+        },
         Expression::Stop { .. } => TokenType::Unknown,
         // Expression::PathMatch { .. } => TokenType::Unknown,
         Expression::NamedParameter { .. } => TokenType::Unknown,
-        Expression::ListGet { .. } => TokenType::Unknown,
-        Expression::MapGet { .. } => TokenType::Unknown,
-        Expression::FieldGet { .. } => TokenType::Unknown,
-        Expression::Range { lower, .. } => infer_type(lower, symbols),
+        Expression::ListGet { list, .. } => match infer_type(list, symbols)? {
+            ListType(element) => element_type(&element),
+            StringType => Char,
+            _ => Unknown,
+        },
+        Expression::Slice { operand, .. } => infer_type(operand, symbols)?,
+        Expression::MapGet { map, .. } => match infer_type(map, symbols)? {
+            MapType(_, value) => element_type(&value),
+            _ => Unknown,
+        },
+        Expression::FieldGet { receiver, field, .. } => match infer_type(receiver, symbols)? {
+            ObjectType(object) => match symbols.get(&object) {
+                Some(Symbol::Object { fields, .. }) => fields
+                    .iter()
+                    .find(|f| f.name.lexeme == *field)
+                    .map(|f| f.var_type.clone())
+                    .unwrap_or(Unknown),
+                _ => Unknown,
+            },
+            _ => Unknown,
+        },
+        Expression::Range { lower, .. } => infer_type(lower, symbols)?,
+        Expression::Interpolation { .. } => StringType,
+        Expression::Lambda { function, .. } => {
+            function_type(&function.parameters, &function.return_type)
+        }
+    })
+}
+
+/// the result type of list methods that take a function, derived from that function
+fn list_method_type(
+    element: &TokenType,
+    method_name: &str,
+    arguments: &[Expression],
+    symbols: &HashMap<String, Symbol>,
+) -> Result<Option<TokenType>, CompilerError> {
+    let function_type = arguments.last().map(|f| infer_type(f, symbols)).transpose()?;
+    if let Some(function_type) = &function_type {
+        check_list_function(element, method_name, arguments, function_type, symbols)?;
     }
+    let function_return_type = match function_type {
+        Some(FunctionType(_, return_type)) if *return_type != Unknown => *return_type,
+        _ => Any,
+    };
+    Ok(Some(match method_name {
+        "map" => ListType(Box::new(function_return_type)),
+        "flat_map" => match function_return_type {
+            ListType(_) => function_return_type,
+            _ => TokenType::untyped_list(),
+        },
+        "group_by" => MapType(
+            Box::new(function_return_type),
+            Box::new(ListType(Box::new(element.clone()))),
+        ),
+        "find" => element_type(element),
+        "reduce" => match arguments.first() {
+            Some(initial) => match calculate_type(&Unknown, &infer_type(initial, symbols)?) {
+                Ok(initial_type) => initial_type,
+                Err(_) => return Ok(None),
+            },
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    }))
+}
+
+/// the function of a list method takes the elements, and returns a bool for the predicates
+fn check_list_function(
+    element: &TokenType,
+    method_name: &str,
+    arguments: &[Expression],
+    function_type: &TokenType,
+    symbols: &HashMap<String, Symbol>,
+) -> Result<(), CompilerError> {
+    let FunctionType(parameters, return_type) = function_type else {
+        return Ok(());
+    };
+    // the elements of a list with mixed types can be anything
+    let element = element_type(element);
+    let (expected_parameters, expected_return) = match method_name {
+        "filter" | "any" | "all" | "find" => (vec![element], Bool),
+        "map" | "flat_map" | "group_by" | "sort_by" => (vec![element], *return_type.clone()),
+        "reduce" => {
+            let initial = match arguments.first() {
+                Some(initial) => calculate_type(&Unknown, &infer_type(initial, symbols)?)?,
+                None => Unknown,
+            };
+            (vec![initial.clone(), element], initial)
+        }
+        _ => return Ok(()),
+    };
+    let fits = |declared: &TokenType, inferred: &TokenType| {
+        calculate_element_type(declared, inferred).is_ok()
+    };
+    if parameters.len() != expected_parameters.len()
+        || !parameters.iter().zip(&expected_parameters).all(|(p, e)| fits(p, e))
+        || !fits(&expected_return, return_type)
+    {
+        let expected = FunctionType(expected_parameters, Box::new(expected_return));
+        return Err(incompatible(&expected, function_type));
+    }
+    Ok(())
+}
+
+fn function_type(parameters: &[Parameter], return_type: &TokenType) -> TokenType {
+    FunctionType(
+        parameters.iter().map(|p| p.var_type.clone()).collect(),
+        Box::new(return_type.clone()),
+    )
 }
@@ -1,11 +1,20 @@
+use crate::compiler::tokens::TokenType;
 use crate::errors::ValueError;
-use chrono::{DateTime, Utc};
+use bytes::BytesMut;
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeDelta, Utc};
+use rust_decimal::Decimal;
+use rust_decimal::MathematicalOps;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use serde::{Serialize, Serializer};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
-use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Neg, Not, Shl, Shr, Sub};
-use crate::DATE_FORMAT_TIMEZONE;
+use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Neg, Not, Rem, Shl, Shr, Sub};
+use std::sync::Arc;
+use tokio_postgres::types::{FromSql, IsNull, ToSql, Type, to_sql_checked};
+use uuid::Uuid;
+use crate::{DATE_FORMAT, DATE_FORMAT_TIMEZONE};
 
 #[derive(Debug, Clone)]
 pub struct Object {
@@ -13,6 +22,14 @@ pub struct Object {
     pub(crate) fields: Vec<(String, Value)>,
 }
 
+/// a function value: a compiled function or lambda, with the values it captured
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure {
+    /// the registry key of the compiled function
+    pub(crate) name: String,
+    pub(crate) captured: Vec<Value>,
+}
+
 #[derive(Debug, Clone)]
 pub enum Value {
     U32(u32),
@@ -21,19 +38,25 @@ pub enum Value {
     I64(i64),
     F32(f32),
     F64(f64),
-    String(String),
+    Decimal(Decimal),
+    String(Arc<str>),
     Char(char),
     Bool(bool),
-    DateTime(Box<DateTime<Utc>>),
+    DateTime(Box<DateTime<FixedOffset>>),
+    Date(NaiveDate),
+    Duration(TimeDelta),
+    Uuid(Uuid),
     Enum,
-    List(Vec<Value>),
-    Map(HashMap<Value, Value>),
+    // collections are shared, and only copied when a shared one is changed
+    List(Arc<Vec<Value>>),
+    Map(Arc<HashMap<Value, Value>>),
     ObjectType(Box<Object>),
+    Function(Box<Closure>),
     Error(String),
     Void,
 }
 
-pub(crate) fn string(v: impl Into<String>) -> Value {
+pub(crate) fn string(v: impl Into<Arc<str>>) -> Value {
     Value::String(v.into())
 }
 
@@ -50,30 +73,6 @@ pub(crate) fn bool(v: impl Into<bool>) -> Value {
 }
 
 impl Value {
-    pub fn cast_u32(self) -> Result<Self, ValueError> {
-        match self {
-            Value::U32(v) => Ok(Value::U32(v)),
-            Value::U64(v) => Ok(Value::U32(v as u32)),
-            Value::I32(v) => Ok(Value::U32(v as u32)),
-            Value::I64(v) => Ok(Value::U32(v as u32)),
-            Value::F32(v) => Ok(Value::U32(v as u32)),
-            Value::F64(v) => Ok(Value::U32(v as u32)),
-            _ => Err(ValueError::IllegalCast),
-        }
-    }
-
-    pub fn cast_u64(self) -> Result<Self, ValueError> {
-        match self {
-            Value::U32(v) => Ok(Value::U64(v as u64)),
-            Value::U64(v) => Ok(Value::U64(v)),
-            Value::I32(v) => Ok(Value::U64(v as u64)),
-            Value::I64(v) => Ok(Value::U64(v as u64)),
-            Value::F32(v) => Ok(Value::U64(v as u64)),
-            Value::F64(v) => Ok(Value::U64(v as u64)),
-            _ => Err(ValueError::IllegalCast),
-        }
-    }
-
     pub fn cast_usize(self) -> Result<usize, ValueError> {
         match self {
             Value::U32(v) => Ok(v as usize),
@@ -86,27 +85,68 @@ impl Value {
         }
     }
 
-    pub fn cast_i32(self) -> Result<Self, ValueError> {
+    /// the `as` conversion, none when the value does not fit the target type exactly
+    pub fn convert(&self, target: &TokenType) -> Option<Value> {
+        let number = match self {
+            Value::I32(v) => Number::Integer(*v as i128),
+            Value::I64(v) => Number::Integer(*v as i128),
+            Value::U32(v) => Number::Integer(*v as i128),
+            Value::U64(v) => Number::Integer(*v as i128),
+            Value::Char(c) => Number::Integer(*c as i128),
+            Value::Bool(b) => Number::Integer(*b as i128),
+            Value::F32(v) => Number::Float(*v as f64),
+            Value::F64(v) => Number::Float(*v),
+            Value::Decimal(v) => Number::Decimal(*v),
+            _ => return None,
+        };
+        Some(match target {
+            TokenType::I32 => Value::I32(number.integer()?.try_into().ok()?),
+            TokenType::I64 => Value::I64(number.integer()?.try_into().ok()?),
+            TokenType::U32 => Value::U32(number.integer()?.try_into().ok()?),
+            TokenType::U64 => Value::U64(number.integer()?.try_into().ok()?),
+            TokenType::Char => Value::Char(char::from_u32(number.integer()?.try_into().ok()?)?),
+            TokenType::F64 => Value::F64(number.float()?),
+            // f32 loses precision like any float does, but not its range
+            TokenType::F32 => {
+                let float = number.float()?;
+                if float.is_finite() && float.abs() > f32::MAX as f64 {
+                    return None;
+                }
+                Value::F32(float as f32)
+            }
+            TokenType::Decimal => Value::Decimal(match number {
+                Number::Integer(i) => Decimal::try_from_i128_with_scale(i, 0).ok()?,
+                Number::Float(f) => Decimal::from_f64(f)?,
+                Number::Decimal(d) => d,
+            }),
+            _ => return None,
+        })
+    }
+}
+
+enum Number {
+    Integer(i128),
+    Float(f64),
+    Decimal(Decimal),
+}
+
+impl Number {
+    // floats and decimals only convert to integers when they have no fraction
+    fn integer(&self) -> Option<i128> {
         match self {
-            Value::U32(v) => Ok(Value::I32(v as i32)),
-            Value::U64(v) => Ok(Value::I32(v as i32)),
-            Value::I32(v) => Ok(Value::I32(v)),
-            Value::I64(v) => Ok(Value::I32(v as i32)),
-            Value::F32(v) => Ok(Value::I32(v as i32)),
-            Value::F64(v) => Ok(Value::I32(v as i32)),
-            _ => Err(ValueError::IllegalCast),
+            Number::Integer(i) => Some(*i),
+            Number::Float(f) if f.is_finite() && f.fract() == 0.0 => Some(*f as i128),
+            Number::Decimal(d) if d.fract().is_zero() => d.to_i128(),
+            _ => None,
         }
     }
 
-    pub fn cast_f32(self) -> Result<Self, ValueError> {
+    // integers beyond 2^53 cannot be represented exactly
+    fn float(&self) -> Option<f64> {
         match self {
-            Value::U32(v) => Ok(Value::F32(v as f32)),
-            Value::U64(v) => Ok(Value::F32(v as f32)),
-            Value::I32(v) => Ok(Value::F32(v as f32)),
-            Value::I64(v) => Ok(Value::F32(v as f32)),
-            Value::F32(v) => Ok(Value::F32(v)),
-            Value::F64(v) => Ok(Value::F32(v as f32)),
-            _ => Err(ValueError::IllegalCast),
+            Number::Integer(i) => Some(*i as f64).filter(|f| *f as i128 == *i),
+            Number::Float(f) => Some(*f),
+            Number::Decimal(d) => d.to_f64(),
         }
     }
 }
@@ -145,15 +185,21 @@ impl From<f64> for Value {
     }
 }
 
+impl From<Decimal> for Value {
+    fn from(v: Decimal) -> Value {
+        Value::Decimal(v)
+    }
+}
+
 impl From<&str> for Value {
     fn from(v: &str) -> Value {
-        Value::String(v.to_string())
+        Value::String(v.into())
     }
 }
 
 impl From<String> for Value {
     fn from(v: String) -> Value {
-        Value::String(v)
+        Value::String(v.into())
     }
 }
 
@@ -171,10 +217,34 @@ impl From<bool> for Value {
 
 impl From<DateTime<Utc>> for Value {
     fn from(v: DateTime<Utc>) -> Value {
+        Value::DateTime(Box::new(v.fixed_offset()))
+    }
+}
+
+impl From<DateTime<FixedOffset>> for Value {
+    fn from(v: DateTime<FixedOffset>) -> Value {
         Value::DateTime(Box::new(v))
     }
 }
 
+impl From<NaiveDate> for Value {
+    fn from(v: NaiveDate) -> Value {
+        Value::Date(v)
+    }
+}
+
+impl From<TimeDelta> for Value {
+    fn from(v: TimeDelta) -> Value {
+        Value::Duration(v)
+    }
+}
+
+impl From<Uuid> for Value {
+    fn from(v: Uuid) -> Value {
+        Value::Uuid(v)
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match &self {
@@ -186,11 +256,35 @@ impl Display for Value {
             Value::I64(v) => write!(f, "{}", v),
             Value::F32(v) => write!(f, "{}", v),
             Value::F64(v) => write!(f, "{}", v),
+            Value::Decimal(v) => write!(f, "{}", v),
             Value::Char(v) => write!(f, "{}", v),
             Value::DateTime(v) => write!(f, "{}", v.format(DATE_FORMAT_TIMEZONE)),
+            Value::Date(v) => write!(f, "{}", v.format(DATE_FORMAT)),
+            Value::Duration(v) => write!(f, "{}", v),
+            Value::Uuid(v) => write!(f, "{}", v),
             Value::Enum => write!(f, "enum"),
-            Value::ObjectType(o) => write!(f, "{}: {:?}", o.definition, o.fields),
-            Value::List(v) => write!(f, "{:?}", v),
+            Value::ObjectType(o) => {
+                write!(f, "{}(", o.definition)?;
+                for (i, (name, value)) in o.fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}: ", name)?;
+                    write_element(f, value)?;
+                }
+                f.write_str(")")
+            }
+            Value::Function(closure) => write!(f, "fn {}", closure.name),
+            Value::List(list) => {
+                f.write_str("[")?;
+                for (i, element) in list.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write_element(f, element)?;
+                }
+                f.write_str("]")
+            }
             Value::Map(map) => to_string(f, map),
             Value::Error(v) => write!(f, "{}", v),
             Value::Void => write!(f, "()"),
@@ -205,23 +299,99 @@ fn to_string(f: &mut Formatter, map: &HashMap<Value, Value>) -> std::fmt::Result
         if !first {
             f.write_str(", ")?;
         }
-        f.write_str(&k.to_string())?;
+        write_element(f, k)?;
         f.write_str(": ")?;
-        f.write_str(&v.to_string())?;
+        write_element(f, v)?;
         first = false;
     }
     f.write_str("}")
 }
 
+// an element of a list or map, with strings and chars quoted like literals
+fn write_element(f: &mut Formatter, value: &Value) -> std::fmt::Result {
+    match value {
+        Value::String(s) => write!(f, "{:?}", s),
+        Value::Char(c) => write!(f, "{:?}", c),
+        value => write!(f, "{}", value),
+    }
+}
+
+// decimal arithmetic is exact, integers are widened to decimals
+fn decimal_operands(left: &Value, right: &Value) -> Option<(Decimal, Decimal)> {
+    match (left, right) {
+        (Value::Decimal(a), Value::Decimal(b)) => Some((*a, *b)),
+        (Value::Decimal(a), b) => Some((*a, decimal(b)?)),
+        (a, Value::Decimal(b)) => Some((decimal(a)?, *b)),
+        _ => None,
+    }
+}
+
+fn decimal(value: &Value) -> Option<Decimal> {
+    match value {
+        Value::I32(v) => Some((*v).into()),
+        Value::I64(v) => Some((*v).into()),
+        Value::U32(v) => Some((*v).into()),
+        Value::U64(v) => Some((*v).into()),
+        _ => None,
+    }
+}
+
+// the value of an integer, or of a decimal without a fraction
+fn integral(value: &Value) -> Option<i128> {
+    match value {
+        Value::I32(v) => Some(*v as i128),
+        Value::I64(v) => Some(*v as i128),
+        Value::U32(v) => Some(*v as i128),
+        Value::U64(v) => Some(*v as i128),
+        Value::Decimal(d) if d.fract().is_zero() => d.to_i128(),
+        _ => None,
+    }
+}
+
+fn decimal_result(result: Option<Decimal>) -> Result<Value, ValueError> {
+    result
+        .map(Value::Decimal)
+        .ok_or(ValueError::Overflow)
+}
+
+// integer arithmetic never wraps silently
+fn checked<T>(result: Option<T>, variant: fn(T) -> Value) -> Result<Value, ValueError> {
+    result.map(variant).ok_or(ValueError::Overflow)
+}
+
+fn divided<T>(
+    result: Option<T>,
+    by_zero: bool,
+    variant: fn(T) -> Value,
+) -> Result<Value, ValueError> {
+    if by_zero {
+        return Err(ValueError::DivisionByZero);
+    }
+    checked(result, variant)
+}
+
+fn shift<T: TryInto<u32>>(bits: T) -> Result<u32, ValueError> {
+    bits.try_into().map_err(|_| ValueError::Overflow)
+}
+
+// bits that are shifted out overflow, like the bits of a multiplication by a power of 2
+fn shifted_left<T>(a: T, bits: u32, shl: fn(T, u32) -> Option<T>) -> Option<T>
+where
+    T: Copy + PartialEq + Shr<u32, Output = T>,
+{
+    shl(a, bits).filter(|shifted| *shifted >> bits == a)
+}
+
 impl Neg for &Value {
     type Output = Result<Value, ValueError>;
 
     fn neg(self) -> Self::Output {
         match self {
-            Value::I32(i) => Ok(Value::I32(-i)),
-            Value::I64(i) => Ok(Value::I64(-i)),
+            Value::I32(i) => checked(i.checked_neg(), Value::I32),
+            Value::I64(i) => checked(i.checked_neg(), Value::I64),
             Value::F32(i) => Ok(Value::F32(-i)),
             Value::F64(i) => Ok(Value::F64(-i)),
+            Value::Decimal(i) => Ok(Value::Decimal(-i)),
             _ => Err(ValueError::Some("Cannot negate")),
         }
     }
@@ -231,37 +401,53 @@ impl Add<&Value> for &Value {
     type Output = Result<Value, ValueError>;
 
     fn add(self, rhs: &Value) -> Self::Output {
+        if let Some((a, b)) = decimal_operands(self, rhs) {
+            return decimal_result(a.checked_add(b));
+        }
         if let Value::List(s) = self {
             let mut copy = s.clone();
-            copy.push(rhs.clone());
+            Arc::make_mut(&mut copy).push(rhs.clone());
             Ok(Value::List(copy))
         } else if let Value::List(rhs) = rhs {
             let mut copy = rhs.clone();
-            copy.push(self.clone());
+            Arc::make_mut(&mut copy).push(self.clone());
             Ok(Value::List(copy))
         } else {
             match (self, rhs) {
-                (Value::I32(a), Value::I32(b)) => Ok(Value::I32(a + b)),
-                (Value::I64(a), Value::I64(b)) => Ok(Value::I64(a + b)),
-                (Value::U32(a), Value::U32(b)) => Ok(Value::U32(a + b)),
-                (Value::U64(a), Value::U64(b)) => Ok(Value::U64(a + b)),
+                (Value::I32(a), Value::I32(b)) => checked(a.checked_add(*b), Value::I32),
+                (Value::I64(a), Value::I64(b)) => checked(a.checked_add(*b), Value::I64),
+                (Value::U32(a), Value::U32(b)) => checked(a.checked_add(*b), Value::U32),
+                (Value::U64(a), Value::U64(b)) => checked(a.checked_add(*b), Value::U64),
                 (Value::F32(a), Value::F32(b)) => Ok(Value::F32(a + b)),
                 (Value::F64(a), Value::F64(b)) => Ok(Value::F64(a + b)),
+                (Value::DateTime(a), Value::Duration(b)) => a
+                    .checked_add_signed(*b)
+                    .map(Value::from)
+                    .ok_or(ValueError::Some("Datetime out of range")),
+                (Value::Date(a), Value::Duration(b)) => a
+                    .checked_add_signed(*b)
+                    .map(Value::Date)
+                    .ok_or(ValueError::Some("Date out of range")),
+                (Value::Duration(a), Value::Duration(b)) => a
+                    .checked_add(b)
+                    .map(Value::Duration)
+                    .ok_or(ValueError::Some("Duration out of range")),
                 (Value::String(s), Value::I32(i)) => Ok(format!("{}{}", s, i).into()),
                 (Value::String(s), Value::I64(i)) => Ok(format!("{}{}", s, i).into()),
-                (Value::String(s), Value::U32(u)) => Ok(Value::String(format!("{}{}", s, u))),
-                (Value::String(s), Value::U64(u)) => Ok(Value::String(format!("{}{}", s, u))),
-                (Value::String(s), Value::F32(f)) => Ok(Value::String(format!("{}{}", s, f))),
-                (Value::String(s), Value::F64(f)) => Ok(Value::String(format!("{}{}", s, f))),
-                (Value::String(s), Value::Bool(b)) => Ok(Value::String(format!("{}{}", s, b))),
-                (Value::String(s), Value::Char(c)) => Ok(Value::String(format!("{}{}", s, c))),
+                (Value::String(s), Value::U32(u)) => Ok(string(format!("{}{}", s, u))),
+                (Value::String(s), Value::U64(u)) => Ok(string(format!("{}{}", s, u))),
+                (Value::String(s), Value::F32(f)) => Ok(string(format!("{}{}", s, f))),
+                (Value::String(s), Value::F64(f)) => Ok(string(format!("{}{}", s, f))),
+                (Value::String(s), Value::Decimal(d)) => Ok(string(format!("{}{}", s, d))),
+                (Value::String(s), Value::Bool(b)) => Ok(string(format!("{}{}", s, b))),
+                (Value::String(s), Value::Char(c)) => Ok(string(format!("{}{}", s, c))),
                 (Value::String(s1), Value::String(s2)) => {
                     let mut s = String::with_capacity(s1.len() + s2.len());
-                    s.push_str(s1.as_str());
-                    s.push_str(s2.as_str());
-                    Ok(Value::String(s))
+                    s.push_str(s1);
+                    s.push_str(s2);
+                    Ok(Value::String(s.into()))
                 }
-                (Value::String(s1), Value::Map(m)) => Ok(Value::String(format!("{}{:?}", s1, m))),
+                (Value::String(s1), map @ Value::Map(_)) => Ok(string(format!("{}{}", s1, map))),
                 //enum?
                 _ => Err(ValueError::Some("Cannot add")),
             }
@@ -273,13 +459,32 @@ impl Sub<&Value> for &Value {
     type Output = Result<Value, ValueError>;
 
     fn sub(self, rhs: &Value) -> Self::Output {
+        if let Some((a, b)) = decimal_operands(self, rhs) {
+            return decimal_result(a.checked_sub(b));
+        }
         match (self, rhs) {
-            (Value::I32(a), Value::I32(b)) => Ok(Value::I32(a - b)),
-            (Value::I64(a), Value::I64(b)) => Ok(Value::I64(a - b)),
-            (Value::U32(a), Value::U32(b)) => Ok(Value::U32(a - b)),
-            (Value::U64(a), Value::U64(b)) => Ok(Value::U64(a - b)),
+            (Value::I32(a), Value::I32(b)) => checked(a.checked_sub(*b), Value::I32),
+            (Value::I64(a), Value::I64(b)) => checked(a.checked_sub(*b), Value::I64),
+            (Value::U32(a), Value::U32(b)) => checked(a.checked_sub(*b), Value::U32),
+            (Value::U64(a), Value::U64(b)) => checked(a.checked_sub(*b), Value::U64),
             (Value::F32(a), Value::F32(b)) => Ok(Value::F32(a - b)),
             (Value::F64(a), Value::F64(b)) => Ok(Value::F64(a - b)),
+            (Value::DateTime(a), Value::Duration(b)) => a
+                .checked_sub_signed(*b)
+                .map(Value::from)
+                .ok_or(ValueError::Some("Datetime out of range")),
+            (Value::DateTime(a), Value::DateTime(b)) => {
+                Ok(Value::Duration(a.signed_duration_since(**b)))
+            }
+            (Value::Date(a), Value::Duration(b)) => a
+                .checked_sub_signed(*b)
+                .map(Value::Date)
+                .ok_or(ValueError::Some("Date out of range")),
+            (Value::Date(a), Value::Date(b)) => Ok(Value::Duration(a.signed_duration_since(*b))),
+            (Value::Duration(a), Value::Duration(b)) => a
+                .checked_sub(b)
+                .map(Value::Duration)
+                .ok_or(ValueError::Some("Duration out of range")),
             //enum?
             _ => Err(ValueError::Some("Cannot subtract")),
         }
@@ -290,11 +495,14 @@ impl Mul<&Value> for &Value {
     type Output = Result<Value, ValueError>;
 
     fn mul(self, rhs: &Value) -> Self::Output {
+        if let Some((a, b)) = decimal_operands(self, rhs) {
+            return decimal_result(a.checked_mul(b));
+        }
         match (self, rhs) {
-            (Value::I32(a), Value::I32(b)) => Ok(Value::I32(a * b)),
-            (Value::I64(a), Value::I64(b)) => Ok(Value::I64(a * b)),
-            (Value::U32(a), Value::U32(b)) => Ok(Value::U32(a * b)),
-            (Value::U64(a), Value::U64(b)) => Ok(Value::U64(a * b)),
+            (Value::I32(a), Value::I32(b)) => checked(a.checked_mul(*b), Value::I32),
+            (Value::I64(a), Value::I64(b)) => checked(a.checked_mul(*b), Value::I64),
+            (Value::U32(a), Value::U32(b)) => checked(a.checked_mul(*b), Value::U32),
+            (Value::U64(a), Value::U64(b)) => checked(a.checked_mul(*b), Value::U64),
             (Value::F32(a), Value::F32(b)) => Ok(Value::F32(a * b)),
             (Value::F64(a), Value::F64(b)) => Ok(Value::F64(a * b)),
             _ => Err(ValueError::Some("Cannot multiply")),
@@ -306,11 +514,17 @@ impl Div<&Value> for &Value {
     type Output = Result<Value, ValueError>;
 
     fn div(self, rhs: &Value) -> Self::Output {
+        if let Some((a, b)) = decimal_operands(self, rhs) {
+            if b.is_zero() {
+                return Err(ValueError::DivisionByZero);
+            }
+            return decimal_result(a.checked_div(b));
+        }
         match (self, rhs) {
-            (Value::I32(a), Value::I32(b)) => Ok(Value::I32(a / b)),
-            (Value::I64(a), Value::I64(b)) => Ok(Value::I64(a / b)),
-            (Value::U32(a), Value::U32(b)) => Ok(Value::U32(a / b)),
-            (Value::U64(a), Value::U64(b)) => Ok(Value::U64(a / b)),
+            (Value::I32(a), Value::I32(b)) => divided(a.checked_div(*b), *b == 0, Value::I32),
+            (Value::I64(a), Value::I64(b)) => divided(a.checked_div(*b), *b == 0, Value::I64),
+            (Value::U32(a), Value::U32(b)) => divided(a.checked_div(*b), *b == 0, Value::U32),
+            (Value::U64(a), Value::U64(b)) => divided(a.checked_div(*b), *b == 0, Value::U64),
             (Value::F32(a), Value::F32(b)) => Ok(Value::F32(a / b)),
             (Value::F64(a), Value::F64(b)) => Ok(Value::F64(a / b)),
             _ => Err(ValueError::Some("Cannot divide")),
@@ -318,6 +532,62 @@ impl Div<&Value> for &Value {
     }
 }
 
+impl Rem<&Value> for &Value {
+    type Output = Result<Value, ValueError>;
+
+    fn rem(self, rhs: &Value) -> Self::Output {
+        if let Some((a, b)) = decimal_operands(self, rhs) {
+            if b.is_zero() {
+                return Err(ValueError::DivisionByZero);
+            }
+            return decimal_result(a.checked_rem(b));
+        }
+        match (self, rhs) {
+            (Value::I32(a), Value::I32(b)) => divided(a.checked_rem(*b), *b == 0, Value::I32),
+            (Value::I64(a), Value::I64(b)) => divided(a.checked_rem(*b), *b == 0, Value::I64),
+            (Value::U32(a), Value::U32(b)) => divided(a.checked_rem(*b), *b == 0, Value::U32),
+            (Value::U64(a), Value::U64(b)) => divided(a.checked_rem(*b), *b == 0, Value::U64),
+            (Value::F32(a), Value::F32(b)) => Ok(Value::F32(a % b)),
+            (Value::F64(a), Value::F64(b)) => Ok(Value::F64(a % b)),
+            _ => Err(ValueError::Some("Cannot take the remainder")),
+        }
+    }
+}
+
+impl Value {
+    /// integer exponents must fit in a u32, floats raise to any power
+    pub fn pow(&self, rhs: &Value) -> Result<Value, ValueError> {
+        fn exponent<T: TryInto<u32>>(e: T) -> Result<u32, ValueError> {
+            e.try_into().map_err(|_| ValueError::Some("Illegal exponent"))
+        }
+        match (self, rhs) {
+            (Value::I32(a), Value::I32(b)) => {
+                checked(a.checked_pow(exponent(*b)?), Value::I32)
+            }
+            (Value::I64(a), Value::I64(b)) => {
+                checked(a.checked_pow(exponent(*b)?), Value::I64)
+            }
+            (Value::U32(a), Value::U32(b)) => checked(a.checked_pow(*b), Value::U32),
+            (Value::U64(a), Value::U64(b)) => {
+                checked(a.checked_pow(exponent(*b)?), Value::U64)
+            }
+            (Value::F32(a), Value::F32(b)) => Ok(Value::F32(a.powf(*b))),
+            (Value::F64(a), Value::F64(b)) => Ok(Value::F64(a.powf(*b))),
+            // the exponent of a decimal is a whole number, so that the result stays exact
+            (Value::Decimal(_), _) | (_, Value::Decimal(_)) => {
+                let (base, exponent) = decimal_operands(self, rhs)
+                    .ok_or(ValueError::Some("Cannot raise to a power"))?;
+                let exponent = Some(exponent)
+                    .filter(|e| e.fract().is_zero())
+                    .and_then(|e| e.to_i64())
+                    .ok_or(ValueError::Some("Illegal exponent"))?;
+                decimal_result(base.checked_powi(exponent))
+            }
+            _ => Err(ValueError::Some("Cannot raise to a power")),
+        }
+    }
+}
+
 impl BitAnd<&Value> for &Value {
     type Output = Result<Value, ValueError>;
     fn bitand(self, rhs: &Value) -> Self::Output {
@@ -376,10 +646,18 @@ impl Shl<&Value> for &Value {
     type Output = Result<Value, ValueError>;
     fn shl(self, rhs: &Value) -> Self::Output {
         match (self, rhs) {
-            (Value::I32(a), Value::I32(b)) => Ok(Value::I32(a << b)),
-            (Value::I64(a), Value::I64(b)) => Ok(Value::I64(a << b)),
-            (Value::U32(a), Value::U32(b)) => Ok(Value::U32(a << b)),
-            (Value::U64(a), Value::U64(b)) => Ok(Value::U64(a << b)),
+            (Value::I32(a), Value::I32(b)) => {
+                checked(shifted_left(*a, shift(*b)?, i32::checked_shl), Value::I32)
+            }
+            (Value::I64(a), Value::I64(b)) => {
+                checked(shifted_left(*a, shift(*b)?, i64::checked_shl), Value::I64)
+            }
+            (Value::U32(a), Value::U32(b)) => {
+                checked(shifted_left(*a, shift(*b)?, u32::checked_shl), Value::U32)
+            }
+            (Value::U64(a), Value::U64(b)) => {
+                checked(shifted_left(*a, shift(*b)?, u64::checked_shl), Value::U64)
+            }
             _ => Err(ValueError::Some("Cannot shift left on")),
         }
     }
@@ -389,10 +667,10 @@ impl Shr<&Value> for &Value {
     type Output = Result<Value, ValueError>;
     fn shr(self, rhs: &Value) -> Self::Output {
         match (self, rhs) {
-            (Value::I32(a), Value::I32(b)) => Ok(Value::I32(a >> b)),
-            (Value::I64(a), Value::I64(b)) => Ok(Value::I64(a >> b)),
-            (Value::U32(a), Value::U32(b)) => Ok(Value::U32(a >> b)),
-            (Value::U64(a), Value::U64(b)) => Ok(Value::U64(a >> b)),
+            (Value::I32(a), Value::I32(b)) => checked(a.checked_shr(shift(*b)?), Value::I32),
+            (Value::I64(a), Value::I64(b)) => checked(a.checked_shr(shift(*b)?), Value::I64),
+            (Value::U32(a), Value::U32(b)) => checked(a.checked_shr(shift(*b)?), Value::U32),
+            (Value::U64(a), Value::U64(b)) => checked(a.checked_shr(shift(*b)?), Value::U64),
             _ => Err(ValueError::Some("Cannot shift right on")),
         }
     }
@@ -400,6 +678,10 @@ impl Shr<&Value> for &Value {
 
 impl PartialEq for Value {
     fn eq(&self, rhs: &Self) -> bool {
+        // a decimal is compared to an integer as a decimal, like in arithmetic
+        if let Some((a, b)) = decimal_operands(self, rhs) {
+            return a == b;
+        }
         match (self, rhs) {
             (Value::I32(a), Value::I32(b)) => a == b,
             (Value::I64(a), Value::I64(b)) => a == b,
@@ -407,11 +689,16 @@ impl PartialEq for Value {
             (Value::U64(a), Value::U64(b)) => a == b,
             (Value::F32(a), Value::F32(b)) => a == b,
             (Value::F64(a), Value::F64(b)) => a == b,
+            (Value::Decimal(a), Value::Decimal(b)) => a == b,
             (Value::Bool(a), Value::Bool(b)) => a == b,
             (Value::String(a), Value::String(b)) => a == b,
             (Value::Char(a), Value::Char(b)) => a == b,
             (Value::DateTime(a), Value::DateTime(b)) => a == b,
+            (Value::Date(a), Value::Date(b)) => a == b,
+            (Value::Duration(a), Value::Duration(b)) => a == b,
+            (Value::Uuid(a), Value::Uuid(b)) => a == b,
             (Value::List(a), Value::List(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => a == b,
             (Value::Map(a), Value::Map(b)) => {
                 let mut equal = true;
                 for (k, v) in a.iter() {
@@ -434,6 +721,9 @@ impl Eq for Value {}
 
 impl PartialOrd for Value {
     fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> {
+        if let Some((a, b)) = decimal_operands(self, rhs) {
+            return a.partial_cmp(&b);
+        }
         match (self, rhs) {
             (Value::I32(a), Value::I32(b)) => Some(a.partial_cmp(b)?),
             (Value::I64(a), Value::I64(b)) => Some(a.partial_cmp(b)?),
@@ -441,9 +731,13 @@ impl PartialOrd for Value {
             (Value::U64(a), Value::U64(b)) => Some(a.partial_cmp(b)?),
             (Value::F32(a), Value::F32(b)) => Some(a.partial_cmp(b)?),
             (Value::F64(a), Value::F64(b)) => Some(a.partial_cmp(b)?),
+            (Value::Decimal(a), Value::Decimal(b)) => Some(a.partial_cmp(b)?),
             (Value::String(a), Value::String(b)) => Some(a.partial_cmp(b)?),
             (Value::Char(a), Value::Char(b)) => Some(a.partial_cmp(b)?),
             (Value::DateTime(a), Value::DateTime(b)) => Some(a.partial_cmp(b)?),
+            (Value::Date(a), Value::Date(b)) => Some(a.partial_cmp(b)?),
+            (Value::Duration(a), Value::Duration(b)) => Some(a.partial_cmp(b)?),
+            (Value::Uuid(a), Value::Uuid(b)) => Some(a.partial_cmp(b)?),
             _ => None,
         }
     }
@@ -451,6 +745,11 @@ impl PartialOrd for Value {
 
 impl Hash for Value {
     fn hash<H: Hasher>(&self, state: &mut H) {
+        // a decimal that equals an integer has the same hash
+        if let Some(integer) = integral(self) {
+            integer.hash(state);
+            return;
+        }
         std::mem::discriminant(self).hash(state);
 
         // Then hash the fields
@@ -461,18 +760,103 @@ impl Hash for Value {
             Value::U64(u64) => u64.hash(state),
             Value::F32(f32) => f32.to_bits().hash(state),
             Value::F64(f64) => f64.to_bits().hash(state),
+            Value::Decimal(d) => d.hash(state),
             Value::String(s) => s.hash(state),
             Value::Char(c) => c.hash(state),
             Value::Bool(b) => b.hash(state),
             Value::DateTime(d) => d.hash(state),
+            Value::Date(d) => d.hash(state),
+            Value::Duration(d) => d.hash(state),
+            Value::Uuid(u) => u.hash(state),
             Value::List(l) => l.hash(state),
             _ => {}
         }
     }
 }
 
+/// json representation, used for http responses
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::U32(v) => serializer.serialize_u32(*v),
+            Value::I32(v) => serializer.serialize_i32(*v),
+            Value::U64(v) => serializer.serialize_u64(*v),
+            Value::I64(v) => serializer.serialize_i64(*v),
+            Value::F32(v) => serializer.serialize_f32(*v),
+            Value::F64(v) => serializer.serialize_f64(*v),
+            Value::String(v) => serializer.serialize_str(v),
+            Value::Char(v) => serializer.serialize_char(*v),
+            Value::Bool(v) => serializer.serialize_bool(*v),
+            Value::List(list) => serializer.collect_seq(list.iter()),
+            Value::Map(map) => serializer.collect_map(map.iter().map(|(k, v)| (k.to_string(), v))),
+            Value::ObjectType(o) => serializer.collect_map(o.fields.iter().map(|(k, v)| (k, v))),
+            Value::Void => serializer.serialize_unit(),
+            // decimals, dates, uuids etc are serialized as strings
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
 // impl Ord for Value {
 //     fn cmp(&self, rhs: &Self) -> Ordering {
 //         self.partial_cmp(rhs).unwrap()
 //     }
 // }
+
+/// postgres parameter binding, decimals map to numeric
+impl ToSql for Value {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn std::error::Error + Sync + Send>> {
+        // the wrapped value checks whether it is compatible with the column type
+        match self {
+            Value::I32(v) => v.to_sql_checked(ty, out),
+            Value::I64(v) => v.to_sql_checked(ty, out),
+            Value::U32(v) => v.to_sql_checked(ty, out),
+            Value::F32(v) => v.to_sql_checked(ty, out),
+            Value::F64(v) => v.to_sql_checked(ty, out),
+            Value::Decimal(v) => v.to_sql_checked(ty, out),
+            Value::String(v) => v.as_ref().to_sql_checked(ty, out),
+            Value::Bool(v) => v.to_sql_checked(ty, out),
+            Value::Void => Ok(IsNull::Yes),
+            _ => Err(format!("cannot map {} to {}", self, ty).into()),
+        }
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+
+    to_sql_checked!();
+}
+
+/// postgres column values, numeric is read as a decimal
+impl<'a> FromSql<'a> for Value {
+    fn from_sql(
+        ty: &Type,
+        raw: &'a [u8],
+    ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(match *ty {
+            Type::INT4 => Value::I32(i32::from_sql(ty, raw)?),
+            Type::INT8 => Value::I64(i64::from_sql(ty, raw)?),
+            Type::OID => Value::U32(u32::from_sql(ty, raw)?),
+            Type::FLOAT4 => Value::F32(f32::from_sql(ty, raw)?),
+            Type::FLOAT8 => Value::F64(f64::from_sql(ty, raw)?),
+            Type::NUMERIC => Value::Decimal(Decimal::from_sql(ty, raw)?),
+            Type::BOOL => Value::Bool(bool::from_sql(ty, raw)?),
+            _ => Value::String(<&str>::from_sql(ty, raw)?.into()),
+        })
+    }
+
+    fn from_sql_null(_ty: &Type) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(Value::Void)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        [Type::INT4, Type::INT8, Type::OID, Type::FLOAT4, Type::FLOAT8, Type::NUMERIC, Type::BOOL]
+            .contains(ty)
+            || <String as FromSql>::accepts(ty)
+    }
+}
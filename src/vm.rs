@@ -1,31 +1,57 @@
 use crate::AsmRegistry;
-use crate::builtins::globals::GLOBAL_FUNCTIONS;
 use crate::compiler::assembly_pass::{AsmChunk, Op};
+use crate::compiler::link_pass::Link;
 use crate::compiler::tokens::TokenType;
 use crate::errors::{RuntimeError, ValueError};
-use crate::value::{Object, Value};
+use crate::value::{Closure, Object, Value, string};
 use arc_swap::Guard;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::debug;
 
 pub async fn interpret_async(
-    registry: Guard<Arc<HashMap<String, AsmChunk>>>,
+    registry: Guard<Arc<AsmRegistry>>,
     function: &str,
     uri: &str,
+    path_param: Option<&str>,
     query_params: HashMap<String, String>,
     headers: HashMap<String, String>,
 ) -> Result<Value, RuntimeError> {
     let chunk = registry.get(function);
     if let Some(chunk) = chunk {
         let mut vm = Vm::new(&registry);
-        vm.local_vars
-            .insert("path".to_string(), Value::String(uri.into()));
-        vm.local_vars
-            .insert("query".to_string(), Value::Map(value_map(query_params)));
-        vm.local_vars
-            .insert("headers".to_string(), Value::Map(value_map(headers)));
-        vm.run(&get_context(function), chunk)
+        let mut path_param = path_param;
+        // the arguments, in the slots of the parameters
+        let mut args = vec![];
+        // other declared parameters are taken from the query, or else from the last path segment
+        for parameter in &chunk.function_parameters {
+            let name = parameter.name.lexeme.as_str();
+            let injected = match name {
+                "path" => Some(Value::String(uri.into())),
+                "query" => Some(Value::Map(value_map(query_params.clone()).into())),
+                "headers" => Some(Value::Map(value_map(headers.clone()).into())),
+                _ => None,
+            };
+            if let Some(value) = injected {
+                args.push(value);
+                continue;
+            }
+            let text = query_params
+                .get(name)
+                .map(String::as_str)
+                .or_else(|| path_param.take())
+                .ok_or_else(|| {
+                    RuntimeError::IllegalParameter(format!("Missing parameter {}", name))
+                })?;
+            let value = parse_parameter(&parameter.var_type, text).ok_or_else(|| {
+                RuntimeError::IllegalParameter(format!(
+                    "Illegal value for parameter {}: expected {}, got '{}'",
+                    name, parameter.var_type, text
+                ))
+            })?;
+            args.push(value);
+        }
+        vm.run_function(chunk, args, vec![])
     } else {
         Err(RuntimeError::FunctionNotFound(function.to_string()))
     }
@@ -34,19 +60,19 @@ pub async fn interpret_async(
 pub fn interpret(registry: Guard<Arc<AsmRegistry>>, function: &str) -> Result<Value, RuntimeError> {
     let chunk = registry.get(function).unwrap().clone();
     let mut vm = Vm::new(&registry);
-    vm.run(&get_context(function), &chunk)
+    vm.run(&chunk)
 }
 
 pub fn interpret_function(chunk: &AsmChunk, args: Vec<Value>) -> Result<Value, RuntimeError> {
-    let mut vm = Vm::new(&Arc::new(HashMap::new()));
-    vm.run_function(chunk, args)
+    let mut vm = Vm::new(&Arc::new(AsmRegistry::new()));
+    vm.run_function(chunk, args, vec![])
 }
 
 pub(crate) struct Vm {
     ip: usize,
     stack: Vec<Value>,
-    local_vars: HashMap<String, Value>,
-    error_occurred: bool,
+    /// the variables of the running chunk, by the slot from the compiler
+    locals: Vec<Value>,
     pub(crate) registry: Arc<AsmRegistry>,
 }
 
@@ -55,8 +81,7 @@ impl Vm {
         Self {
             ip: 0,
             stack: vec![],
-            local_vars: HashMap::new(),
-            error_occurred: false,
+            locals: vec![],
             registry: registry.clone(),
         }
     }
@@ -64,48 +89,98 @@ impl Vm {
     fn run_function(
         &mut self,
         chunk: &AsmChunk,
-        mut args: Vec<Value>,
+        args: Vec<Value>,
+        captured: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        // arguments and captured values -> locals, parameters are the first slots
+        self.locals = args;
+        self.locals.resize(chunk.vars.len(), Value::Void);
+        for (slot, value) in chunk.captures.iter().zip(captured) {
+            self.locals[*slot] = value;
+        }
+        self.run(chunk)
+    }
+
+    // every call gets a fresh frame, that shares the registry
+    fn call(
+        &self,
+        chunk: &AsmChunk,
+        args: Vec<Value>,
+        captured: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        Vm::new(&self.registry).run_function(chunk, args, captured)
+    }
+
+    /// calls a function value, also used by builtins that take a function as argument
+    pub(crate) fn call_value(
+        &self,
+        function: Value,
+        args: Vec<Value>,
     ) -> Result<Value, RuntimeError> {
-        // arguments -> locals
-        for (_, name) in chunk.vars.iter() {
-            self.local_vars.insert(name.clone(), args.remove(0));
+        let Value::Function(closure) = function else {
+            return Err(RuntimeError::ExpectedType("function".to_string()));
+        };
+        let function_chunk = self
+            .registry
+            .get(&closure.name)
+            .ok_or_else(|| RuntimeError::FunctionNotFound(closure.name.clone()))?;
+        if function_chunk.function_parameters.len() != args.len() {
+            return Err(RuntimeError::IllegalArgumentsException(
+                closure.name,
+                function_chunk.function_parameters.len(),
+                args.len(),
+            ));
         }
-        self.run("", chunk)
+        self.call(function_chunk, args, closure.captured)
     }
 
-    pub(crate) fn run(&mut self, context: &str, chunk: &AsmChunk) -> Result<Value, RuntimeError> {
+    pub(crate) fn run(&mut self, chunk: &AsmChunk) -> Result<Value, RuntimeError> {
         self.ip = 0;
+        // the REPL runs main again with the variables of the earlier lines
+        if self.locals.len() < chunk.vars.len() {
+            self.locals.resize(chunk.vars.len(), Value::Void);
+        }
         loop {
             let opcode = &chunk.code[self.ip];
+            let line = chunk.line(self.ip);
             self.ip += 1;
             match opcode {
                 Op::Constant(c) => {
                     let value = &chunk.constants[*c];
                     self.push(value.clone());
                 }
-                Op::Add => binary_op(self, |a, b| a + b),
-                Op::Subtract => binary_op(self, |a, b| a - b),
-                Op::Multiply => binary_op(self, |a, b| a * b),
-                Op::Divide => binary_op(self, |a, b| a / b),
-                Op::And => binary_op(self, |a, b| {
+                Op::Add => binary_op(self, line, |a, b| a + b)?,
+                Op::Subtract => binary_op(self, line, |a, b| a - b)?,
+                Op::Multiply => binary_op(self, line, |a, b| a * b)?,
+                Op::Divide => binary_op(self, line, |a, b| a / b)?,
+                Op::Cast(target_type) => {
+                    let value = self.pop();
+                    let converted = value.convert(target_type).ok_or_else(|| {
+                        RuntimeError::LossyConversion(value.to_string(), target_type.clone(), line)
+                    })?;
+                    self.push(converted);
+                }
+                Op::Remainder => binary_op(self, line, |a, b| a % b)?,
+                Op::Power => binary_op(self, line, |a, b| a.pow(b))?,
+                Op::And => binary_op(self, line, |a, b| {
                     if let (Value::Bool(a), Value::Bool(b)) = (a, b) {
                         Ok(Value::Bool(*a && *b))
                     } else {
                         Err(ValueError::Some("Cannot and"))
                     }
-                }),
-                Op::Or => binary_op(self, |a, b| {
+                })?,
+                Op::Or => binary_op(self, line, |a, b| {
                     if let (Value::Bool(a), Value::Bool(b)) = (a, b) {
                         Ok(Value::Bool(*a || *b))
                     } else {
                         Err(ValueError::Some("Cannot compare"))
                     }
-                }),
-                Op::Not => unary_op(self, |a| !a),
-                Op::BitAnd => binary_op(self, |a, b| a & b),
-                Op::BitOr => binary_op(self, |a, b| a | b),
-                Op::BitXor => binary_op(self, |a, b| a ^ b),
-                Op::Negate => unary_op(self, |a| -a),
+                })?,
+                Op::Not => unary_op(self, line, |a| !a)?,
+                Op::BitAnd => binary_op(self, line, |a, b| a & b)?,
+                Op::BitOr => binary_op(self, line, |a, b| a | b)?,
+                Op::BitXor => binary_op(self, line, |a, b| a ^ b)?,
+                Op::Negate => unary_op(self, line, |a| -a)?,
                 Op::Return => {
                     return if self.stack.is_empty() {
                         Ok(Value::Void)
@@ -113,14 +188,14 @@ impl Vm {
                         Ok(self.pop())
                     };
                 }
-                Op::Shl => binary_op(self, |a, b| a << b),
-                Op::Shr => binary_op(self, |a, b| a >> b),
-                Op::Equal => binary_op(self, |a, b| Ok(Value::Bool(a == b))),
-                Op::Greater => binary_op(self, |a, b| Ok(Value::Bool(a > b))),
-                Op::GreaterEqual => binary_op(self, |a, b| Ok(Value::Bool(a >= b))),
-                Op::Less => binary_op(self, |a, b| Ok(Value::Bool(a < b))),
-                Op::LessEqual => binary_op(self, |a, b| Ok(Value::Bool(a <= b))),
-                Op::NotEqual => binary_op(self, |a, b| Ok(Value::Bool(a != b))),
+                Op::Shl => binary_op(self, line, |a, b| a << b)?,
+                Op::Shr => binary_op(self, line, |a, b| a >> b)?,
+                Op::Equal => binary_op(self, line, |a, b| Ok(Value::Bool(a == b)))?,
+                Op::Greater => binary_op(self, line, |a, b| Ok(Value::Bool(a > b)))?,
+                Op::GreaterEqual => binary_op(self, line, |a, b| Ok(Value::Bool(a >= b)))?,
+                Op::Less => binary_op(self, line, |a, b| Ok(Value::Bool(a < b)))?,
+                Op::LessEqual => binary_op(self, line, |a, b| Ok(Value::Bool(a <= b)))?,
+                Op::NotEqual => binary_op(self, line, |a, b| Ok(Value::Bool(a != b)))?,
                 Op::Print => {
                     debug!("print {:?}", self.stack);
                     let v = self.pop();
@@ -133,13 +208,11 @@ impl Vm {
                         list.push(value);
                     }
                     list.reverse();
-                    self.push(Value::List(list));
+                    self.push(Value::List(list.into()));
                 }
-                Op::Assign(var_index) => {
-                    let (var_type, name) = chunk.vars.get(*var_index).unwrap();
+                Op::Assign(slot) => {
                     let value = self.pop();
-                    let value = number(var_type, value)?;
-                    self.local_vars.insert(name.to_string(), value); //insert or update
+                    self.locals[*slot] = number(&chunk.vars[*slot].0, value, line)?;
                 }
                 Op::DefMap(len) => {
                     let mut map = HashMap::new();
@@ -148,84 +221,194 @@ impl Vm {
                         let key = self.pop();
                         map.insert(key, value);
                     }
-                    self.push(Value::Map(map));
+                    self.push(Value::Map(map.into()));
+                }
+                Op::Get(slot) => {
+                    let value = self.locals[*slot].clone();
+                    self.push(value);
                 }
-                Op::Get(var_index) => {
-                    let (_, name_index) = chunk.vars.get(*var_index).unwrap();
-                    let value = self.local_vars.get(name_index).unwrap().clone();
+                Op::Take(slot) => {
+                    let value = std::mem::replace(&mut self.locals[*slot], Value::Void);
                     self.push(value);
                 }
                 Op::ListGet => {
+                    let index = self.pop();
+                    let value = match self.pop() {
+                        Value::List(list) => list[index_from_end(&index, list.len())?].clone(),
+                        Value::String(s) => {
+                            let index = index_from_end(&index, s.chars().count())?;
+                            Value::Char(s.chars().nth(index).unwrap())
+                        }
+                        _ => return Err(RuntimeError::ExpectedType("list".to_string())),
+                    };
+                    self.push(value)
+                }
+                Op::ListSet => {
                     let index = self.pop();
                     let list = self.pop();
-                    if let Value::List(list) = list {
-                        self.push(list.get(index.cast_usize()?).cloned().unwrap())
+                    let value = self.pop();
+                    match list {
+                        Value::List(mut list) => {
+                            let index = index_from_end(&index, list.len())?;
+                            // copies the list only when it is shared
+                            Arc::make_mut(&mut list)[index] = value;
+                            self.push(Value::List(list));
+                        }
+                        _ => return Err(RuntimeError::ExpectedType("list".to_string())),
+                    }
+                }
+                Op::MapSet => {
+                    let key = self.pop();
+                    let map = self.pop();
+                    let value = self.pop();
+                    match map {
+                        Value::Map(mut map) => {
+                            Arc::make_mut(&mut map).insert(key, value);
+                            self.push(Value::Map(map));
+                        }
+                        _ => return Err(RuntimeError::ExpectedType("map".to_string())),
+                    }
+                }
+                Op::FieldGet(name_index) => {
+                    let field = chunk.constants[*name_index].to_string();
+                    let value = match self.pop() {
+                        Value::ObjectType(object) => object
+                            .fields
+                            .into_iter()
+                            .find(|(name, _)| *name == field)
+                            .map(|(_, value)| value)
+                            .ok_or(RuntimeError::KeyNotFound(field))?,
+                        _ => return Err(RuntimeError::ExpectedType("object".to_string())),
+                    };
+                    self.push(value);
+                }
+                Op::FieldSet(name_index) => {
+                    let field = chunk.constants[*name_index].to_string();
+                    let object = self.pop();
+                    let value = self.pop();
+                    match object {
+                        Value::ObjectType(mut object) => {
+                            let (_, old) = object
+                                .fields
+                                .iter_mut()
+                                .find(|(name, _)| *name == field)
+                                .ok_or(RuntimeError::KeyNotFound(field))?;
+                            *old = value;
+                            self.push(Value::ObjectType(object));
+                        }
+                        _ => return Err(RuntimeError::ExpectedType("object".to_string())),
+                    }
+                }
+                Op::Slice(inclusive) => {
+                    let upper = self.pop();
+                    let lower = self.pop();
+                    let value = match self.pop() {
+                        Value::List(list) => {
+                            let range = slice_range(&lower, &upper, *inclusive, list.len())?;
+                            Value::List(list[range].to_vec().into())
+                        }
+                        Value::String(s) => {
+                            let range = slice_range(&lower, &upper, *inclusive, s.chars().count())?;
+                            let sliced = s.chars().skip(range.start).take(range.len());
+                            string(sliced.collect::<String>())
+                        }
+                        _ => return Err(RuntimeError::ExpectedType("list or string".to_string())),
+                    };
+                    self.push(value)
+                }
+                Op::MapGet => {
+                    let key = self.pop();
+                    let map = self.pop();
+                    if let Value::Map(map) = map {
+                        let value = map
+                            .get(&key)
+                            .cloned()
+                            .ok_or_else(|| RuntimeError::KeyNotFound(key.to_string()))?;
+                        self.push(value)
                     }
                 }
-                Op::CallBuiltin(function_name_index, function_type_index, num_args) => {
+                // calls are linked before they run
+                Op::Call(function_name_index, _) => {
                     let function_name = chunk.constants[*function_name_index].to_string();
-                    let receiver_type_name = chunk.constants[*function_type_index].to_string();
-
+                    return Err(RuntimeError::FunctionNotFound(function_name));
+                }
+                Op::CallBuiltin(function_name_index, function_type_index, _) => {
+                    return Err(RuntimeError::FunctionNotFound(format!(
+                        "{}.{}",
+                        chunk.constants[*function_type_index], chunk.constants[*function_name_index]
+                    )));
+                }
+                Op::CallLinked(link_index, num_args) => {
                     let mut args = vec![];
                     for _ in 0..*num_args {
                         let arg = self.pop();
                         args.push(arg);
                     }
                     args.reverse();
-                    let receiver = self.pop();
-                    let return_value =
-                        crate::builtins::call(&receiver_type_name, &function_name, receiver, args)?;
+
+                    let return_value = match &chunk.links[*link_index].1 {
+                        Link::Function(index) => {
+                            let (_, function_chunk) = self.registry.get_index(*index).unwrap();
+                            self.call(function_chunk, args, vec![])?
+                        }
+                        Link::Global(function) => (function.function)(self, Value::Void, args)?,
+                        Link::Method(method) => {
+                            let receiver = self.pop();
+                            (method.function)(self, receiver, args)?
+                        }
+                        Link::Constructor(function_name, params) => {
+                            if params.len() != args.len() {
+                                return Err(RuntimeError::IllegalArgumentsException(
+                                    function_name.clone(),
+                                    params.len(),
+                                    args.len(),
+                                ));
+                            }
+
+                            let mut fields = vec![];
+                            params.iter().zip(args).for_each(|(param, arg)| {
+                                fields.push((param.name.lexeme.clone(), arg))
+                            });
+                            Value::ObjectType(Box::new(Object {
+                                definition: function_name.clone(),
+                                fields,
+                            }))
+                        }
+                    };
                     self.push(return_value);
                 }
                 Op::Pop => {
                     self.pop();
                 }
-                Op::Call(function_name_index, num_args) => {
+                Op::Closure(function_name_index, num_captured) => {
+                    let mut captured = vec![];
+                    for _ in 0..*num_captured {
+                        captured.push(self.pop());
+                    }
+                    captured.reverse();
+                    let name = chunk.constants[*function_name_index].to_string();
+                    self.push(Value::Function(Box::new(Closure { name, captured })));
+                }
+                Op::CallValue(num_args) => {
                     let mut args = vec![];
                     for _ in 0..*num_args {
-                        let arg = self.pop();
-                        args.push(arg);
+                        args.push(self.pop());
                     }
                     args.reverse();
-
-                    let function_name = chunk.constants[*function_name_index].to_string();
-                    if let Some(fun) = GLOBAL_FUNCTIONS.get(&function_name) {
-                        let return_value = (fun.function)(Value::Void, args)?;
-                        self.push(return_value);
-                    } else {
-                        let function_chunk = self.registry.get(&function_name).or_else(|| {
-                            self.registry.get(&format!("{}/{}", context, function_name))
-                        });
-
-                        if function_chunk.is_none() {
-                            let constructor = chunk.object_defs.get(&function_name);
-
-                            if let Some(params) = constructor {
-                                if params.len() != args.len() {
-                                    return Err(RuntimeError::IllegalArgumentsException(
-                                        function_name,
-                                        params.len(),
-                                        args.len(),
-                                    ));
-                                }
-
-                                let mut fields = vec![];
-                                params.iter().zip(args).for_each(|(param, arg)| {
-                                    fields.push((param.name.lexeme.clone(), arg))
-                                });
-                                let new_instance = Value::ObjectType(Box::new(Object {
-                                    definition: function_name,
-                                    fields,
-                                }));
-                                self.push(new_instance);
-                            } else {
-                                return Err(RuntimeError::FunctionNotFound(function_name));
-                            }
-                        } else {
-                            let result = interpret_function(function_chunk.unwrap(), args)?;
-                            self.push(result);
-                        }
+                    let function = self.pop();
+                    let result = self.call_value(function, args)?;
+                    self.push(result);
+                }
+                Op::Concat(num_parts) => {
+                    let mut parts = vec![];
+                    for _ in 0..*num_parts {
+                        parts.push(self.pop());
+                    }
+                    let mut concatenated = String::new();
+                    for part in parts.iter().rev() {
+                        concatenated.push_str(&part.to_string());
                     }
+                    self.push(Value::String(concatenated.into()));
                 }
                 Op::GotoIfNot(goto_addr) => {
                     let b = self.pop();
@@ -262,51 +445,154 @@ impl Vm {
     }
 }
 
-fn binary_op(vm: &mut Vm, op: impl Fn(&Value, &Value) -> Result<Value, ValueError> + Copy) {
+fn binary_op(
+    vm: &mut Vm,
+    line: usize,
+    op: impl Fn(&Value, &Value) -> Result<Value, ValueError> + Copy,
+) -> Result<(), RuntimeError> {
     let b = vm.pop();
     let a = vm.pop();
+    let result = op(&a, &b).map_err(|e| at_line(e, line))?;
+    vm.push(result);
+    Ok(())
+}
 
-    let result = op(&a, &b);
-    match result {
-        Ok(result) => vm.push(result),
-        Err(e) => {
-            vm.error_occurred = true;
-            println!("Error: {} {:?} and {:?}", e, a, b);
-        }
+fn unary_op(
+    vm: &mut Vm,
+    line: usize,
+    op: impl Fn(&Value) -> Result<Value, ValueError> + Copy,
+) -> Result<(), RuntimeError> {
+    let a = vm.pop();
+    let result = op(&a).map_err(|e| at_line(e, line))?;
+    vm.push(result);
+    Ok(())
+}
+
+// arithmetic errors report where they happened
+fn at_line(error: ValueError, line: usize) -> RuntimeError {
+    match error {
+        ValueError::Overflow => RuntimeError::Overflow(line),
+        ValueError::DivisionByZero => RuntimeError::DivisionByZero(line),
+        other => RuntimeError::ValueError(other),
+    }
+}
+
+// negative indexes count from the end
+fn index(value: &Value, len: usize) -> Result<usize, RuntimeError> {
+    let index = match value {
+        Value::I32(i) => *i as i64,
+        Value::I64(i) => *i,
+        other => return Ok(other.clone().cast_usize()?),
+    };
+    if index < 0 {
+        len.checked_sub(index.unsigned_abs() as usize)
+            .ok_or(RuntimeError::IndexOutOfBounds(index.unsigned_abs() as usize, len))
+    } else {
+        Ok(index as usize)
     }
 }
 
-fn unary_op(stack: &mut Vm, op: impl Fn(&Value) -> Result<Value, ValueError> + Copy) {
-    let a = stack.pop();
-    let result = op(&a);
-    match result {
-        Ok(result) => stack.push(result),
-        Err(e) => panic!("Error: {:?} {:?}", e, a),
+fn index_from_end(value: &Value, len: usize) -> Result<usize, RuntimeError> {
+    let index = index(value, len)?;
+    if index >= len {
+        return Err(RuntimeError::IndexOutOfBounds(index, len));
     }
+    Ok(index)
 }
 
-pub(crate) fn get_context(path: &str) -> String {
-    let mut parts: Vec<&str> = path.split('/').collect();
-    if parts.len() >= 2 {
-        parts.truncate(parts.len() - 2);
+// void bounds default to the start and end
+fn slice_range(
+    lower: &Value,
+    upper: &Value,
+    inclusive: bool,
+    len: usize,
+) -> Result<std::ops::Range<usize>, RuntimeError> {
+    let start = match lower {
+        Value::Void => 0,
+        lower => index(lower, len)?,
+    };
+    let end = match upper {
+        Value::Void => len,
+        upper if inclusive => index(upper, len)? + 1,
+        upper => index(upper, len)?,
+    };
+    if end > len {
+        return Err(RuntimeError::IndexOutOfBounds(end, len));
+    }
+    if start > end {
+        return Err(RuntimeError::IndexOutOfBounds(start, end));
     }
-    parts.join("/")
+    Ok(start..end)
 }
 
-fn number(var_type: &TokenType, value: Value) -> Result<Value, RuntimeError> {
+// a value that does not fit the declared type fails like `as` does
+fn number(var_type: &TokenType, value: Value, line: usize) -> Result<Value, RuntimeError> {
     let value = match var_type {
-        TokenType::U32 => value.cast_u32()?,
-        TokenType::U64 => value.cast_u64()?,
-        TokenType::F32 => value.cast_f32()?,
-        TokenType::I32 => value.cast_i32()?,
+        TokenType::U32
+        | TokenType::U64
+        | TokenType::F32
+        | TokenType::I32
+        | TokenType::Decimal => value.convert(var_type).ok_or_else(|| {
+            RuntimeError::LossyConversion(value.to_string(), var_type.clone(), line)
+        })?,
+        // the elements are only converted when their type needs it, so that the list is not copied
+        TokenType::ListType(element_type) => match value {
+            Value::List(list) if converts(element_type) => Value::List(
+                Arc::unwrap_or_clone(list)
+                    .into_iter()
+                    .map(|element| number(element_type, element, line))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into(),
+            ),
+            _ => value,
+        },
+        TokenType::MapType(key_type, value_type) => match value {
+            Value::Map(map) if converts(key_type) || converts(value_type) => Value::Map(
+                Arc::unwrap_or_clone(map)
+                    .into_iter()
+                    .map(|(key, value)| {
+                        Ok((number(key_type, key, line)?, number(value_type, value, line)?))
+                    })
+                    .collect::<Result<HashMap<_, _>, RuntimeError>>()?
+                    .into(),
+            ),
+            _ => value,
+        },
         _ => value,
     };
     Ok(value)
 }
 
+// whether number changes values of the type
+fn converts(var_type: &TokenType) -> bool {
+    match var_type {
+        TokenType::U32 | TokenType::U64 | TokenType::F32 | TokenType::I32 => true,
+        TokenType::Decimal => true,
+        TokenType::ListType(element_type) => converts(element_type),
+        TokenType::MapType(key_type, value_type) => converts(key_type) || converts(value_type),
+        _ => false,
+    }
+}
+
+fn parse_parameter(var_type: &TokenType, text: &str) -> Option<Value> {
+    Some(match var_type {
+        TokenType::StringType => Value::String(text.into()),
+        TokenType::Uuid => Value::Uuid(uuid::Uuid::parse_str(text).ok()?),
+        TokenType::U32 => Value::U32(text.parse().ok()?),
+        TokenType::U64 => Value::U64(text.parse().ok()?),
+        TokenType::I32 => Value::I32(text.parse().ok()?),
+        TokenType::I64 => Value::I64(text.parse().ok()?),
+        TokenType::F32 => Value::F32(text.parse().ok()?),
+        TokenType::F64 => Value::F64(text.parse().ok()?),
+        TokenType::Decimal => Value::Decimal(text.parse().ok()?),
+        TokenType::Bool => Value::Bool(text.parse().ok()?),
+        _ => return None,
+    })
+}
+
 fn value_map(strings: HashMap<String, String>) -> HashMap<Value, Value> {
     strings
         .into_iter()
-        .map(|(k, v)| (Value::String(k.to_string()), Value::String(v.to_string())))
+        .map(|(k, v)| (Value::String(k.into()), Value::String(v.into())))
         .collect()
 }